@@ -3,4 +3,65 @@ pub const U64_L: usize = 8;
 pub const U16_L: usize = 2;
 pub const BOOL_L: usize = 1;
 pub const OPTION_L: usize = 1;
-pub const U8_L: usize = 1;
\ No newline at end of file
+pub const U8_L: usize = 1;
+pub const U128_L: usize = 16;
+pub const U32_L: usize = 4;
+pub const I32_L: usize = 4;
+pub const I64_L: usize = 8;
+pub const VEC_L: usize = 4;
+
+// Maximum amplification coefficient accepted for StableSwap pools
+pub const MAX_AMP: u64 = 1_000_000;
+
+// Minimum weight (in basis points) of each token in a Weighted pool
+pub const MIN_WEIGHT: u16 = 200;
+
+// Maximum tick spacing accepted for Concentrated pools
+pub const MAX_TICK_SPACING: u16 = 1_000;
+
+// Minimum delay (in seconds) before a fee change applies, 1 hour
+pub const MIN_FEE_DELAY: i64 = 60 * 60;
+
+// Maximum delay (in seconds) a pool can set before a fee change applies, 30 days
+pub const MAX_FEE_DELAY: i64 = 30 * 24 * 60 * 60;
+
+// Pause bits of Config.paused, each one stops a single kind of operation
+pub const PAUSE_SWAP: u8 = 1 << 0;
+pub const PAUSE_DEPOSIT: u8 = 1 << 1;
+pub const PAUSE_WITHDRAW: u8 = 1 << 2;
+pub const PAUSE_ALL: u8 = PAUSE_SWAP | PAUSE_DEPOSIT | PAUSE_WITHDRAW;
+
+// Maximum number of observations the oracle ring buffer can hold
+pub const MAX_OBSERVATIONS: u16 = 1_000;
+
+// Maximum number of transfer hook programs a pool can allow
+pub const MAX_ALLOWED_HOOKS: usize = 4;
+
+// Discriminator of the instruction called on the borrower program during a flash swap,
+// the Anchor sighash of `flash_swap_callback`
+pub const FLASH_SWAP_CALLBACK: [u8; 8] = [225, 54, 80, 1, 45, 208, 202, 124];
+
+// Maximum number of pools a routed swap can go through
+pub const MAX_ROUTE_HOPS: u8 = 4;
+
+// Maximum number of pools a split swap can use
+pub const MAX_SPLIT_POOLS: usize = 4;
+
+// Precision of the spot prices given by the curves, 6 decimals
+pub const SPOT_PRICE_PRECISION: u128 = 1_000_000;
+
+// Long-term orders end on multiples of an interval which starts at this many slots (~1 minute) and
+// doubles until the order fits in TWAMM_INTERVALS_PER_ORDER of them. Far expiries are coarser, so
+// orders share them and a pool only has a bounded number at a time
+pub const TWAMM_INTERVAL: u64 = 150;
+pub const TWAMM_INTERVALS_PER_ORDER: u64 = 4;
+
+// Maximum duration of a long-term order in slots, ~30 days
+pub const MAX_TWAMM_SLOTS: u64 = 6_480_000;
+
+// Maximum number of distinct expiries with open long-term orders in a pool. The expiry intervals
+// allow less than this many at a time, so dust orders can't fill them
+pub const MAX_TWAMM_EXPIRIES: usize = 64;
+
+// Precision of the TWAMM earnings factors, tokens bought per unit of Q64.64 sell rate
+pub const TWAMM_EARNINGS_PRECISION: u128 = 1 << 96;
@@ -0,0 +1,112 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    has_update_authority, Config, PoolRegistry,
+    errors::AmmError,
+};
+
+/// Apply Fee Context
+///
+/// The registry entry of a pool is derived from its fee tier, so applying a new fee moves the
+/// entry to the new tier too
+#[derive(Accounts)]
+pub struct ApplyFee<'info> {
+    // The update authority, it pays the new entry and gets the rent of the old one
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+        constraint = config.pending_fee.is_some() @ AmmError::NoPendingFee,
+    )]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: entry of the current fee tier. It's only closed when it belongs to this pool, pools
+    /// moved from the legacy vaults may not have one
+    #[account(
+        mut,
+        seeds = [b"pool", config.x_mint.as_ref(), config.y_mint.as_ref(), config.fee.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub registry: UncheckedAccount<'info>,
+
+    // Entry of the new fee tier. As it's created with init, the fee can't move to a tier which
+    // already has a pool for the pair. Anchor creates it before checking the config constraints,
+    // so the seeds fail on their own when there is no pending fee
+    #[account(
+        init,
+        payer = payer,
+        seeds = [
+            b"pool",
+            config.x_mint.as_ref(),
+            config.y_mint.as_ref(),
+            config.pending_fee.ok_or(AmmError::NoPendingFee)?.to_le_bytes().as_ref(),
+        ],
+        bump,
+        space = PoolRegistry::INIT_SPACE
+    )]
+    pub new_registry: Box<Account<'info, PoolRegistry>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ApplyFee<'info> {
+    /// Apply Fee
+    ///
+    /// Replace the fee with the pending one once its delay is over
+    pub fn apply_fee(&mut self, bumps: &ApplyFeeBumps) -> Result<()> {
+        has_update_authority!(self);
+
+        let new_fee = self.config.pending_fee.ok_or(AmmError::NoPendingFee)?;
+        require!(
+            Clock::get()?.unix_timestamp >= self.config.fee_eta,
+            AmmError::FeeDelayNotElapsed
+        );
+
+        self.close_registry()?;
+
+        self.new_registry.init(
+            self.config.key(),
+            self.config.x_mint,
+            self.config.y_mint,
+            new_fee,
+            bumps.new_registry,
+        );
+
+        self.config.fee = new_fee;
+        self.config.pending_fee = None;
+        self.config.fee_eta = 0;
+
+        Ok(())
+    }
+
+    /// Close Registry
+    ///
+    /// Helper function to close the entry of the old fee tier, unless it doesn't exist or it
+    /// belongs to another pool
+    fn close_registry(&self) -> Result<()> {
+        if self.registry.owner != &crate::ID {
+            return Ok(());
+        }
+
+        let registry = PoolRegistry::try_deserialize(&mut &self.registry.data.borrow()[..])?;
+
+        if registry.config != self.config.key() {
+            return Ok(());
+        }
+
+        // Same as the `close` constraint, the rent goes to the payer and the account back to the system program
+        let info = self.registry.to_account_info();
+        let payer = self.payer.to_account_info();
+        let lamports = payer.lamports().checked_add(info.lamports()).ok_or(AmmError::Overflow)?;
+        **payer.lamports.borrow_mut() = lamports;
+        **info.lamports.borrow_mut() = 0;
+
+        info.assign(&System::id());
+        info.realloc(0, false)?;
+
+        Ok(())
+    }
+}
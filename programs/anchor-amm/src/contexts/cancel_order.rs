@@ -0,0 +1,146 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{close_account, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    Config, Order,
+    errors::AmmError,
+    events::OrderCancelled,
+    helpers::token::{has_withheld_fees, transfer_checked_with_hook, transfer_hook_program},
+};
+
+#[derive(Accounts)]
+pub struct CancelOrder<'info> {
+    // Only the owner can cancel the order, the escrow, the bounty and the rent go back to it
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        close = payer,
+        has_one = config,
+        has_one = input_mint,
+        constraint = order.owner == payer.key() @ AmmError::InvalidAuthority,
+        seeds = [
+            b"order",
+            config.key().as_ref(),
+            payer.key().as_ref(),
+            order.id.to_le_bytes().as_ref(),
+        ],
+        bump = order.bump,
+    )]
+    pub order: Box<Account<'info, Order>>,
+
+    #[account(mint::token_program = input_token_program)]
+    pub input_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        associated_token::mint = input_mint,
+        associated_token::token_program = input_token_program,
+        associated_token::authority = order,
+    )]
+    pub escrow: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = input_mint,
+        associated_token::token_program = input_token_program,
+        associated_token::authority = payer,
+    )]
+    pub input_user_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub input_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CancelOrder<'info> {
+    /// Cancel Order
+    ///
+    /// Return the escrow to the owner and close the order. It works any time, even if the pool is locked
+    pub fn cancel_order(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let amount = self.escrow.amount;
+
+        if amount > 0 {
+            self.withdraw_tokens(amount, remaining_accounts)?;
+        }
+        self.close_escrow()?;
+
+        emit!(OrderCancelled {
+            config: self.config.key(),
+            order: self.order.key(),
+            owner: self.payer.key(),
+            amount_in: amount,
+        });
+
+        Ok(())
+    }
+
+    /// Withdraw Tokens
+    ///
+    /// Helper function to send the escrow back to the owner, signed by the order
+    fn withdraw_tokens(&self, amount: u64, extra_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let cpi_accounts = TransferChecked {
+            from: self.escrow.to_account_info(),
+            mint: self.input_mint.to_account_info(),
+            to: self.input_user_ata.to_account_info(),
+            authority: self.order.to_account_info(),
+        };
+
+        let config_key = self.config.key();
+        let owner_key = self.payer.key();
+        let id = self.order.id.to_le_bytes();
+        let seeds = &[&b"order"[..], config_key.as_ref(), owner_key.as_ref(), id.as_ref(), &[self.order.bump]];
+        let signer_seeds = &[&seeds[..]];
+
+        let ctx = CpiContext::new_with_signer(
+            self.input_token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+
+        // The owner gets its own tokens back, so the pool allowlist doesn't apply. Otherwise removing
+        // a hook from it would lock the escrow
+        let hook_program = transfer_hook_program(&self.input_mint.to_account_info())?;
+
+        transfer_checked_with_hook(ctx, amount, self.input_mint.decimals, extra_accounts, hook_program.as_slice())
+    }
+
+    /// Close Escrow
+    ///
+    /// Helper function to give the escrow rent back to the owner. Escrows holding withheld
+    /// transfer fees can't be closed and are left behind
+    fn close_escrow(&self) -> Result<()> {
+        if has_withheld_fees(&self.escrow.to_account_info())? {
+            return Ok(());
+        }
+
+        let cpi_accounts = CloseAccount {
+            account: self.escrow.to_account_info(),
+            destination: self.payer.to_account_info(),
+            authority: self.order.to_account_info(),
+        };
+
+        let config_key = self.config.key();
+        let owner_key = self.payer.key();
+        let id = self.order.id.to_le_bytes();
+        let seeds = &[&b"order"[..], config_key.as_ref(), owner_key.as_ref(), id.as_ref(), &[self.order.bump]];
+        let signer_seeds = &[&seeds[..]];
+
+        close_account(CpiContext::new_with_signer(
+            self.input_token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        ))
+    }
+}
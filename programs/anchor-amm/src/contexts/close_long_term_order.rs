@@ -0,0 +1,189 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    Config, LongTermOrder, Twamm,
+    errors::AmmError,
+    events::LongTermOrderClosed,
+    helpers::token::transfer_checked_with_hook,
+};
+
+#[derive(Accounts)]
+pub struct CloseLongTermOrder<'info> {
+    // Only the owner can close the order, it gets the proceeds, the unsold input and the rent
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(mint::token_program = x_token_program)]
+    pub x_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = y_token_program)]
+    pub y_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        associated_token::mint = x_mint,
+        associated_token::token_program = x_token_program,
+        associated_token::authority = auth,
+    )]
+    pub x_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = y_mint,
+        associated_token::token_program = y_token_program,
+        associated_token::authority = auth,
+    )]
+    pub y_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = x_mint,
+        associated_token::token_program = x_token_program,
+        associated_token::authority = payer,
+    )]
+    pub x_user_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = y_mint,
+        associated_token::token_program = y_token_program,
+        associated_token::authority = payer,
+    )]
+    pub y_user_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        has_one = x_mint,
+        has_one = y_mint,
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        has_one = config,
+        seeds = [b"twamm", config.key().as_ref()],
+        bump = twamm.bump,
+    )]
+    pub twamm: Box<Account<'info, Twamm>>,
+
+    #[account(
+        mut,
+        close = payer,
+        has_one = config,
+        constraint = order.owner == payer.key() @ AmmError::InvalidAuthority,
+        seeds = [
+            b"long_term_order",
+            config.key().as_ref(),
+            payer.key().as_ref(),
+            order.id.to_le_bytes().as_ref(),
+        ],
+        bump = order.bump,
+    )]
+    pub order: Box<Account<'info, LongTermOrder>>,
+
+    /// CHECK: just a pda for signing
+    #[account(
+        seeds = [b"auth", config.key().as_ref()],
+        bump = config.auth_bump,
+    )]
+    pub auth: UncheckedAccount<'info>,
+
+    pub x_token_program: Interface<'info, TokenInterface>,
+    pub y_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CloseLongTermOrder<'info> {
+    /// Close Long Term Order
+    ///
+    /// Send the owner what the order bought and, before its expiry, the input it didn't sell. It works
+    /// any time, while swaps are paused the order is settled up to the last virtual trade
+    pub fn close_long_term_order(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        self.config.check_idle()?;
+
+        self.twamm.execute(&mut self.config, self.x_vault.amount, self.y_vault.amount)?;
+
+        let (proceeds, unsold) = self.twamm.close_order(&self.order)?;
+
+        let is_x_to_y = self.order.is_x_to_y;
+        let (amount_x, amount_y) = match is_x_to_y {
+            true => (unsold, proceeds),
+            false => (proceeds, unsold),
+        };
+
+        // Rounding can leave the orders a few units short, they never take from the LPs
+        let amount_x = amount_x.min(self.config.twamm_balance_x);
+        let amount_y = amount_y.min(self.config.twamm_balance_y);
+        self.config.twamm_balance_x -= amount_x;
+        self.config.twamm_balance_y -= amount_y;
+
+        if amount_x > 0 {
+            self.withdraw_tokens(true, amount_x, remaining_accounts)?;
+        }
+        if amount_y > 0 {
+            self.withdraw_tokens(false, amount_y, remaining_accounts)?;
+        }
+
+        self.config.touch()?;
+
+        let (amount_out, amount_unsold) = match is_x_to_y {
+            true => (amount_y, amount_x),
+            false => (amount_x, amount_y),
+        };
+
+        emit!(LongTermOrderClosed {
+            config: self.config.key(),
+            order: self.order.key(),
+            owner: self.payer.key(),
+            amount_out,
+            amount_unsold,
+        });
+
+        Ok(())
+    }
+
+    /// Withdraw Tokens
+    ///
+    /// Helper function to send tokens (X or Y) from the vault to the owner, signed by `auth`
+    fn withdraw_tokens(&self, is_x: bool, amount: u64, extra_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let (from, to, mint, token_program) = match is_x {
+            true => (
+                self.x_vault.to_account_info(),
+                self.x_user_ata.to_account_info(),
+                &self.x_mint,
+                &self.x_token_program,
+            ),
+            false => (
+                self.y_vault.to_account_info(),
+                self.y_user_ata.to_account_info(),
+                &self.y_mint,
+                &self.y_token_program,
+            ),
+        };
+
+        let cpi_accounts = TransferChecked {
+            from,
+            mint: mint.to_account_info(),
+            to,
+            authority: self.auth.to_account_info(),
+        };
+
+        let config_key = self.config.key();
+        let seeds = &[&b"auth"[..], config_key.as_ref(), &[self.config.auth_bump]];
+        let signer_seeds = &[&seeds[..]];
+
+        let ctx = CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+
+        transfer_checked_with_hook(ctx, amount, mint.decimals, extra_accounts, &self.config.allowed_hooks)
+    }
+}
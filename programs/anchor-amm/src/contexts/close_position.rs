@@ -0,0 +1,43 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    Config, Position,
+    errors::AmmError,
+};
+
+#[derive(Accounts)]
+pub struct ClosePosition<'info> {
+    // Only the owner can close the position, the rent goes back to it
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        close = payer,
+        has_one = config,
+        constraint = position.owner == payer.key() @ AmmError::InvalidAuthority,
+    )]
+    pub position: Account<'info, Position>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ClosePosition<'info> {
+    pub fn close_position(&mut self) -> Result<()> {
+        // Liquidity and fees have to be withdrawn before closing
+        require!(
+            self.position.liquidity == 0
+                && self.position.fees_owed_x == 0
+                && self.position.fees_owed_y == 0,
+            AmmError::PositionNotEmpty
+        );
+
+        Ok(())
+    }
+}
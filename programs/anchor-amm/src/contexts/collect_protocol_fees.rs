@@ -0,0 +1,140 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    has_update_authority, Config,
+    errors::AmmError,
+    helpers::token::transfer_checked_with_hook,
+};
+
+/// Collect Protocol Fees Context
+///
+/// Move the protocol fees accrued in the vaults to the treasury ATAs
+#[derive(Accounts)]
+pub struct CollectProtocolFees<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(mint::token_program = x_token_program)]
+    pub x_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = y_token_program)]
+    pub y_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        associated_token::mint = x_mint,
+        associated_token::token_program = x_token_program,
+        associated_token::authority = auth,
+    )]
+    pub x_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = y_mint,
+        associated_token::token_program = y_token_program,
+        associated_token::authority = auth,
+    )]
+    pub y_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: owner of the treasury ATAs, it has to be the one saved in the config
+    #[account(address = config.treasury)]
+    pub treasury: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = x_mint,
+        associated_token::token_program = x_token_program,
+        associated_token::authority = treasury,
+    )]
+    pub x_treasury_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = y_mint,
+        associated_token::token_program = y_token_program,
+        associated_token::authority = treasury,
+    )]
+    pub y_treasury_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        has_one = x_mint,
+        has_one = y_mint,
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: just a pda for signing
+    #[account(
+        seeds = [b"auth", config.key().as_ref()],
+        bump = config.auth_bump,
+    )]
+    pub auth: UncheckedAccount<'info>,
+
+    // Each mint can belong to the legacy token program or to Token-2022
+    pub x_token_program: Interface<'info, TokenInterface>,
+    pub y_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CollectProtocolFees<'info> {
+    pub fn collect_protocol_fees(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        has_update_authority!(self);
+
+        let (amount_x, amount_y) = (self.config.protocol_fees_x, self.config.protocol_fees_y);
+
+        self.config.protocol_fees_x = 0;
+        self.config.protocol_fees_y = 0;
+
+        self.withdraw_tokens(true, amount_x, remaining_accounts)?;
+        self.withdraw_tokens(false, amount_y, remaining_accounts)
+    }
+
+    /// Withdraw Tokens
+    ///
+    /// Helper function to send the fees of one side from the vault to the treasury
+    fn withdraw_tokens(&self, is_x: bool, amount: u64, extra_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        if amount == 0 {
+            return Ok(());
+        }
+
+        let (from, to, mint, token_program) = match is_x {
+            true => (
+                self.x_vault.to_account_info(),
+                self.x_treasury_ata.to_account_info(),
+                &self.x_mint,
+                &self.x_token_program,
+            ),
+            false => (
+                self.y_vault.to_account_info(),
+                self.y_treasury_ata.to_account_info(),
+                &self.y_mint,
+                &self.y_token_program,
+            ),
+        };
+
+        let cpi_accounts = TransferChecked {
+            from,
+            mint: mint.to_account_info(),
+            to,
+            authority: self.auth.to_account_info(),
+        };
+
+        let config_key = self.config.key();
+        let seeds = &[&b"auth"[..], config_key.as_ref(), &[self.config.auth_bump]];
+        let signer_seeds = &[&seeds[..]];
+
+        let ctx = CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+
+        transfer_checked_with_hook(ctx, amount, mint.decimals, extra_accounts, &self.config.allowed_hooks)
+    }
+}
@@ -1,14 +1,15 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token::{mint_to, transfer, MintTo, Token, Transfer},
-    token_interface::{Mint, TokenAccount},
+    token_interface::{mint_to, Mint, MintTo, TokenAccount, TokenInterface, TransferChecked},
 };
 // use constant_product_curve::ConstantProduct;
 
 use crate::{
-    assert_non_zero, assert_not_expired, assert_not_locked, helpers::ConstantProduct, Config,
+    assert_non_zero, assert_not_expired, assert_not_locked, Config, Twamm,
     errors::AmmError,
+    events::LiquidityDeposited,
+    helpers::token::{amount_before_transfer_fee, is_native_mint, transfer_checked_with_hook, unwrap_sol, wrap_sol},
 };
 
 #[derive(Accounts)]
@@ -17,14 +18,17 @@ pub struct Deposit<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
 
+    #[account(mint::token_program = x_token_program)]
     pub x_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = y_token_program)]
     pub y_mint: Box<InterfaceAccount<'info, Mint>>,
 
     // now we are going to define the lp_mint. IT will contain the token information for our LPs
     #[account(
         mut,
         seeds = [b"lp", config.key().as_ref()],
-        bump = config.lp_bump
+        bump = config.lp_bump,
+        mint::token_program = token_program,
     )]
     pub lp_mint: Box<InterfaceAccount<'info, Mint>>,
 
@@ -32,26 +36,33 @@ pub struct Deposit<'info> {
     #[account(
         mut,
         associated_token::mint = config.x_mint,
+        associated_token::token_program = x_token_program,
         associated_token::authority = auth
     )]
     pub x_vault: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         mut,
         associated_token::mint = config.y_mint,
+        associated_token::token_program = y_token_program,
         associated_token::authority = auth
     )]
     pub y_vault: Box<InterfaceAccount<'info, TokenAccount>>,
 
-    // The two user ATAs which could get changed if the user deposits X or Y
+    // The two user ATAs which could get changed if the user deposits X or Y. They are created if needed,
+    // so native SOL can be wrapped into a new wSOL account
     #[account(
-        mut,
-        associated_token::mint = config.x_mint,
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = x_mint,
+        associated_token::token_program = x_token_program,
         associated_token::authority = payer,
     )]
     pub x_user_ata: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(
-        mut,
-        associated_token::mint = config.y_mint,
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = y_mint,
+        associated_token::token_program = y_token_program,
         associated_token::authority = payer,
     )]
     pub y_user_ata: Box<InterfaceAccount<'info, TokenAccount>>,
@@ -61,19 +72,21 @@ pub struct Deposit<'info> {
         init_if_needed,
         payer = payer,
         associated_token::mint = lp_mint,
+        associated_token::token_program = token_program,
         associated_token::authority = payer,
     )]
     pub lp_user_ata: Box<InterfaceAccount<'info, TokenAccount>>,
 
     /// CHECK: this is safe
     #[account(
-        seeds = [b"auth"],
+        seeds = [b"auth", config.key().as_ref()],
         bump = config.auth_bump,
     )]
     pub auth: UncheckedAccount<'info>,
 
     // We will still need the config account to retrieve some data
     #[account(
+        mut,
         has_one = x_mint,
         has_one = y_mint,
         seeds = [b"config", config.seed.to_le_bytes().as_ref()],
@@ -81,8 +94,20 @@ pub struct Deposit<'info> {
     )]
     pub config: Account<'info, Config>,
 
+    // Long-term orders, their virtual trades run before the reserves are read
+    #[account(
+        mut,
+        has_one = config,
+        seeds = [b"twamm", config.key().as_ref()],
+        bump = twamm.bump,
+    )]
+    pub twamm: Box<Account<'info, Twamm>>,
+
     // as always we add the required programs to mint, transfer and create accounts
-    pub token_program: Program<'info, Token>,
+    // Each mint can belong to the legacy token program or to Token-2022
+    pub x_token_program: Interface<'info, TokenInterface>,
+    pub y_token_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
@@ -92,71 +117,152 @@ impl<'info> Deposit<'info> {
     /// after it will calculate:
     /// - if the pool is empty, it will be able to add the maximum in x and y
     /// - if the pool already has funds, it will  calculate the ratio and multiply/divide to balance the amount added
-    pub fn deposit(&mut self, amount: u64, x_max: u64, y_max: u64, expiration: i64) -> Result<()> {
-        assert_not_locked!(self.config.locked);
+    pub fn deposit(
+        &mut self,
+        amount: u64,
+        x_max: u64,
+        y_max: u64,
+        expiration: i64,
+        wrap_native: bool, // Pay native SOL instead of wSOL when a mint is wSOL
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        assert_not_locked!(self.config.deposits_paused());
+        self.config.check_idle()?;
         assert_not_expired!(expiration);
         assert_non_zero!([amount, x_max, y_max]);
 
+        // Long-term orders trade first, so the deposit gets the current price
+        self.twamm.execute(&mut self.config, self.x_vault.amount, self.y_vault.amount)?;
+
+        // Protocol fees sitting in the vaults don't count as pool liquidity
+        let (reserve_x, reserve_y) = self.config.reserves(self.x_vault.amount, self.y_vault.amount);
+        self.config.update_price_accumulators(reserve_x, reserve_y)?;
+
         let (x, y) = match self.lp_mint.supply == 0
-            && self.x_vault.amount == 0
-            && self.y_vault.amount == 0
+            && reserve_x == 0
+            && reserve_y == 0
         {
             true => (x_max, y_max),
             false => {
-                let amounts = ConstantProduct::calculate_deposit_amounts(
-                    self.x_vault.amount,
-                    self.y_vault.amount,
-                    self.lp_mint.supply,
-                    amount,
-                    6,
+                let amounts = self
+                    .config
+                    .curve(reserve_x, reserve_y, self.lp_mint.supply)?
+                    .deposit_amounts(amount)
+                    .map_err(AmmError::from)?;
+
+                // The curve gives what the vaults need to receive, with transfer fee mints the user sends more
+                (
+                    amount_before_transfer_fee(&self.x_mint.to_account_info(), amounts.token_x)?,
+                    amount_before_transfer_fee(&self.y_mint.to_account_info(), amounts.token_y)?,
                 )
-                .map_err(AmmError::from)?;
-                (amounts.token_x, amounts.token_y)
             }
         };
 
         // The amount of tokens we want to deposit can't exceeded the maximum of tokens based on the current pool liquidity
         require!(x <= x_max && y <= y_max, AmmError::SlippageExceeded);
 
+        // Native SOL is wrapped on the fly when the user pays with it
+        self.wrap_if_native(true, x, wrap_native)?;
+        self.wrap_if_native(false, y, wrap_native)?;
+
         // this is a weird way to deposity in X or in Y
-        self.deposit_tokens(true, x)?;
-        self.deposit_tokens(false, y)?;
+        self.deposit_tokens(true, x, remaining_accounts)?;
+        self.deposit_tokens(false, y, remaining_accounts)?;
+
+        // wSOL accounts created to wrap the SOL are closed again
+        self.unwrap_if_native(true, wrap_native)?;
+        self.unwrap_if_native(false, wrap_native)?;
 
         // BAsed on how many tokens the user has deposit, it will get some LP tokens
         self.mint_lp_tokens(amount)?;
 
+        self.config.touch()?;
+
+        self.x_vault.reload()?;
+        self.y_vault.reload()?;
+        let (reserve_x, reserve_y) = self.config.reserves(self.x_vault.amount, self.y_vault.amount);
+
+        emit!(LiquidityDeposited {
+            config: self.config.key(),
+            user: self.payer.key(),
+            liquidity: amount as u128,
+            amount_x: x,
+            amount_y: y,
+            reserve_x,
+            reserve_y,
+        });
+
         Ok(())
     }
 
     /// Deposit Tokens
     ///
     /// Helper Function which will have a boolean to specify if it's x or y and the amount to deposit
-    pub fn deposit_tokens(&self, is_x: bool, amount: u64) -> Result<()> {
+    pub fn deposit_tokens(&self, is_x: bool, amount: u64, extra_accounts: &[AccountInfo<'info>]) -> Result<()> {
         // If X, from will be user ATA x to vault ATA X
         // Otherwise, from user ATA Y to vault ATA Y
-        let (from, to) = match is_x {
+        let (from, to, mint, token_program) = match is_x {
             true => (
                 self.x_user_ata.to_account_info(),
                 self.x_vault.to_account_info(),
+                &self.x_mint,
+                &self.x_token_program,
             ),
             false => (
                 self.y_user_ata.to_account_info(),
                 self.y_vault.to_account_info(),
+                &self.y_mint,
+                &self.y_token_program,
             ),
         };
 
         // As any CPI call, we will have the accounts, context and cpi method.
-        let cpi_accounts = Transfer {
+        let cpi_accounts = TransferChecked {
             from,
+            mint: mint.to_account_info(),
             to,
             authority: self.payer.to_account_info(),
         };
 
         // CPI Context
-        let ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+        let ctx = CpiContext::new(token_program.to_account_info(), cpi_accounts);
 
         // Token transfer cpi call
-        transfer(ctx, amount)
+        transfer_checked_with_hook(ctx, amount, mint.decimals, extra_accounts, &self.config.allowed_hooks)
+    }
+
+    /// Wrap If Native
+    ///
+    /// Helper function to wrap the SOL the user is about to send when the mint is native and the user
+    /// pays with SOL. Otherwise the tokens come from the user wSOL account like any other mint
+    fn wrap_if_native(&self, is_x: bool, amount: u64, wrap_native: bool) -> Result<()> {
+        let (mint, user_ata, token_program) = match is_x {
+            true => (&self.x_mint, &self.x_user_ata, &self.x_token_program),
+            false => (&self.y_mint, &self.y_user_ata, &self.y_token_program),
+        };
+
+        if !wrap_native || !is_native_mint(&mint.key()) {
+            return Ok(());
+        }
+
+        wrap_sol(&self.payer, user_ata, amount, &self.system_program, token_program)
+    }
+
+    /// Unwrap If Native
+    ///
+    /// Helper function to close the user wSOL account when the mint is native, the user paid with SOL
+    /// and the account was empty before, so the rent goes back to the user
+    fn unwrap_if_native(&self, is_x: bool, wrap_native: bool) -> Result<()> {
+        let (mint, user_ata, token_program) = match is_x {
+            true => (&self.x_mint, &self.x_user_ata, &self.x_token_program),
+            false => (&self.y_mint, &self.y_user_ata, &self.y_token_program),
+        };
+
+        if !wrap_native || !is_native_mint(&mint.key()) {
+            return Ok(());
+        }
+
+        unwrap_sol(&self.payer, user_ata, token_program)
     }
 
     /// Mint LP Tokens
@@ -167,11 +273,12 @@ impl<'info> Deposit<'info> {
         let accounts = MintTo {
             mint: self.lp_mint.to_account_info(),
             to: self.lp_user_ata.to_account_info(),
-            authority: self.payer.to_account_info(),
+            authority: self.auth.to_account_info(),
         };
 
-        // As the PDA has to sign the transaction, we need to create the seed based on the LP mint seed
-        let seeds = &[&b"auth"[..], &[self.config.auth_bump]];
+        // As the PDA has to sign the transaction, we need to create the seed based on the pool auth seed
+        let config_key = self.config.key();
+        let seeds = &[&b"auth"[..], config_key.as_ref(), &[self.config.auth_bump]];
         let signer_seeds = &[&seeds[..]];
 
         // We define the Context based on accounts and the PDA signer.
@@ -0,0 +1,301 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{close_account, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    assert_non_zero, assert_not_locked, Config, Observation, Observations, Order, Twamm,
+    errors::AmmError,
+    events::{OrderFilled, Swapped},
+    helpers::{
+        token::{amount_after_transfer_fee, has_withheld_fees, transfer_checked_with_hook},
+        LiquidityPair,
+    },
+};
+
+/// Fill Order Context
+///
+/// Permissionless, any keeper can fill an order once the pool price has reached its target
+#[derive(Accounts)]
+pub struct FillOrder<'info> {
+    // The keeper gets the bounty
+    #[account(mut)]
+    pub keeper: Signer<'info>,
+
+    /// CHECK: owner of the order, it gets the output and the rent. Checked by the order
+    #[account(mut)]
+    pub owner: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        close = owner,
+        has_one = config,
+        has_one = owner,
+        has_one = input_mint,
+        has_one = output_mint,
+        seeds = [
+            b"order",
+            config.key().as_ref(),
+            owner.key().as_ref(),
+            order.id.to_le_bytes().as_ref(),
+        ],
+        bump = order.bump,
+    )]
+    pub order: Box<Account<'info, Order>>,
+
+    #[account(mint::token_program = input_token_program)]
+    pub input_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = output_token_program)]
+    pub output_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        associated_token::mint = input_mint,
+        associated_token::token_program = input_token_program,
+        associated_token::authority = order,
+    )]
+    pub escrow: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // Pool vaults of the input and output tokens of the order
+    #[account(
+        mut,
+        associated_token::mint = input_mint,
+        associated_token::token_program = input_token_program,
+        associated_token::authority = auth,
+    )]
+    pub input_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = output_mint,
+        associated_token::token_program = output_token_program,
+        associated_token::authority = auth,
+    )]
+    pub output_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        init_if_needed,
+        payer = keeper,
+        associated_token::mint = output_mint,
+        associated_token::token_program = output_token_program,
+        associated_token::authority = owner,
+    )]
+    pub owner_output_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // Oracle ring buffer, written before every trade
+    #[account(
+        mut,
+        has_one = config,
+        seeds = [b"observations", config.key().as_ref()],
+        bump = observations.bump,
+    )]
+    pub observations: Box<Account<'info, Observations>>,
+
+    // Long-term orders, their virtual trades run before every trade
+    #[account(
+        mut,
+        has_one = config,
+        seeds = [b"twamm", config.key().as_ref()],
+        bump = twamm.bump,
+    )]
+    pub twamm: Box<Account<'info, Twamm>>,
+
+    /// CHECK: just a pda for signing
+    #[account(
+        seeds = [b"auth", config.key().as_ref()],
+        bump = config.auth_bump,
+    )]
+    pub auth: UncheckedAccount<'info>,
+
+    pub input_token_program: Interface<'info, TokenInterface>,
+    pub output_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> FillOrder<'info> {
+    /// Fill Order
+    ///
+    /// Swap the whole escrow through the pool once the spot price has crossed the target. The
+    /// owner gets the output, the keeper the bounty, and the order is closed
+    pub fn fill_order(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        assert_not_locked!(self.config.swaps_paused());
+        self.config.check_idle()?;
+
+        let is_x_to_y = self.order.is_x_to_y;
+        let (vault_x, vault_y) = match is_x_to_y {
+            true => (self.input_vault.amount, self.output_vault.amount),
+            false => (self.output_vault.amount, self.input_vault.amount),
+        };
+
+        // Long-term orders trade first, the trigger is checked on the price they leave
+        self.twamm.execute(&mut self.config, vault_x, vault_y)?;
+
+        // Price accumulators use the reserves from before the trade
+        let (reserve_x, reserve_y) = self.config.reserves(vault_x, vault_y);
+        self.config.update_price_accumulators(reserve_x, reserve_y)?;
+        self.observations.write(Observation {
+            timestamp: self.config.last_price_timestamp,
+            price_cumulative: self.config.price_x_cumulative,
+            liquidity_cumulative: self.config.liquidity_cumulative,
+        });
+
+        // The LP supply doesn't take part in swaps
+        let mut curve = self.config.curve(reserve_x, reserve_y, 0)?;
+
+        let spot_price = curve.spot_price_x().map_err(AmmError::from)?;
+        require!(self.order.is_triggered(spot_price.amount), AmmError::OrderNotTriggered);
+
+        let pair = match is_x_to_y {
+            true => LiquidityPair::TokenX,
+            false => LiquidityPair::TokenY,
+        };
+
+        // The whole escrow is swapped, tokens sent to it after the order was placed included, so it
+        // can always be emptied and closed
+        let amount_in = self.escrow.amount;
+
+        // With transfer fee mints the vault only gets the net input and the owner only gets the net output
+        let amount_in_net = amount_after_transfer_fee(&self.input_mint.to_account_info(), amount_in)?;
+
+        let swap_result = curve
+            .swap(pair, amount_in_net, 0)
+            .map_err(AmmError::from)?;
+
+        assert_non_zero!([swap_result.deposited, swap_result.withdrawn]);
+
+        let amount_out_net = amount_after_transfer_fee(&self.output_mint.to_account_info(), swap_result.withdrawn)?;
+        require!(amount_out_net >= self.order.min_amount_out, AmmError::SlippageExceeded);
+
+        // Part of the fee stays in the vault for the protocol
+        self.config.accrue_protocol_fee(is_x_to_y, swap_result.fee)?;
+        self.config.record_swap(is_x_to_y, swap_result.deposited, swap_result.withdrawn, swap_result.fee)?;
+
+        self.deposit_tokens(amount_in, remaining_accounts)?;
+        self.withdraw_tokens(swap_result.withdrawn, remaining_accounts)?;
+        self.close_escrow()?;
+
+        // The bounty sits in the order on top of the rent, the rest goes back to the owner on close
+        let bounty = self.order.bounty;
+        let order_info = self.order.to_account_info();
+        let order_lamports = order_info.lamports().checked_sub(bounty).ok_or(AmmError::Underflow)?;
+        let keeper_lamports = self.keeper.lamports().checked_add(bounty).ok_or(AmmError::Overflow)?;
+        **order_info.try_borrow_mut_lamports()? = order_lamports;
+        **self.keeper.try_borrow_mut_lamports()? = keeper_lamports;
+
+        self.input_vault.reload()?;
+        self.output_vault.reload()?;
+        let (reserve_x, reserve_y) = match is_x_to_y {
+            true => self.config.reserves(self.input_vault.amount, self.output_vault.amount),
+            false => self.config.reserves(self.output_vault.amount, self.input_vault.amount),
+        };
+
+        emit!(Swapped {
+            config: self.config.key(),
+            user: self.owner.key(),
+            is_x_to_y,
+            amount_in: swap_result.deposited,
+            amount_out: swap_result.withdrawn,
+            fee: swap_result.fee,
+            reserve_x,
+            reserve_y,
+        });
+
+        emit!(OrderFilled {
+            config: self.config.key(),
+            order: self.order.key(),
+            owner: self.owner.key(),
+            keeper: self.keeper.key(),
+            amount_in,
+            amount_out: amount_out_net,
+            bounty,
+        });
+
+        Ok(())
+    }
+
+    /// Deposit Tokens
+    ///
+    /// Helper function to move the escrow to the pool, signed by the order
+    fn deposit_tokens(&self, amount: u64, extra_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let cpi_accounts = TransferChecked {
+            from: self.escrow.to_account_info(),
+            mint: self.input_mint.to_account_info(),
+            to: self.input_vault.to_account_info(),
+            authority: self.order.to_account_info(),
+        };
+
+        let config_key = self.config.key();
+        let owner_key = self.owner.key();
+        let id = self.order.id.to_le_bytes();
+        let seeds = &[&b"order"[..], config_key.as_ref(), owner_key.as_ref(), id.as_ref(), &[self.order.bump]];
+        let signer_seeds = &[&seeds[..]];
+
+        let ctx = CpiContext::new_with_signer(
+            self.input_token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+
+        transfer_checked_with_hook(ctx, amount, self.input_mint.decimals, extra_accounts, &self.config.allowed_hooks)
+    }
+
+    /// Withdraw Tokens
+    ///
+    /// Helper function to send the output from the pool to the owner, signed by `auth`
+    fn withdraw_tokens(&self, amount: u64, extra_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let cpi_accounts = TransferChecked {
+            from: self.output_vault.to_account_info(),
+            mint: self.output_mint.to_account_info(),
+            to: self.owner_output_ata.to_account_info(),
+            authority: self.auth.to_account_info(),
+        };
+
+        let config_key = self.config.key();
+        let seeds = &[&b"auth"[..], config_key.as_ref(), &[self.config.auth_bump]];
+        let signer_seeds = &[&seeds[..]];
+
+        let ctx = CpiContext::new_with_signer(
+            self.output_token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+
+        transfer_checked_with_hook(ctx, amount, self.output_mint.decimals, extra_accounts, &self.config.allowed_hooks)
+    }
+
+    /// Close Escrow
+    ///
+    /// Helper function to give the escrow rent back to the owner. Escrows holding withheld
+    /// transfer fees can't be closed and are left behind
+    fn close_escrow(&self) -> Result<()> {
+        if has_withheld_fees(&self.escrow.to_account_info())? {
+            return Ok(());
+        }
+
+        let cpi_accounts = CloseAccount {
+            account: self.escrow.to_account_info(),
+            destination: self.owner.to_account_info(),
+            authority: self.order.to_account_info(),
+        };
+
+        let config_key = self.config.key();
+        let owner_key = self.owner.key();
+        let id = self.order.id.to_le_bytes();
+        let seeds = &[&b"order"[..], config_key.as_ref(), owner_key.as_ref(), id.as_ref(), &[self.order.bump]];
+        let signer_seeds = &[&seeds[..]];
+
+        close_account(CpiContext::new_with_signer(
+            self.input_token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        ))
+    }
+}
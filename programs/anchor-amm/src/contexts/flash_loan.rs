@@ -0,0 +1,273 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::sysvar::instructions::{self as sysvar_instructions, load_current_index_checked, load_instruction_at_checked},
+    Discriminator,
+};
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    assert_non_zero, assert_not_locked, Config, CurveType, OutstandingLoan,
+    errors::AmmError,
+    events::FlashLoanRepaid,
+    helpers::token::{amount_before_transfer_fee, transfer_checked_with_hook},
+    instruction::FlashRepay,
+};
+
+// Position of `config` in the FlashLoan accounts, used to match the repayment with the pool
+const CONFIG_ACCOUNT_INDEX: usize = 7;
+
+/// Flash Loan Context
+///
+/// Shared by flash_borrow and flash_repay. The borrowed tokens go to the user ATA of the
+/// borrowed side and have to come back with the fee before the end of the transaction
+#[derive(Accounts)]
+pub struct FlashLoan<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(mint::token_program = x_token_program)]
+    pub x_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = y_token_program)]
+    pub y_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        associated_token::mint = x_mint,
+        associated_token::token_program = x_token_program,
+        associated_token::authority = auth,
+    )]
+    pub x_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = y_mint,
+        associated_token::token_program = y_token_program,
+        associated_token::authority = auth,
+    )]
+    pub y_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = x_mint,
+        associated_token::token_program = x_token_program,
+        associated_token::authority = payer,
+    )]
+    pub x_user_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = y_mint,
+        associated_token::token_program = y_token_program,
+        associated_token::authority = payer,
+    )]
+    pub y_user_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        has_one = x_mint,
+        has_one = y_mint,
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+
+    /// CHECK: just a pda for signing
+    #[account(
+        seeds = [b"auth", config.key().as_ref()],
+        bump = config.auth_bump,
+    )]
+    pub auth: UncheckedAccount<'info>,
+
+    /// CHECK: instructions sysvar, used to find the repayment in the transaction
+    #[account(address = sysvar_instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    // Each mint can belong to the legacy token program or to Token-2022
+    pub x_token_program: Interface<'info, TokenInterface>,
+    pub y_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> FlashLoan<'info> {
+    /// Flash Borrow
+    ///
+    /// Lend `amount` of X or Y from the vault. It only works if a flash_repay for the same pool
+    /// comes later in the transaction, and the pool is frozen for everything else until then
+    pub fn flash_borrow(&mut self, amount: u64, is_x: bool, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        assert_non_zero!([amount]);
+        assert_not_locked!(self.config.swaps_paused());
+        self.config.check_idle()?;
+
+        // Concentrated pools keep their own accounting, a fee left in the vault wouldn't reach the positions
+        require!(
+            !matches!(self.config.curve_type, CurveType::Concentrated { .. }),
+            AmmError::UnsupportedCurve
+        );
+
+        self.check_repay_instruction()?;
+
+        // Protocol fees sitting in the vaults can't be lent
+        let (reserve_x, reserve_y) = self.config.reserves(self.x_vault.amount, self.y_vault.amount);
+        let (reserve, vault_balance) = match is_x {
+            true => (reserve_x, self.x_vault.amount),
+            false => (reserve_y, self.y_vault.amount),
+        };
+        require!(amount <= reserve, AmmError::InsufficientBalance);
+
+        let fee = self.config.flash_fee_amount(amount)?;
+
+        self.config.flash_loan = Some(OutstandingLoan {
+            is_x,
+            amount,
+            fee,
+            vault_balance,
+        });
+
+        self.withdraw_tokens(is_x, amount, remaining_accounts)
+    }
+
+    /// Flash Repay
+    ///
+    /// Send the loan plus the fee back to the vault and clear the loan. The fee stays in the
+    /// vault, so it goes to the LPs
+    pub fn flash_repay(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let loan = self.config.flash_loan.ok_or(AmmError::NoFlashLoan)?;
+
+        // With transfer fee mints the user has to send more, so the vault gets the loan plus the fee
+        let owed = loan.amount.checked_add(loan.fee).ok_or(AmmError::Overflow)?;
+        let mint = match loan.is_x {
+            true => self.x_mint.to_account_info(),
+            false => self.y_mint.to_account_info(),
+        };
+        let amount_in = amount_before_transfer_fee(&mint, owed)?;
+
+        self.deposit_tokens(loan.is_x, amount_in, remaining_accounts)?;
+
+        let vault = match loan.is_x {
+            true => &mut self.x_vault,
+            false => &mut self.y_vault,
+        };
+        vault.reload()?;
+
+        let expected = loan.vault_balance.checked_add(loan.fee).ok_or(AmmError::Overflow)?;
+        require!(vault.amount >= expected, AmmError::FlashLoanNotRepaid);
+
+        self.config.flash_loan = None;
+
+        match loan.is_x {
+            true => self.config.fees_x = self.config.fees_x.saturating_add(loan.fee as u128),
+            false => self.config.fees_y = self.config.fees_y.saturating_add(loan.fee as u128),
+        }
+        self.config.touch()?;
+
+        emit!(FlashLoanRepaid {
+            config: self.config.key(),
+            user: self.payer.key(),
+            is_x: loan.is_x,
+            amount: loan.amount,
+            fee: loan.fee,
+        });
+
+        Ok(())
+    }
+
+    /// Check Repay Instruction
+    ///
+    /// Helper function to look for a flash_repay of this pool after the current instruction.
+    /// The borrow has to be a top level instruction, through CPI the sysvar would show the caller
+    fn check_repay_instruction(&self) -> Result<()> {
+        let instructions = self.instructions.to_account_info();
+        let current_index = load_current_index_checked(&instructions)? as usize;
+
+        let current = load_instruction_at_checked(current_index, &instructions)?;
+        require_keys_eq!(current.program_id, crate::ID, AmmError::FlashLoanCpiNotAllowed);
+
+        let mut index = current_index + 1;
+        while let Ok(ix) = load_instruction_at_checked(index, &instructions) {
+            if ix.program_id == crate::ID
+                && ix.data.get(..8) == Some(&FlashRepay::DISCRIMINATOR[..])
+                && ix.accounts.get(CONFIG_ACCOUNT_INDEX).map(|account| account.pubkey) == Some(self.config.key())
+            {
+                return Ok(());
+            }
+
+            index += 1;
+        }
+
+        err!(AmmError::FlashRepayNotFound)
+    }
+
+    /// Deposit Tokens
+    ///
+    /// Helper function to send the repayment from the user ATA to the vault
+    fn deposit_tokens(&self, is_x: bool, amount: u64, extra_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let (from, to, mint, token_program) = match is_x {
+            true => (
+                self.x_user_ata.to_account_info(),
+                self.x_vault.to_account_info(),
+                &self.x_mint,
+                &self.x_token_program,
+            ),
+            false => (
+                self.y_user_ata.to_account_info(),
+                self.y_vault.to_account_info(),
+                &self.y_mint,
+                &self.y_token_program,
+            ),
+        };
+
+        let cpi_accounts = TransferChecked {
+            from,
+            mint: mint.to_account_info(),
+            to,
+            authority: self.payer.to_account_info(),
+        };
+
+        let ctx = CpiContext::new(token_program.to_account_info(), cpi_accounts);
+
+        transfer_checked_with_hook(ctx, amount, mint.decimals, extra_accounts, &self.config.allowed_hooks)
+    }
+
+    /// Withdraw Tokens
+    ///
+    /// Helper function to send the borrowed tokens from the vault to the user ATA
+    fn withdraw_tokens(&self, is_x: bool, amount: u64, extra_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let (from, to, mint, token_program) = match is_x {
+            true => (
+                self.x_vault.to_account_info(),
+                self.x_user_ata.to_account_info(),
+                &self.x_mint,
+                &self.x_token_program,
+            ),
+            false => (
+                self.y_vault.to_account_info(),
+                self.y_user_ata.to_account_info(),
+                &self.y_mint,
+                &self.y_token_program,
+            ),
+        };
+
+        let cpi_accounts = TransferChecked {
+            from,
+            mint: mint.to_account_info(),
+            to,
+            authority: self.auth.to_account_info(),
+        };
+
+        let config_key = self.config.key();
+        let seeds = &[&b"auth"[..], config_key.as_ref(), &[self.config.auth_bump]];
+        let signer_seeds = &[&seeds[..]];
+
+        let ctx = CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+
+        transfer_checked_with_hook(ctx, amount, mint.decimals, extra_accounts, &self.config.allowed_hooks)
+    }
+}
@@ -0,0 +1,55 @@
+use anchor_lang::{prelude::*, solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE};
+
+use crate::{
+    Config, Observation, Observations,
+    errors::AmmError,
+    MAX_OBSERVATIONS,
+};
+
+/// Grow Observations Context
+///
+/// Anyone can pay to hold more observations, so longer TWAP windows become available
+#[derive(Accounts)]
+#[instruction(new_capacity: u16)]
+pub struct GrowObservations<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        has_one = config,
+        seeds = [b"observations", config.key().as_ref()],
+        bump = observations.bump,
+        realloc = Observations::space(new_capacity),
+        realloc::payer = payer,
+        realloc::zero = false,
+    )]
+    pub observations: Box<Account<'info, Observations>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> GrowObservations<'info> {
+    pub fn grow_observations(&mut self, new_capacity: u16) -> Result<()> {
+        require!(
+            new_capacity > self.observations.capacity && new_capacity <= MAX_OBSERVATIONS,
+            AmmError::InvalidObservationsCapacity
+        );
+
+        // An account can only grow a limited amount of bytes per instruction
+        require!(
+            (new_capacity - self.observations.capacity) as usize * Observation::LEN <= MAX_PERMITTED_DATA_INCREASE,
+            AmmError::InvalidObservationsCapacity
+        );
+
+        self.observations.capacity = new_capacity;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,38 @@
+use anchor_lang::prelude::*;
+
+use crate::{Config, Observations};
+
+/// Init Observations Context
+///
+/// Pools created before the oracle, or moved from the legacy vaults, don't have an Observations
+/// account and can't swap without it. Anyone can pay to create it, then grow it like any other
+#[derive(Accounts)]
+pub struct InitObservations<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"observations", config.key().as_ref()],
+        bump,
+        space = Observations::space(1)
+    )]
+    pub observations: Box<Account<'info, Observations>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitObservations<'info> {
+    pub fn init_observations(&mut self, bumps: &InitObservationsBumps) -> Result<()> {
+        self.observations.init(self.config.key(), bumps.observations);
+
+        Ok(())
+    }
+}
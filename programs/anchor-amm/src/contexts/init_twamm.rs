@@ -0,0 +1,38 @@
+use anchor_lang::prelude::*;
+
+use crate::{Config, Twamm};
+
+/// Init Twamm Context
+///
+/// Pools created before the long-term orders, or moved from the legacy vaults, don't have a Twamm
+/// account and can't swap, deposit or withdraw without it. Anyone can pay to create it
+#[derive(Accounts)]
+pub struct InitTwamm<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"twamm", config.key().as_ref()],
+        bump,
+        space = Twamm::INIT_SPACE
+    )]
+    pub twamm: Box<Account<'info, Twamm>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitTwamm<'info> {
+    pub fn init_twamm(&mut self, bumps: &InitTwammBumps) -> Result<()> {
+        self.twamm.init(self.config.key(), Clock::get()?.slot, bumps.twamm);
+
+        Ok(())
+    }
+}
@@ -1,27 +1,30 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken, 
-    token::Token, 
-    token_interface::{ Mint, TokenAccount }
+    token_interface::{ Mint, TokenAccount, TokenInterface }
 };
 
 use crate::{ 
-    Config,
+    Config, Observations, PoolRegistry, CurveType, Twamm,
     errors::AmmError,
+    events::PoolInitialized,
+    MAX_FEE_DELAY, MIN_FEE_DELAY,
 };
 
 /// Initialize Context
 /// 
 /// It will require to expose the seed to create the pools
 #[derive(Accounts)]
-#[instruction(seed: u64)]
+#[instruction(seed: u64, fee: u16)]
 pub struct Initialize<'info> {
     // As always we will need a person who will pay for creating the LP
     #[account(mut)]
     pub payer: Signer<'info>,
 
     // An AMM allows to exchange 2 different SPL tokens, therefore we will need to define both mint accounts
+    #[account(mint::token_program = x_token_program)]
     pub x_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = y_token_program)]
     pub y_mint: Box<InterfaceAccount<'info, Mint>>,
 
     #[account(
@@ -30,7 +33,8 @@ pub struct Initialize<'info> {
         seeds = [b"lp", config.key.as_ref()],
         bump,
         mint::decimals = 6,
-        mint::authority = payer
+        mint::authority = auth,
+        mint::token_program = token_program,
     )]
     pub lp_mint: Box<InterfaceAccount<'info, Mint>>,
 
@@ -39,6 +43,7 @@ pub struct Initialize<'info> {
         init,
         payer = payer,
         associated_token::mint = x_mint,
+        associated_token::token_program = x_token_program,
         associated_token::authority = auth,
     )]
     pub x_vault: Box<InterfaceAccount<'info, TokenAccount>>,
@@ -47,12 +52,13 @@ pub struct Initialize<'info> {
         init,
         payer = payer,
         associated_token::mint = y_mint,
+        associated_token::token_program = y_token_program,
         associated_token::authority = auth,
     )]
     pub y_vault: Box<InterfaceAccount<'info, TokenAccount>>,
 
     /// CHECK: This account is only used to sign. it doesn't contain SOL
-    #[account(seeds = [b"auth"], bump)]
+    #[account(seeds = [b"auth", config.key().as_ref()], bump)]
     pub auth: UncheckedAccount<'info>,
 
     // We will need an extra PDA to store some configuration
@@ -65,8 +71,42 @@ pub struct Initialize<'info> {
     )]
     pub config: Account<'info, Config>,
 
+    // Canonical entry of the pool, one per sorted mint pair and fee tier. As it's created with init,
+    // a second pool for the same pair and fee will fail here
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"pool", x_mint.key().as_ref(), y_mint.key().as_ref(), fee.to_le_bytes().as_ref()],
+        bump,
+        space = PoolRegistry::INIT_SPACE
+    )]
+    pub registry: Box<Account<'info, PoolRegistry>>,
+
+    // Oracle ring buffer, it starts with a single observation and can be grown later
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"observations", config.key().as_ref()],
+        bump,
+        space = Observations::space(1)
+    )]
+    pub observations: Box<Account<'info, Observations>>,
+
+    // Long-term orders of the pool, traded lazily on every interaction
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"twamm", config.key().as_ref()],
+        bump,
+        space = Twamm::INIT_SPACE
+    )]
+    pub twamm: Box<Account<'info, Twamm>>,
+
     // Last we will include the root programs to create accounts, tokens and ATAs
-    pub token_program: Program<'info, Token>,
+    // Each mint can belong to the legacy token program or to Token-2022
+    pub x_token_program: Interface<'info, TokenInterface>,
+    pub y_token_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
@@ -76,11 +116,26 @@ impl<'info> Initialize<'info> {
         &mut self,
         seed: u64,
         fee: u16,
+        fee_delay: i64,
         authority: Option<Pubkey>,
+        curve_type: CurveType,
         bumps: &InitializeBumps,
     ) -> Result<()> {
         // Fee can't be higher than 100%. We will  pass it without decimas 0-10000
         require!(fee <= 10000, AmmError::InvalidFee);
+        require!((MIN_FEE_DELAY..=MAX_FEE_DELAY).contains(&fee_delay), AmmError::InvalidFeeDelay);
+
+        // Mints have to be sorted, so X/Y and Y/X can't be created as different pools
+        require!(self.x_mint.key() < self.y_mint.key(), AmmError::InvalidMintOrder);
+
+        // Concentrated pools need their tick account, they are only created by initialize_concentrated
+        require!(
+            !matches!(curve_type, CurveType::Concentrated { .. }),
+            AmmError::UnsupportedCurve
+        );
+
+        // The curve parameters (like the StableSwap amplification) have to be sane
+        curve_type.validate()?;
 
         self.config.init(
             seed,
@@ -88,11 +143,34 @@ impl<'info> Initialize<'info> {
             self.x_mint.key(),
             self.y_mint.key(),
             fee,
+            fee_delay,
+            curve_type,
             bumps.auth,
             bumps.config,
             bumps.lp_mint,
         );
 
+        self.registry.init(
+            self.config.key(),
+            self.x_mint.key(),
+            self.y_mint.key(),
+            fee,
+            bumps.registry,
+        );
+
+        self.observations.init(self.config.key(), bumps.observations);
+        self.twamm.init(self.config.key(), Clock::get()?.slot, bumps.twamm);
+
+        emit!(PoolInitialized {
+            config: self.config.key(),
+            payer: self.payer.key(),
+            authority,
+            x_mint: self.x_mint.key(),
+            y_mint: self.y_mint.key(),
+            fee,
+            curve_type: self.config.curve_type,
+        });
+
         Ok(())
     }
 }
\ No newline at end of file
@@ -0,0 +1,144 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken, 
+    token_interface::{ Mint, TokenAccount, TokenInterface }
+};
+
+use crate::{ 
+    Config, PoolRegistry, ConcentratedPool, CurveType,
+    errors::AmmError,
+    events::PoolInitialized,
+    MAX_FEE_DELAY, MIN_FEE_DELAY,
+};
+
+/// Initialize Concentrated Context
+/// 
+/// Same vaults, auth and config layout as Initialize, but liquidity is tracked by positions
+/// instead of an LP mint, so we create the concentrated pool state instead
+#[derive(Accounts)]
+#[instruction(seed: u64, fee: u16)]
+pub struct InitializeConcentrated<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(mint::token_program = x_token_program)]
+    pub x_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = y_token_program)]
+    pub y_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    // We will need ATAs to store X and Y tokens
+    #[account(
+        init,
+        payer = payer,
+        associated_token::mint = x_mint,
+        associated_token::token_program = x_token_program,
+        associated_token::authority = auth,
+    )]
+    pub x_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    
+    #[account(
+        init,
+        payer = payer,
+        associated_token::mint = y_mint,
+        associated_token::token_program = y_token_program,
+        associated_token::authority = auth,
+    )]
+    pub y_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: This account is only used to sign. it doesn't contain SOL
+    #[account(seeds = [b"auth", config.key().as_ref()], bump)]
+    pub auth: UncheckedAccount<'info>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"config", seed.to_le_bytes().as_ref()],
+        bump,
+        space = Config::INIT_SPACE
+    )]
+    pub config: Account<'info, Config>,
+
+    // Price, liquidity and ticks of the pool
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"concentrated", config.key().as_ref()],
+        bump,
+        space = ConcentratedPool::INIT_SPACE
+    )]
+    pub pool: Box<Account<'info, ConcentratedPool>>,
+
+    // Canonical entry of the pool, one per sorted mint pair and fee tier. As it's created with init,
+    // a second pool for the same pair and fee will fail here
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"pool", x_mint.key().as_ref(), y_mint.key().as_ref(), fee.to_le_bytes().as_ref()],
+        bump,
+        space = PoolRegistry::INIT_SPACE
+    )]
+    pub registry: Box<Account<'info, PoolRegistry>>,
+
+    // Each mint can belong to the legacy token program or to Token-2022
+    pub x_token_program: Interface<'info, TokenInterface>,
+    pub y_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitializeConcentrated<'info> {
+    pub fn initialize_concentrated(
+        &mut self,
+        seed: u64,
+        fee: u16,
+        fee_delay: i64,
+        authority: Option<Pubkey>,
+        tick_spacing: u16,
+        sqrt_price: u128,
+        bumps: &InitializeConcentratedBumps,
+    ) -> Result<()> {
+        // Fee can't be higher than 100%. We will  pass it without decimas 0-10000
+        require!(fee <= 10000, AmmError::InvalidFee);
+        require!((MIN_FEE_DELAY..=MAX_FEE_DELAY).contains(&fee_delay), AmmError::InvalidFeeDelay);
+
+        // Mints have to be sorted, so X/Y and Y/X can't be created as different pools
+        require!(self.x_mint.key() < self.y_mint.key(), AmmError::InvalidMintOrder);
+
+        let curve_type = CurveType::Concentrated { tick_spacing };
+        curve_type.validate()?;
+
+        // There is no LP mint for concentrated pools, positions take its place
+        self.config.init(
+            seed,
+            authority,
+            self.x_mint.key(),
+            self.y_mint.key(),
+            fee,
+            fee_delay,
+            curve_type,
+            bumps.auth,
+            bumps.config,
+            0,
+        );
+
+        self.registry.init(
+            self.config.key(),
+            self.x_mint.key(),
+            self.y_mint.key(),
+            fee,
+            bumps.registry,
+        );
+
+        emit!(PoolInitialized {
+            config: self.config.key(),
+            payer: self.payer.key(),
+            authority,
+            x_mint: self.x_mint.key(),
+            y_mint: self.y_mint.key(),
+            fee,
+            curve_type: self.config.curve_type,
+        });
+
+        self.pool.init(self.config.key(), sqrt_price, bumps.pool)
+    }
+}
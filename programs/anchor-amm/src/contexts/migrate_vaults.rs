@@ -0,0 +1,230 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::program_option::COption,
+    system_program::{self, Transfer},
+};
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{
+        set_authority, spl_token_2022::instruction::AuthorityType, Mint, SetAuthority, TokenAccount, TokenInterface,
+        TransferChecked,
+    },
+};
+
+use crate::{
+    Config, LegacyConfig,
+    errors::AmmError,
+    events::VaultsMigrated,
+    helpers::token::transfer_checked_with_hook,
+    MAX_ALLOWED_HOOKS,
+};
+
+/// Migrate Vaults Context
+///
+/// Pools created before the vault authority was derived per pool keep their tokens in the ATAs of
+/// the global `[b"auth"]` PDA and their config in the legacy layout. This context moves the tokens
+/// to the ATAs of the pool `[b"auth", config]` PDA and converts the config
+#[derive(Accounts)]
+pub struct MigrateVaults<'info> {
+    // Anyone can migrate a pool, it pays the new vaults and the rent of the bigger config
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(mint::token_program = x_token_program)]
+    pub x_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = y_token_program)]
+    pub y_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [b"lp", config.key().as_ref()],
+        bump,
+        mint::token_program = token_program,
+    )]
+    pub lp_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// CHECK: legacy global PDA, only used to sign
+    #[account(seeds = [b"auth"], bump)]
+    pub legacy_auth: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = x_mint,
+        associated_token::token_program = x_token_program,
+        associated_token::authority = legacy_auth,
+    )]
+    pub legacy_x_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = y_mint,
+        associated_token::token_program = y_token_program,
+        associated_token::authority = legacy_auth,
+    )]
+    pub legacy_y_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: just a pda for signing. no contains SOL
+    #[account(seeds = [b"auth", config.key().as_ref()], bump)]
+    pub auth: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = x_mint,
+        associated_token::token_program = x_token_program,
+        associated_token::authority = auth,
+    )]
+    pub x_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = y_mint,
+        associated_token::token_program = y_token_program,
+        associated_token::authority = auth,
+    )]
+    pub y_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: config in the legacy layout, it can't be loaded as Config until it's converted.
+    /// LegacyConfig::load checks the owner, the layout and the discriminator
+    #[account(mut)]
+    pub config: UncheckedAccount<'info>,
+
+    // Each mint can belong to the legacy token program or to Token-2022
+    pub x_token_program: Interface<'info, TokenInterface>,
+    pub y_token_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> MigrateVaults<'info> {
+    /// Migrate Vaults
+    ///
+    /// Every legacy pool created its own vaults, so their whole balance belongs to the pool. The
+    /// config is converted in the same call, so it only runs once. The remaining accounts are the
+    /// transfer hook accounts
+    pub fn migrate_vaults(&mut self, bumps: &MigrateVaultsBumps, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let legacy = LegacyConfig::load(&self.config)?;
+
+        require_keys_eq!(legacy.config_key()?, self.config.key(), AmmError::InvalidPool);
+        require_keys_eq!(legacy.x_mint, self.x_mint.key(), AmmError::InvalidToken);
+        require_keys_eq!(legacy.y_mint, self.y_mint.key(), AmmError::InvalidToken);
+
+        let amount_x = self.legacy_x_vault.amount;
+        let amount_y = self.legacy_y_vault.amount;
+
+        self.move_tokens(true, amount_x, bumps.legacy_auth, remaining_accounts)?;
+        self.move_tokens(false, amount_y, bumps.legacy_auth, remaining_accounts)?;
+
+        self.move_lp_authority(bumps.legacy_auth)?;
+
+        self.convert_config(&legacy, bumps.auth)?;
+
+        emit!(VaultsMigrated {
+            config: self.config.key(),
+            payer: self.payer.key(),
+            amount_x,
+            amount_y,
+        });
+
+        Ok(())
+    }
+
+    fn move_tokens(&self, is_x: bool, amount: u64, legacy_bump: u8, extra_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        if amount == 0 {
+            return Ok(());
+        }
+
+        let (from, to, mint, token_program) = match is_x {
+            true => (
+                self.legacy_x_vault.to_account_info(),
+                self.x_vault.to_account_info(),
+                &self.x_mint,
+                &self.x_token_program,
+            ),
+            false => (
+                self.legacy_y_vault.to_account_info(),
+                self.y_vault.to_account_info(),
+                &self.y_mint,
+                &self.y_token_program,
+            ),
+        };
+
+        let cpi_accounts = TransferChecked {
+            from,
+            mint: mint.to_account_info(),
+            to,
+            authority: self.legacy_auth.to_account_info(),
+        };
+
+        // The legacy vaults are signed with the global seed
+        let seeds = &[&b"auth"[..], &[legacy_bump]];
+        let signer_seeds = &[&seeds[..]];
+
+        let ctx = CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+
+        // Legacy pools had no hook allowlist
+        transfer_checked_with_hook(ctx, amount, mint.decimals, extra_accounts, &[Pubkey::default(); MAX_ALLOWED_HOOKS])
+    }
+
+    /// Move LP Authority
+    ///
+    /// Helper function to hand the LP mint to the pool `auth`, which signs the mints from now on.
+    /// Legacy LP mints belong to the global PDA or to the pool creator, who has to be the payer then
+    fn move_lp_authority(&self, legacy_bump: u8) -> Result<()> {
+        let mint_authority = self.lp_mint.mint_authority;
+        if mint_authority == COption::Some(self.auth.key()) {
+            return Ok(());
+        }
+
+        let seeds = &[&b"auth"[..], &[legacy_bump]];
+        let signer_seeds = &[&seeds[..]];
+
+        let current_authority = match mint_authority {
+            COption::Some(authority) if authority == self.legacy_auth.key() => self.legacy_auth.to_account_info(),
+            COption::Some(authority) if authority == self.payer.key() => self.payer.to_account_info(),
+            _ => return err!(AmmError::InvalidAuthority),
+        };
+
+        let cpi_accounts = SetAuthority {
+            current_authority,
+            account_or_mint: self.lp_mint.to_account_info(),
+        };
+
+        let ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+
+        set_authority(ctx, AuthorityType::MintTokens, Some(self.auth.key()))
+    }
+
+    /// Convert Config
+    ///
+    /// Helper function to grow the config to the current layout and write it again
+    fn convert_config(&self, legacy: &LegacyConfig, auth_bump: u8) -> Result<()> {
+        let info = self.config.to_account_info();
+
+        let rent = Rent::get()?.minimum_balance(Config::INIT_SPACE).saturating_sub(info.lamports());
+        if rent > 0 {
+            let accounts = Transfer {
+                from: self.payer.to_account_info(),
+                to: info.clone(),
+            };
+            system_program::transfer(CpiContext::new(self.system_program.to_account_info(), accounts), rent)?;
+        }
+
+        info.realloc(Config::INIT_SPACE, false)?;
+        info.try_borrow_mut_data()?[8..].fill(0);
+
+        let mut config = Config::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+        config.migrate(legacy, auth_bump);
+
+        let mut data = info.try_borrow_mut_data()?;
+        config.try_serialize(&mut &mut data[..])
+    }
+}
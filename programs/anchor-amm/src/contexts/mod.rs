@@ -10,5 +10,59 @@ pub use withdraw::*;
 pub mod update;
 pub use update::*;
 
+pub mod apply_fee;
+pub use apply_fee::*;
+
 pub mod swap;
-pub use swap::*;
\ No newline at end of file
+pub use swap::*;
+
+pub mod initialize_concentrated;
+pub use initialize_concentrated::*;
+
+pub mod open_position;
+pub use open_position::*;
+
+pub mod modify_position;
+pub use modify_position::*;
+
+pub mod close_position;
+pub use close_position::*;
+
+pub mod swap_concentrated;
+pub use swap_concentrated::*;
+
+pub mod migrate_vaults;
+pub use migrate_vaults::*;
+
+pub mod collect_protocol_fees;
+pub use collect_protocol_fees::*;
+
+pub mod grow_observations;
+pub use grow_observations::*;
+
+pub mod init_observations;
+pub use init_observations::*;
+
+pub mod flash_loan;
+pub use flash_loan::*;
+
+pub mod route_swap;
+pub use route_swap::*;
+
+pub mod place_order;
+pub use place_order::*;
+
+pub mod fill_order;
+pub use fill_order::*;
+
+pub mod cancel_order;
+pub use cancel_order::*;
+
+pub mod place_long_term_order;
+pub use place_long_term_order::*;
+
+pub mod close_long_term_order;
+pub use close_long_term_order::*;
+
+pub mod init_twamm;
+pub use init_twamm::*;
@@ -0,0 +1,313 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    assert_not_expired, assert_not_locked, Config, ConcentratedPool, Position,
+    helpers::concentrated::calculate_amounts_for_liquidity,
+    errors::AmmError,
+    helpers::token::{amount_before_transfer_fee, transfer_checked_with_hook},
+    events::{LiquidityDeposited, LiquidityWithdrawn},
+};
+
+#[derive(Accounts)]
+pub struct ModifyPosition<'info> {
+    // The position owner, who pays or receives the tokens
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(mint::token_program = x_token_program)]
+    pub x_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = y_token_program)]
+    pub y_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    // We also need the 2 vaults where store X and Y mutables
+    #[account(
+        mut,
+        associated_token::mint = x_mint,
+        associated_token::token_program = x_token_program,
+        associated_token::authority = auth,
+    )]
+    pub x_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = y_mint,
+        associated_token::token_program = y_token_program,
+        associated_token::authority = auth,
+    )]
+    pub y_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = x_mint,
+        associated_token::token_program = x_token_program,
+        associated_token::authority = payer,
+    )]
+    pub x_user_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = y_mint,
+        associated_token::token_program = y_token_program,
+        associated_token::authority = payer,
+    )]
+    pub y_user_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        has_one = config,
+        constraint = position.owner == payer.key() @ AmmError::InvalidAuthority,
+    )]
+    pub position: Box<Account<'info, Position>>,
+
+    #[account(
+        mut,
+        has_one = config,
+        seeds = [b"concentrated", config.key().as_ref()],
+        bump = pool.bump,
+    )]
+    pub pool: Box<Account<'info, ConcentratedPool>>,
+
+    /// CHECK: just a pda for signing. no contains SOL
+    #[account(seeds = [b"auth", config.key().as_ref()], bump = config.auth_bump)]
+    pub auth: UncheckedAccount<'info>,
+
+    #[account(
+        has_one = x_mint,
+        has_one = y_mint,
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+
+    // Each mint can belong to the legacy token program or to Token-2022
+    pub x_token_program: Interface<'info, TokenInterface>,
+    pub y_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ModifyPosition<'info> {
+    /// Increase Liquidity
+    ///
+    /// Add liquidity to the position range. The tokens required depend on where the current price is:
+    /// - below the range only X is needed
+    /// - above the range only Y is needed
+    /// - inside the range both, and the liquidity becomes active straight away
+    pub fn increase_liquidity(
+        &mut self,
+        liquidity: u128,
+        x_max: u64,
+        y_max: u64,
+        expiration: i64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        assert_not_locked!(self.config.deposits_paused());
+        assert_not_expired!(expiration);
+        require!(liquidity > 0, AmmError::ZeroBalance);
+
+        let delta = i128::try_from(liquidity).map_err(|_| AmmError::Overflow)?;
+        let (tick_lower, tick_upper) = (self.position.tick_lower, self.position.tick_upper);
+
+        // Ticks first, so the fee growth inside the range can be read from them
+        self.pool.update_tick(tick_lower, delta, false)?;
+        self.pool.update_tick(tick_upper, delta, true)?;
+        self.collect_fees_into_position()?;
+
+        self.position.liquidity = self.position.liquidity.checked_add(liquidity).ok_or(AmmError::Overflow)?;
+        if self.is_in_range() {
+            self.pool.liquidity = self.pool.liquidity.checked_add(liquidity).ok_or(AmmError::Overflow)?;
+        }
+
+        // Round up, the pool never gives liquidity away
+        let (x, y) = calculate_amounts_for_liquidity(
+            self.pool.sqrt_price,
+            self.pool.tick_current,
+            tick_lower,
+            tick_upper,
+            liquidity,
+            true,
+        ).map_err(AmmError::from)?;
+
+        // The vaults need to receive the liquidity amounts, with transfer fee mints the owner sends more
+        let x_gross = amount_before_transfer_fee(&self.x_mint.to_account_info(), x)?;
+        let y_gross = amount_before_transfer_fee(&self.y_mint.to_account_info(), y)?;
+
+        require!(x_gross <= x_max && y_gross <= y_max, AmmError::SlippageExceeded);
+
+        self.deposit_tokens(true, x_gross, remaining_accounts)?;
+        self.deposit_tokens(false, y_gross, remaining_accounts)?;
+
+        self.x_vault.reload()?;
+        self.y_vault.reload()?;
+
+        emit!(LiquidityDeposited {
+            config: self.config.key(),
+            user: self.payer.key(),
+            liquidity,
+            amount_x: x,
+            amount_y: y,
+            reserve_x: self.x_vault.amount,
+            reserve_y: self.y_vault.amount,
+        });
+
+        Ok(())
+    }
+
+    /// Decrease Liquidity
+    ///
+    /// Remove liquidity from the position range and send it to the owner along with the fees earned.
+    /// Passing zero liquidity just collects the fees
+    pub fn decrease_liquidity(
+        &mut self,
+        liquidity: u128,
+        x_min: u64,
+        y_min: u64,
+        expiration: i64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        assert_not_locked!(self.config.withdrawals_paused());
+        assert_not_expired!(expiration);
+        require!(liquidity <= self.position.liquidity, AmmError::InsufficientBalance);
+
+        let delta = i128::try_from(liquidity).map_err(|_| AmmError::Overflow)?;
+        let (tick_lower, tick_upper) = (self.position.tick_lower, self.position.tick_upper);
+
+        // Fees have to be settled with the old liquidity, before the ticks might be removed
+        self.collect_fees_into_position()?;
+
+        self.position.liquidity -= liquidity;
+        if self.is_in_range() {
+            self.pool.liquidity = self.pool.liquidity.checked_sub(liquidity).ok_or(AmmError::Underflow)?;
+        }
+
+        let (x, y) = calculate_amounts_for_liquidity(
+            self.pool.sqrt_price,
+            self.pool.tick_current,
+            tick_lower,
+            tick_upper,
+            liquidity,
+            false,
+        ).map_err(AmmError::from)?;
+
+        require!(x_min <= x && y_min <= y, AmmError::SlippageExceeded);
+
+        if liquidity > 0 {
+            self.pool.update_tick(tick_lower, -delta, false)?;
+            self.pool.update_tick(tick_upper, -delta, true)?;
+        }
+
+        let x = x.checked_add(self.position.fees_owed_x).ok_or(AmmError::Overflow)?;
+        let y = y.checked_add(self.position.fees_owed_y).ok_or(AmmError::Overflow)?;
+        self.position.fees_owed_x = 0;
+        self.position.fees_owed_y = 0;
+
+        self.withdraw_tokens(true, x, remaining_accounts)?;
+        self.withdraw_tokens(false, y, remaining_accounts)?;
+
+        self.x_vault.reload()?;
+        self.y_vault.reload()?;
+
+        emit!(LiquidityWithdrawn {
+            config: self.config.key(),
+            user: self.payer.key(),
+            liquidity,
+            amount_x: x,
+            amount_y: y,
+            reserve_x: self.x_vault.amount,
+            reserve_y: self.y_vault.amount,
+        });
+
+        Ok(())
+    }
+
+    fn is_in_range(&self) -> bool {
+        self.pool.tick_current >= self.position.tick_lower && self.pool.tick_current < self.position.tick_upper
+    }
+
+    fn collect_fees_into_position(&mut self) -> Result<()> {
+        let (inside_x, inside_y) = self.pool.fee_growth_inside(self.position.tick_lower, self.position.tick_upper)?;
+        self.position.update_fees(inside_x, inside_y)
+    }
+
+    /// Deposit Tokens
+    ///
+    /// Helper function to move tokens (X or Y) from the owner to the vault
+    fn deposit_tokens(&self, is_x: bool, amount: u64, extra_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        if amount == 0 {
+            return Ok(());
+        }
+
+        let (from, to, mint, token_program) = match is_x {
+            true => (
+                self.x_user_ata.to_account_info(),
+                self.x_vault.to_account_info(),
+                &self.x_mint,
+                &self.x_token_program,
+            ),
+            false => (
+                self.y_user_ata.to_account_info(),
+                self.y_vault.to_account_info(),
+                &self.y_mint,
+                &self.y_token_program,
+            ),
+        };
+
+        let cpi_accounts = TransferChecked {
+            from,
+            mint: mint.to_account_info(),
+            to,
+            authority: self.payer.to_account_info(),
+        };
+
+        let ctx = CpiContext::new(token_program.to_account_info(), cpi_accounts);
+
+        transfer_checked_with_hook(ctx, amount, mint.decimals, extra_accounts, &self.config.allowed_hooks)
+    }
+
+    /// Withdraw Tokens
+    ///
+    /// Helper function to move tokens (X or Y) from the vault to the owner, signed by the auth PDA
+    fn withdraw_tokens(&self, is_x: bool, amount: u64, extra_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        if amount == 0 {
+            return Ok(());
+        }
+
+        let (from, to, mint, token_program) = match is_x {
+            true => (
+                self.x_vault.to_account_info(),
+                self.x_user_ata.to_account_info(),
+                &self.x_mint,
+                &self.x_token_program,
+            ),
+            false => (
+                self.y_vault.to_account_info(),
+                self.y_user_ata.to_account_info(),
+                &self.y_mint,
+                &self.y_token_program,
+            ),
+        };
+
+        let cpi_accounts = TransferChecked {
+            from,
+            mint: mint.to_account_info(),
+            to,
+            authority: self.auth.to_account_info(),
+        };
+
+        let config_key = self.config.key();
+        let seeds = &[&b"auth"[..], config_key.as_ref(), &[self.config.auth_bump]];
+        let signer_seeds = &[&seeds[..]];
+
+        let ctx = CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+
+        transfer_checked_with_hook(ctx, amount, mint.decimals, extra_accounts, &self.config.allowed_hooks)
+    }
+}
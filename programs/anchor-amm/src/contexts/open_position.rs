@@ -0,0 +1,74 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    Config, ConcentratedPool, Position,
+    helpers::concentrated::{MAX_TICK, MIN_TICK},
+    errors::AmmError,
+};
+
+#[derive(Accounts)]
+#[instruction(tick_lower: i32, tick_upper: i32)]
+pub struct OpenPosition<'info> {
+    // The owner of the new position
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        has_one = config,
+        seeds = [b"concentrated", config.key().as_ref()],
+        bump = pool.bump,
+    )]
+    pub pool: Box<Account<'info, ConcentratedPool>>,
+
+    // One position per owner and range
+    #[account(
+        init,
+        payer = payer,
+        seeds = [
+            b"position",
+            config.key().as_ref(),
+            payer.key().as_ref(),
+            tick_lower.to_le_bytes().as_ref(),
+            tick_upper.to_le_bytes().as_ref(),
+        ],
+        bump,
+        space = Position::INIT_SPACE
+    )]
+    pub position: Account<'info, Position>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> OpenPosition<'info> {
+    /// Open Position
+    ///
+    /// Create an empty position for a price range. Ticks have to be ordered, in bounds and multiples of the tick spacing
+    pub fn open_position(&mut self, tick_lower: i32, tick_upper: i32, bumps: &OpenPositionBumps) -> Result<()> {
+        let tick_spacing = self.config.tick_spacing()? as i32;
+
+        require!(
+            tick_lower < tick_upper
+                && tick_lower >= MIN_TICK
+                && tick_upper <= MAX_TICK
+                && tick_lower % tick_spacing == 0
+                && tick_upper % tick_spacing == 0,
+            AmmError::InvalidTickRange
+        );
+
+        self.position.init(
+            self.config.key(),
+            self.payer.key(),
+            tick_lower,
+            tick_upper,
+            bumps.position,
+        );
+
+        Ok(())
+    }
+}
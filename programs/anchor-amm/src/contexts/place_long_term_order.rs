@@ -0,0 +1,223 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    assert_non_zero, assert_not_locked, order_expiry, Config, CurveType, LongTermOrder, Twamm,
+    errors::AmmError,
+    events::LongTermOrderPlaced,
+    helpers::{
+        concentrated::{mul_div, Q64},
+        token::transfer_checked_with_hook,
+    },
+    MAX_TWAMM_SLOTS,
+};
+
+#[derive(Accounts)]
+#[instruction(id: u64)]
+pub struct PlaceLongTermOrder<'info> {
+    // The owner of the new order
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(mint::token_program = x_token_program)]
+    pub x_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = y_token_program)]
+    pub y_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    // The input of the order waits in the pool vaults, booked as Config.twamm_balance_x/y
+    #[account(
+        mut,
+        associated_token::mint = x_mint,
+        associated_token::token_program = x_token_program,
+        associated_token::authority = auth,
+    )]
+    pub x_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = y_mint,
+        associated_token::token_program = y_token_program,
+        associated_token::authority = auth,
+    )]
+    pub y_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = x_mint,
+        associated_token::token_program = x_token_program,
+        associated_token::authority = payer,
+    )]
+    pub x_user_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = y_mint,
+        associated_token::token_program = y_token_program,
+        associated_token::authority = payer,
+    )]
+    pub y_user_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        has_one = x_mint,
+        has_one = y_mint,
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        has_one = config,
+        seeds = [b"twamm", config.key().as_ref()],
+        bump = twamm.bump,
+    )]
+    pub twamm: Box<Account<'info, Twamm>>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [
+            b"long_term_order",
+            config.key().as_ref(),
+            payer.key().as_ref(),
+            id.to_le_bytes().as_ref(),
+        ],
+        bump,
+        space = LongTermOrder::INIT_SPACE
+    )]
+    pub order: Box<Account<'info, LongTermOrder>>,
+
+    /// CHECK: just a pda for signing
+    #[account(
+        seeds = [b"auth", config.key().as_ref()],
+        bump = config.auth_bump,
+    )]
+    pub auth: UncheckedAccount<'info>,
+
+    pub x_token_program: Interface<'info, TokenInterface>,
+    pub y_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> PlaceLongTermOrder<'info> {
+    /// Place Long Term Order
+    ///
+    /// Sell `amount_in` at a constant rate over at least `slots`. The order ends on the expiry given
+    /// by order_expiry, so orders can share expiries, and the pool trades it lazily
+    pub fn place_long_term_order(
+        &mut self,
+        id: u64,
+        amount_in: u64,
+        is_x_to_y: bool,
+        slots: u64,
+        bumps: &PlaceLongTermOrderBumps,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        assert_non_zero!([amount_in]);
+        assert_not_locked!(self.config.swaps_paused());
+        self.config.check_idle()?;
+        require!(slots > 0 && slots <= MAX_TWAMM_SLOTS, AmmError::InvalidOrderDuration);
+
+        // Virtual trades use the constant product math
+        require!(self.config.curve_type == CurveType::ConstantProduct, AmmError::UnsupportedCurve);
+
+        // Orders already placed trade up to now, the new one only sells from here
+        self.twamm.execute(&mut self.config, self.x_vault.amount, self.y_vault.amount)?;
+
+        // With transfer fee mints the vault only gets the net amount
+        let vault_balance = self.vault(is_x_to_y).amount;
+        self.deposit_tokens(is_x_to_y, amount_in, remaining_accounts)?;
+        match is_x_to_y {
+            true => self.x_vault.reload()?,
+            false => self.y_vault.reload()?,
+        }
+        let amount = self.vault(is_x_to_y).amount.checked_sub(vault_balance).ok_or(AmmError::Underflow)?;
+
+        // The sale starts where the virtual trades are, which is the current slot unless the pool is empty
+        let start_slot = self.twamm.last_slot;
+        let expiry = order_expiry(start_slot, slots)?;
+
+        let sell_rate = mul_div(amount as u128, Q64, (expiry - start_slot) as u128, false).map_err(AmmError::from)?;
+        require!(sell_rate > 0, AmmError::InvalidAmount);
+
+        let earnings_factor = self.twamm.add_order(is_x_to_y, sell_rate, expiry)?;
+
+        match is_x_to_y {
+            true => self.config.twamm_balance_x = self.config.twamm_balance_x.checked_add(amount).ok_or(AmmError::Overflow)?,
+            false => self.config.twamm_balance_y = self.config.twamm_balance_y.checked_add(amount).ok_or(AmmError::Overflow)?,
+        }
+
+        self.order.set_inner(LongTermOrder {
+            config: self.config.key(),
+            owner: self.payer.key(),
+            id,
+            is_x_to_y,
+            sell_rate,
+            earnings_factor,
+            start_slot,
+            expiry,
+            bump: bumps.order,
+        });
+
+        self.config.touch()?;
+
+        emit!(LongTermOrderPlaced {
+            config: self.config.key(),
+            order: self.order.key(),
+            owner: self.payer.key(),
+            is_x_to_y,
+            amount_in: amount,
+            sell_rate,
+            start_slot,
+            expiry,
+        });
+
+        Ok(())
+    }
+
+    /// Vault
+    ///
+    /// Helper function to get the vault of the input token
+    fn vault(&self, is_x: bool) -> &InterfaceAccount<'info, TokenAccount> {
+        match is_x {
+            true => &self.x_vault,
+            false => &self.y_vault,
+        }
+    }
+
+    /// Deposit Tokens
+    ///
+    /// Helper function to move the input tokens from the user to the vault
+    fn deposit_tokens(&self, is_x: bool, amount: u64, extra_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let (from, to, mint, token_program) = match is_x {
+            true => (
+                self.x_user_ata.to_account_info(),
+                self.x_vault.to_account_info(),
+                &self.x_mint,
+                &self.x_token_program,
+            ),
+            false => (
+                self.y_user_ata.to_account_info(),
+                self.y_vault.to_account_info(),
+                &self.y_mint,
+                &self.y_token_program,
+            ),
+        };
+
+        let cpi_accounts = TransferChecked {
+            from,
+            mint: mint.to_account_info(),
+            to,
+            authority: self.payer.to_account_info(),
+        };
+
+        let ctx = CpiContext::new(token_program.to_account_info(), cpi_accounts);
+
+        transfer_checked_with_hook(ctx, amount, mint.decimals, extra_accounts, &self.config.allowed_hooks)
+    }
+}
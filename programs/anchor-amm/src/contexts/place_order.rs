@@ -0,0 +1,161 @@
+use anchor_lang::{
+    prelude::*,
+    system_program::{self, Transfer},
+};
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    assert_non_zero, Config, CurveType, Order,
+    errors::AmmError,
+    events::OrderPlaced,
+    helpers::token::transfer_checked_with_hook,
+};
+
+#[derive(Accounts)]
+#[instruction(id: u64)]
+pub struct PlaceOrder<'info> {
+    // The owner of the new order
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+
+    // Token sold by the order and token bought, they have to be the two mints of the pool
+    #[account(mint::token_program = input_token_program)]
+    pub input_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = output_token_program)]
+    pub output_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [
+            b"order",
+            config.key().as_ref(),
+            payer.key().as_ref(),
+            id.to_le_bytes().as_ref(),
+        ],
+        bump,
+        space = Order::INIT_SPACE
+    )]
+    pub order: Box<Account<'info, Order>>,
+
+    // The input tokens wait here until the order is filled or cancelled
+    #[account(
+        init,
+        payer = payer,
+        associated_token::mint = input_mint,
+        associated_token::token_program = input_token_program,
+        associated_token::authority = order,
+    )]
+    pub escrow: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        associated_token::mint = input_mint,
+        associated_token::token_program = input_token_program,
+        associated_token::authority = payer,
+    )]
+    pub input_user_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub input_token_program: Interface<'info, TokenInterface>,
+    pub output_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> PlaceOrder<'info> {
+    /// Place Order
+    ///
+    /// Escrow `amount_in` to be sold once the pool reaches `target_price` (X per Y, with
+    /// SPOT_PRICE_PRECISION). The `bounty` lamports go to the keeper who fills it
+    pub fn place_order(
+        &mut self,
+        id: u64,
+        amount_in: u64,
+        target_price: u128,
+        bounty: u64,
+        bumps: &PlaceOrderBumps,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        assert_non_zero!([amount_in]);
+        require!(target_price > 0, AmmError::InvalidAmount);
+
+        // Concentrated pools have no spot price from the vaults to fill against
+        require!(
+            !matches!(self.config.curve_type, CurveType::Concentrated { .. }),
+            AmmError::UnsupportedCurve
+        );
+
+        let (input_mint, output_mint) = (self.input_mint.key(), self.output_mint.key());
+        let is_x_to_y = match (input_mint, output_mint) {
+            (input, output) if input == self.config.x_mint && output == self.config.y_mint => true,
+            (input, output) if input == self.config.y_mint && output == self.config.x_mint => false,
+            _ => return err!(AmmError::InvalidToken),
+        };
+
+        self.deposit_tokens(amount_in, remaining_accounts)?;
+
+        if bounty > 0 {
+            let accounts = Transfer {
+                from: self.payer.to_account_info(),
+                to: self.order.to_account_info(),
+            };
+            system_program::transfer(CpiContext::new(self.system_program.to_account_info(), accounts), bounty)?;
+        }
+
+        // With transfer fee mints the escrow only gets the net amount
+        self.escrow.reload()?;
+
+        self.order.set_inner(Order {
+            config: self.config.key(),
+            owner: self.payer.key(),
+            id,
+            input_mint,
+            output_mint,
+            is_x_to_y,
+            amount_in: self.escrow.amount,
+            target_price,
+            min_amount_out: 0,
+            bounty,
+            bump: bumps.order,
+        });
+        self.order.min_amount_out = self.order.amount_out_at_target()?;
+
+        emit!(OrderPlaced {
+            config: self.config.key(),
+            order: self.order.key(),
+            owner: self.payer.key(),
+            is_x_to_y,
+            amount_in: self.order.amount_in,
+            target_price,
+            min_amount_out: self.order.min_amount_out,
+            bounty,
+        });
+
+        Ok(())
+    }
+
+    /// Deposit Tokens
+    ///
+    /// Helper function to move the input tokens from the user to the escrow
+    fn deposit_tokens(&self, amount: u64, extra_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let cpi_accounts = TransferChecked {
+            from: self.input_user_ata.to_account_info(),
+            mint: self.input_mint.to_account_info(),
+            to: self.escrow.to_account_info(),
+            authority: self.payer.to_account_info(),
+        };
+
+        let ctx = CpiContext::new(self.input_token_program.to_account_info(), cpi_accounts);
+
+        transfer_checked_with_hook(ctx, amount, self.input_mint.decimals, extra_accounts, &self.config.allowed_hooks)
+    }
+}
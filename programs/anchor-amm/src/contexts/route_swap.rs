@@ -0,0 +1,222 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    assert_non_zero, assert_not_expired,
+    errors::AmmError,
+    helpers::{
+        pool::Pool,
+        token::{amount_after_transfer_fee, transfer_checked_with_hook},
+        SwapResult,
+    },
+    MAX_ROUTE_HOPS, MAX_SPLIT_POOLS,
+};
+
+/// Route Swap Context
+///
+/// Shared by route_swap and split_swap. Only the user side of the trade lives here, the pools come
+/// in order through the remaining accounts (`Pool::ACCOUNTS` each), followed by the extra accounts
+/// of the transfer hooks
+#[derive(Accounts)]
+pub struct RouteSwap<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    // First token of the route and last one
+    #[account(mint::token_program = input_token_program)]
+    pub input_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = output_token_program)]
+    pub output_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        associated_token::mint = input_mint,
+        associated_token::token_program = input_token_program,
+        associated_token::authority = payer,
+    )]
+    pub input_user_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = output_mint,
+        associated_token::token_program = output_token_program,
+        associated_token::authority = payer,
+    )]
+    pub output_user_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub input_token_program: Interface<'info, TokenInterface>,
+    pub output_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> RouteSwap<'info> {
+    /// Execute a swap through several pools, A to B in the first one, B to C in the next one...
+    ///
+    /// The user only sends the input to the first pool and gets the output of the last one,
+    /// intermediate tokens go straight from one vault to the next.
+    ///
+    /// # Arguments
+    ///
+    /// * `amount_in` - The amount of input tokens the user sends to the first pool.
+    /// * `min_amount_out` - The minimum amount of output tokens the user expects from the whole route.
+    /// * `hops` - Number of pools in the remaining accounts.
+    /// * `expiration` - timestamp to restrict old swaps which can be expired
+    /// * `remaining_accounts` - the pools in order, then the extra accounts of the transfer hooks, if any
+    pub fn route_swap(
+        &mut self,
+        amount_in: u64,
+        min_amount_out: u64,
+        hops: u8,
+        expiration: i64,
+        remaining_accounts: &'info [AccountInfo<'info>],
+    ) -> Result<()> {
+        assert_non_zero!([amount_in]);
+        assert_not_expired!(expiration);
+        require!(hops > 0 && hops <= MAX_ROUTE_HOPS, AmmError::InvalidRoute);
+
+        let mut pools = Self::load_pools(hops as usize, remaining_accounts)?;
+
+        // Quote every hop. With transfer fee mints each vault only gets the net amount
+        let mut mint = self.input_mint.key();
+        let mut amount = amount_after_transfer_fee(&self.input_mint.to_account_info(), amount_in)?;
+        let mut legs: Vec<(bool, SwapResult)> = Vec::with_capacity(pools.len());
+
+        for pool in pools.iter_mut() {
+            let is_x_to_y = pool.direction(&mint)?;
+            let swap_result = pool.swap(is_x_to_y, amount)?;
+
+            let mint_out = pool.mint_out(is_x_to_y);
+            mint = mint_out.key();
+            amount = amount_after_transfer_fee(&mint_out.to_account_info(), swap_result.withdrawn)?;
+
+            legs.push((is_x_to_y, swap_result));
+        }
+
+        require_keys_eq!(mint, self.output_mint.key(), AmmError::InvalidRoute);
+        require!(amount >= min_amount_out, AmmError::SlippageExceeded);
+
+        // Transfer the input tokens from the user to the first pool
+        self.deposit_tokens(&pools[0], legs[0].0, amount_in, remaining_accounts)?;
+
+        // Each pool sends its output to the next one, the last one to the user
+        for (i, (is_x_to_y, swap_result)) in legs.iter().enumerate() {
+            let to = match pools.get(i + 1) {
+                Some(next) => next.vault_in(legs[i + 1].0).to_account_info(),
+                None => self.output_user_ata.to_account_info(),
+            };
+
+            pools[i].transfer_out(*is_x_to_y, swap_result.withdrawn, to, remaining_accounts)?;
+        }
+
+        for (pool, (is_x_to_y, swap_result)) in pools.iter_mut().zip(legs.iter()) {
+            pool.finish(self.payer.key(), *is_x_to_y, swap_result)?;
+        }
+
+        Ok(())
+    }
+
+    /// Execute a swap split across several pools of the same pair
+    ///
+    /// Each pool gets its own part of the input, and the outputs are added up for a single slippage check.
+    /// ConstantProduct::calculate_optimal_split gives clients the best split for the current reserves.
+    ///
+    /// # Arguments
+    ///
+    /// * `amounts` - The amount of input tokens the user sends to each pool, in the order of the pools.
+    /// * `min_amount_out` - The minimum amount of output tokens the user expects from all the pools together.
+    /// * `expiration` - timestamp to restrict old swaps which can be expired
+    /// * `remaining_accounts` - one pool per amount, then the extra accounts of the transfer hooks, if any
+    pub fn split_swap(
+        &mut self,
+        amounts: Vec<u64>,
+        min_amount_out: u64,
+        expiration: i64,
+        remaining_accounts: &'info [AccountInfo<'info>],
+    ) -> Result<()> {
+        assert_non_zero!(amounts);
+        assert_not_expired!(expiration);
+        require!(!amounts.is_empty() && amounts.len() <= MAX_SPLIT_POOLS, AmmError::InvalidRoute);
+
+        let mut pools = Self::load_pools(amounts.len(), remaining_accounts)?;
+
+        // Quote every leg. With transfer fee mints each vault only gets the net amount
+        let mut amount_out: u64 = 0;
+        let mut legs: Vec<(bool, SwapResult)> = Vec::with_capacity(pools.len());
+
+        for (pool, amount_in) in pools.iter_mut().zip(amounts.iter()) {
+            let is_x_to_y = pool.direction(&self.input_mint.key())?;
+            require_keys_eq!(pool.mint_out(is_x_to_y).key(), self.output_mint.key(), AmmError::InvalidRoute);
+
+            let amount_in_net = amount_after_transfer_fee(&self.input_mint.to_account_info(), *amount_in)?;
+            let swap_result = pool.swap(is_x_to_y, amount_in_net)?;
+
+            let amount_out_net = amount_after_transfer_fee(&self.output_mint.to_account_info(), swap_result.withdrawn)?;
+            amount_out = amount_out.checked_add(amount_out_net).ok_or(AmmError::Overflow)?;
+
+            legs.push((is_x_to_y, swap_result));
+        }
+
+        require!(amount_out >= min_amount_out, AmmError::SlippageExceeded);
+
+        // Every leg moves the input from the user and the output back to the user
+        for ((pool, (is_x_to_y, swap_result)), amount_in) in pools.iter().zip(legs.iter()).zip(amounts.iter()) {
+            self.deposit_tokens(pool, *is_x_to_y, *amount_in, remaining_accounts)?;
+            pool.transfer_out(*is_x_to_y, swap_result.withdrawn, self.output_user_ata.to_account_info(), remaining_accounts)?;
+        }
+
+        for (pool, (is_x_to_y, swap_result)) in pools.iter_mut().zip(legs.iter()) {
+            pool.finish(self.payer.key(), *is_x_to_y, swap_result)?;
+        }
+
+        Ok(())
+    }
+
+    /// Load Pools
+    ///
+    /// Helper function to load the first `count` pools of the remaining accounts. A pool can only
+    /// show up once, its vaults are read before any transfer
+    fn load_pools(count: usize, remaining_accounts: &'info [AccountInfo<'info>]) -> Result<Vec<Pool<'info>>> {
+        let pools_len = count * Pool::ACCOUNTS;
+        require!(remaining_accounts.len() >= pools_len, AmmError::InvalidRoute);
+
+        let pools = remaining_accounts[..pools_len]
+            .chunks(Pool::ACCOUNTS)
+            .map(Pool::load)
+            .collect::<Result<Vec<_>>>()?;
+
+        for (i, pool) in pools.iter().enumerate() {
+            require!(
+                pools[..i].iter().all(|other| other.config.key() != pool.config.key()),
+                AmmError::InvalidRoute
+            );
+        }
+
+        Ok(pools)
+    }
+
+    /// Deposit Tokens
+    ///
+    /// Helper function to send the input of the route from the user to the first pool
+    fn deposit_tokens(
+        &self,
+        pool: &Pool<'info>,
+        is_x_to_y: bool,
+        amount: u64,
+        extra_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let cpi_accounts = TransferChecked {
+            from: self.input_user_ata.to_account_info(),
+            mint: self.input_mint.to_account_info(),
+            to: pool.vault_in(is_x_to_y).to_account_info(),
+            authority: self.payer.to_account_info(),
+        };
+
+        let ctx = CpiContext::new(self.input_token_program.to_account_info(), cpi_accounts);
+
+        transfer_checked_with_hook(ctx, amount, self.input_mint.decimals, extra_accounts, &pool.config.allowed_hooks)
+    }
+}
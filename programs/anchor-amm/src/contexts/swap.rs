@@ -1,19 +1,50 @@
-use anchor_lang::prelude::*;
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        instruction::{AccountMeta, Instruction},
+        program::invoke,
+    },
+};
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token::{transfer, Token, Transfer},
-    token_interface::{Mint, TokenAccount},
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked},
 };
 
 use crate::{
-    assert_non_zero, assert_not_expired, assert_not_locked, Config,
-    helpers::{ConstantProduct, LiquidityPair},
+    assert_non_zero, assert_not_expired, assert_not_locked, Config, Observation, Observations, Twamm,
+    helpers::{
+        token::{
+            amount_after_transfer_fee, amount_before_transfer_fee, is_native_mint, transfer_checked_with_hook, unwrap_sol,
+            wrap_sol,
+        },
+        Curve, LiquidityPair, SwapResult,
+    },
     errors::AmmError, 
+    events::Swapped,
+    FLASH_SWAP_CALLBACK,
 };
 
+/// Flash Swap Callback
+///
+/// Arguments of the `flash_swap_callback` instruction called on the borrower program. The
+/// accounts are the payer followed by the remaining accounts after the program
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct FlashSwapCallback {
+    pub config: Pubkey,
+    pub is_x_to_y: bool,
+    // Amount the vault has to receive before the callback returns, fee included
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee: u64,
+    // Opaque data forwarded from the flash_swap caller
+    pub data: Vec<u8>,
+}
+
 #[derive(Accounts)]
 pub struct Swap<'info> {
+    #[account(mint::token_program = x_token_program)]
     pub x_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = y_token_program)]
     pub y_mint: Box<InterfaceAccount<'info, Mint>>,
 
     #[account(mut)]
@@ -23,12 +54,14 @@ pub struct Swap<'info> {
     #[account(
         mut,
         associated_token::mint = config.x_mint,
+        associated_token::token_program = x_token_program,
         associated_token::authority = auth
     )]
     pub x_vault: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         mut,
         associated_token::mint = config.y_mint,
+        associated_token::token_program = y_token_program,
         associated_token::authority = auth
     )]
     pub y_vault: Box<InterfaceAccount<'info, TokenAccount>>,
@@ -40,22 +73,29 @@ pub struct Swap<'info> {
     )]
     pub lp_mint: Box<InterfaceAccount<'info, Mint>>,
 
-    // The two user ATAs which could get changed if the user deposits X or Y
+    // The two user ATAs which could get changed if the user deposits X or Y. They are created if needed,
+    // so native SOL can be wrapped into a new wSOL account
     #[account(
-        mut,
-        associated_token::mint = config.x_mint,
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = x_mint,
+        associated_token::token_program = x_token_program,
         associated_token::authority = payer,
     )]
     pub x_user_ata: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(
-        mut,
-        associated_token::mint = config.y_mint,
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = y_mint,
+        associated_token::token_program = y_token_program,
         associated_token::authority = payer,
     )]
     pub y_user_ata: Box<InterfaceAccount<'info, TokenAccount>>,
 
     #[account(
         mut,
+        has_one = x_mint,
+        has_one = y_mint,
         seeds = [
             b"config", 
             config.seed.to_le_bytes().as_ref()
@@ -64,15 +104,35 @@ pub struct Swap<'info> {
     )]
     pub config: Account<'info, Config>,
 
+    // Oracle ring buffer, written before every trade
+    #[account(
+        mut,
+        has_one = config,
+        seeds = [b"observations", config.key().as_ref()],
+        bump = observations.bump,
+    )]
+    pub observations: Box<Account<'info, Observations>>,
+
+    // Long-term orders, their virtual trades run before every trade
+    #[account(
+        mut,
+        has_one = config,
+        seeds = [b"twamm", config.key().as_ref()],
+        bump = twamm.bump,
+    )]
+    pub twamm: Box<Account<'info, Twamm>>,
+
     /// CHECK: this is safe
     #[account(
-        seeds = [b"auth"],
+        seeds = [b"auth", config.key().as_ref()],
         bump = config.auth_bump,
     )]
     pub auth: UncheckedAccount<'info>,
 
     // as always we add the required programs to mint, transfer and create accounts
-    pub token_program: Program<'info, Token>,
+    // Each mint can belong to the legacy token program or to Token-2022
+    pub x_token_program: Interface<'info, TokenInterface>,
+    pub y_token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
@@ -86,27 +146,183 @@ impl<'info> Swap<'info> {
     /// * `min_amount_out` - The minimum amount of output tokens the user expects to receive (to enforce slippage protection).
     /// * `is_x_to_y` - Boolean indicating whether the swap is from X to Y (true) or Y to X (false).
     /// * `expiration` - timestamp to restrict old swaps which can be expired
+    /// * `wrap_native` - pay native SOL and receive native SOL when a mint is wSOL, instead of using the user wSOL account
+    /// * `remaining_accounts` - extra accounts required by the transfer hooks of the mints, if any
     pub fn swap(
         &mut self,
         amount_in: u64,
         min_amount_out: u64,
         is_x_to_y: bool,
         expiration: i64,
+        wrap_native: bool,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        // Ensure the input amount is non-zero
+        assert_non_zero!([amount_in]);
+        assert_not_locked!(self.config.swaps_paused());
+        self.config.check_idle()?;
+        assert_not_expired!(expiration);
+
+        // Long-term orders trade first, up to the current slot
+        self.twamm.execute(&mut self.config, self.x_vault.amount, self.y_vault.amount)?;
+
+        // Price accumulators use the reserves from before the trade
+        let (reserve_x, reserve_y) = self.config.reserves(self.x_vault.amount, self.y_vault.amount);
+        self.config.update_price_accumulators(reserve_x, reserve_y)?;
+        self.write_observation();
+
+        // Retrieve the current state of the pool curve
+        let mut curve = self.load_curve()?;
+
+        let pair = match is_x_to_y {
+            true => LiquidityPair::TokenX,
+            false => LiquidityPair::TokenY,
+        };
+
+        // With transfer fee mints the vault only gets the net input and the user only gets the net output
+        let (mint_in, mint_out) = self.mints(is_x_to_y);
+        let amount_in_net = amount_after_transfer_fee(&mint_in, amount_in)?;
+
+        let swap_result = curve
+            .swap(pair, amount_in_net, 0)
+            .map_err(AmmError::from)?;
+
+        assert_non_zero!([swap_result.deposited, swap_result.withdrawn]);
+
+        let amount_out_net = amount_after_transfer_fee(&mint_out, swap_result.withdrawn)?;
+        require!(amount_out_net >= min_amount_out, AmmError::SlippageExceeded);
+
+        // Part of the fee stays in the vault for the protocol
+        self.config.accrue_protocol_fee(is_x_to_y, swap_result.fee)?;
+        self.config.record_swap(is_x_to_y, swap_result.deposited, swap_result.withdrawn, swap_result.fee)?;
+
+        // Transfer the input tokens from the user to the vault, wrapping SOL if asked to
+        self.wrap_if_native(is_x_to_y, amount_in, wrap_native)?;
+        self.deposit_tokens(is_x_to_y, amount_in, remaining_accounts)?;
+        self.unwrap_if_native(is_x_to_y, wrap_native)?;
+
+        // Transfer the output tokens from the vault to the user, unwrapping SOL if asked to
+        self.withdraw_tokens(!is_x_to_y, swap_result.withdrawn, remaining_accounts)?;
+        self.unwrap_if_native(!is_x_to_y, wrap_native)?;
+
+        self.emit_swapped(is_x_to_y, &swap_result)
+    }
+
+    /// Execute a swap from X to Y or Y to X for an exact output amount
+    ///
+    /// # Arguments
+    ///
+    /// * `amount_out` - The exact amount of output tokens (either X or Y) the user wants to receive.
+    /// * `max_amount_in` - The maximum amount of input tokens the user is willing to pay, fee included (to enforce slippage protection).
+    /// * `is_x_to_y` - Boolean indicating whether the swap is from X to Y (true) or Y to X (false).
+    /// * `expiration` - timestamp to restrict old swaps which can be expired
+    /// * `wrap_native` - pay native SOL and receive native SOL when a mint is wSOL, instead of using the user wSOL account
+    /// * `remaining_accounts` - extra accounts required by the transfer hooks of the mints, if any
+    pub fn swap_exact_out(
+        &mut self,
+        amount_out: u64,
+        max_amount_in: u64,
+        is_x_to_y: bool,
+        expiration: i64,
+        wrap_native: bool,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        // Ensure the output amount is non-zero
+        assert_non_zero!([amount_out]);
+        assert_not_locked!(self.config.swaps_paused());
+        self.config.check_idle()?;
+        assert_not_expired!(expiration);
+
+        // Long-term orders trade first, up to the current slot
+        self.twamm.execute(&mut self.config, self.x_vault.amount, self.y_vault.amount)?;
+
+        // Price accumulators use the reserves from before the trade
+        let (reserve_x, reserve_y) = self.config.reserves(self.x_vault.amount, self.y_vault.amount);
+        self.config.update_price_accumulators(reserve_x, reserve_y)?;
+        self.write_observation();
+
+        // Retrieve the current state of the pool curve
+        let mut curve = self.load_curve()?;
+
+        let pair = match is_x_to_y {
+            true => LiquidityPair::TokenX,
+            false => LiquidityPair::TokenY,
+        };
+
+        // With transfer fee mints the vault has to send more than `amount_out`, and the user
+        // has to send more than what the vault needs to receive
+        let (mint_in, mint_out) = self.mints(is_x_to_y);
+        let amount_out_gross = amount_before_transfer_fee(&mint_out, amount_out)?;
+
+        let swap_result = curve
+            .swap_exact_out(pair, amount_out_gross, u64::MAX)
+            .map_err(AmmError::from)?;
+
+        assert_non_zero!([swap_result.deposited, swap_result.withdrawn]);
+
+        let amount_in_gross = amount_before_transfer_fee(&mint_in, swap_result.deposited)?;
+        require!(amount_in_gross <= max_amount_in, AmmError::SlippageExceeded);
+
+        // Part of the fee stays in the vault for the protocol
+        self.config.accrue_protocol_fee(is_x_to_y, swap_result.fee)?;
+        self.config.record_swap(is_x_to_y, swap_result.deposited, swap_result.withdrawn, swap_result.fee)?;
+
+        // Transfer the input tokens from the user to the vault, wrapping SOL if asked to
+        self.wrap_if_native(is_x_to_y, amount_in_gross, wrap_native)?;
+        self.deposit_tokens(is_x_to_y, amount_in_gross, remaining_accounts)?;
+        self.unwrap_if_native(is_x_to_y, wrap_native)?;
+
+        // Transfer the output tokens from the vault to the user, unwrapping SOL if asked to
+        self.withdraw_tokens(!is_x_to_y, swap_result.withdrawn, remaining_accounts)?;
+        self.unwrap_if_native(!is_x_to_y, wrap_native)?;
+
+        self.emit_swapped(is_x_to_y, &swap_result)
+    }
+
+    /// Flash swap from X to Y or Y to X, paying after receiving the output
+    ///
+    /// The output is sent first, then the borrower program gets called with the trade details and
+    /// it has to send the input to the vault before returning.
+    ///
+    /// # Arguments
+    ///
+    /// * `amount_in` - The amount of input tokens (either X or Y) the vault has to receive, fee included.
+    /// * `min_amount_out` - The minimum amount of output tokens the user expects to receive (to enforce slippage protection).
+    /// * `is_x_to_y` - Boolean indicating whether the swap is from X to Y (true) or Y to X (false).
+    /// * `expiration` - timestamp to restrict old swaps which can be expired
+    /// * `data` - opaque data forwarded to the borrower program
+    /// * `remaining_accounts` - the borrower program first, then the accounts of the callback. The
+    ///   extra accounts of the transfer hooks are taken from the same list
+    pub fn flash_swap(
+        &mut self,
+        amount_in: u64,
+        min_amount_out: u64,
+        is_x_to_y: bool,
+        expiration: i64,
+        data: Vec<u8>,
+        remaining_accounts: &[AccountInfo<'info>],
     ) -> Result<()> {
         // Ensure the input amount is non-zero
         assert_non_zero!([amount_in]);
-        assert_not_locked!(self.config.locked);
+        assert_not_locked!(self.config.swaps_paused());
+        self.config.check_idle()?;
         assert_not_expired!(expiration);
 
-        // Retrieve the current state of the Constant Product curve
-        let mut curve = ConstantProduct::init(
-            self.x_vault.amount,
-            self.y_vault.amount,
-            self.lp_mint.supply,
-            self.config.fee,
-            Some(6), // Assuming 6 decimal precision for calculations
-        )
-        .map_err(AmmError::from)?;
+        let (callback_program, callback_accounts) = remaining_accounts
+            .split_first()
+            .ok_or(AmmError::InvalidCallbackProgram)?;
+        require!(callback_program.executable, AmmError::InvalidCallbackProgram);
+
+        // Long-term orders trade first, up to the current slot
+        self.twamm.execute(&mut self.config, self.x_vault.amount, self.y_vault.amount)?;
+
+        // Price accumulators use the reserves from before the trade
+        let (reserve_x, reserve_y) = self.config.reserves(self.x_vault.amount, self.y_vault.amount);
+        self.config.update_price_accumulators(reserve_x, reserve_y)?;
+        self.write_observation();
+
+        // Retrieve the current state of the pool curve
+        let mut curve = self.load_curve()?;
 
         let pair = match is_x_to_y {
             true => LiquidityPair::TokenX,
@@ -114,77 +330,247 @@ impl<'info> Swap<'info> {
         };
 
         let swap_result = curve
-            .swap(pair, amount_in, min_amount_out)
+            .swap(pair, amount_in, 0)
             .map_err(AmmError::from)?;
 
         assert_non_zero!([swap_result.deposited, swap_result.withdrawn]);
 
-        // Transfer the input tokens from the user to the vault
-        self.deposit_tokens(is_x_to_y, swap_result.deposited)?;
+        let (_, mint_out) = self.mints(is_x_to_y);
+        let amount_out_net = amount_after_transfer_fee(&mint_out, swap_result.withdrawn)?;
+        require!(amount_out_net >= min_amount_out, AmmError::SlippageExceeded);
+
+        // Part of the fee stays in the vault for the protocol
+        self.config.accrue_protocol_fee(is_x_to_y, swap_result.fee)?;
+        self.config.record_swap(is_x_to_y, swap_result.deposited, swap_result.withdrawn, swap_result.fee)?;
+
+        let vault_in_balance = match is_x_to_y {
+            true => self.x_vault.amount,
+            false => self.y_vault.amount,
+        };
+
+        // Transfer the output tokens from the vault to the user before getting paid
+        self.withdraw_tokens(!is_x_to_y, swap_result.withdrawn, remaining_accounts)?;
+
+        // The guard has to be in the account data before handing over control
+        self.config.in_callback = true;
+        self.config.exit(&crate::ID)?;
+
+        self.invoke_callback(callback_program, callback_accounts, is_x_to_y, &swap_result, data)?;
+
+        self.config.in_callback = false;
+
+        // The borrower program had to send the input, fee included
+        let vault_in = match is_x_to_y {
+            true => &mut self.x_vault,
+            false => &mut self.y_vault,
+        };
+        vault_in.reload()?;
+
+        let expected = vault_in_balance
+            .checked_add(swap_result.deposited)
+            .ok_or(AmmError::Overflow)?;
+        require!(vault_in.amount >= expected, AmmError::FlashSwapNotPaid);
+
+        self.emit_swapped(is_x_to_y, &swap_result)
+    }
+
+    /// Invoke Callback
+    ///
+    /// Helper function to call `flash_swap_callback` on the borrower program. The payer signature
+    /// is forwarded, so the borrower program can pay from the user accounts
+    fn invoke_callback(
+        &self,
+        callback_program: &AccountInfo<'info>,
+        callback_accounts: &[AccountInfo<'info>],
+        is_x_to_y: bool,
+        swap_result: &SwapResult,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        let args = FlashSwapCallback {
+            config: self.config.key(),
+            is_x_to_y,
+            amount_in: swap_result.deposited,
+            amount_out: swap_result.withdrawn,
+            fee: swap_result.fee,
+            data,
+        };
+
+        let mut ix_data = FLASH_SWAP_CALLBACK.to_vec();
+        args.serialize(&mut ix_data)?;
+
+        let mut accounts = vec![AccountMeta::new(self.payer.key(), true)];
+        accounts.extend(callback_accounts.iter().map(|account| AccountMeta {
+            pubkey: account.key(),
+            is_signer: account.is_signer,
+            is_writable: account.is_writable,
+        }));
+
+        let mut account_infos = vec![self.payer.to_account_info()];
+        account_infos.extend_from_slice(callback_accounts);
+        account_infos.push(callback_program.clone());
+
+        let ix = Instruction {
+            program_id: callback_program.key(),
+            accounts,
+            data: ix_data,
+        };
+
+        invoke(&ix, &account_infos).map_err(Into::into)
+    }
+
+    /// Wrap If Native
+    ///
+    /// Helper function to wrap the SOL the user is about to send when the mint is native and the user
+    /// pays with SOL. Otherwise the tokens come from the user wSOL account like any other mint
+    fn wrap_if_native(&self, is_x: bool, amount: u64, wrap_native: bool) -> Result<()> {
+        let (mint, user_ata, token_program) = match is_x {
+            true => (&self.x_mint, &self.x_user_ata, &self.x_token_program),
+            false => (&self.y_mint, &self.y_user_ata, &self.y_token_program),
+        };
+
+        if !wrap_native || !is_native_mint(&mint.key()) {
+            return Ok(());
+        }
+
+        wrap_sol(&self.payer, user_ata, amount, &self.system_program, token_program)
+    }
+
+    /// Unwrap If Native
+    ///
+    /// Helper function to close the user wSOL account when the mint is native, the user asked for SOL
+    /// and the account was empty before, so the user gets SOL and the rent back
+    fn unwrap_if_native(&self, is_x: bool, unwrap_native: bool) -> Result<()> {
+        let (mint, user_ata, token_program) = match is_x {
+            true => (&self.x_mint, &self.x_user_ata, &self.x_token_program),
+            false => (&self.y_mint, &self.y_user_ata, &self.y_token_program),
+        };
+
+        if !unwrap_native || !is_native_mint(&mint.key()) {
+            return Ok(());
+        }
+
+        unwrap_sol(&self.payer, user_ata, token_program)
+    }
+
+    /// Mints
+    ///
+    /// Helper function to get the input and output mints of the swap
+    fn mints(&self, is_x_to_y: bool) -> (AccountInfo<'info>, AccountInfo<'info>) {
+        match is_x_to_y {
+            true => (self.x_mint.to_account_info(), self.y_mint.to_account_info()),
+            false => (self.y_mint.to_account_info(), self.x_mint.to_account_info()),
+        }
+    }
+
+    /// Write Observation
+    ///
+    /// Helper function to save the accumulators in the oracle ring buffer
+    fn write_observation(&mut self) {
+        self.observations.write(Observation {
+            timestamp: self.config.last_price_timestamp,
+            price_cumulative: self.config.price_x_cumulative,
+            liquidity_cumulative: self.config.liquidity_cumulative,
+        });
+    }
+
+    /// Emit Swapped
+    ///
+    /// Helper function to emit the swap event with the reserves after the transfers
+    fn emit_swapped(&mut self, is_x_to_y: bool, swap_result: &SwapResult) -> Result<()> {
+        self.x_vault.reload()?;
+        self.y_vault.reload()?;
+        let (reserve_x, reserve_y) = self.config.reserves(self.x_vault.amount, self.y_vault.amount);
+
+        emit!(Swapped {
+            config: self.config.key(),
+            user: self.payer.key(),
+            is_x_to_y,
+            amount_in: swap_result.deposited,
+            amount_out: swap_result.withdrawn,
+            fee: swap_result.fee,
+            reserve_x,
+            reserve_y,
+        });
 
-        // Transfer the output tokens from the vault to the user
-        self.withdraw_tokens(!is_x_to_y, swap_result.withdrawn)?;
- 
         Ok(())
     }
 
+    /// Load Curve
+    ///
+    /// Helper function to build the pool curve from the current reserves (vaults without protocol fees)
+    fn load_curve(&self) -> Result<Box<dyn Curve>> {
+        let (reserve_x, reserve_y) = self.config.reserves(self.x_vault.amount, self.y_vault.amount);
+        self.config.curve(reserve_x, reserve_y, self.lp_mint.supply)
+    }
+
     /// Deposit Tokens
     ///
     /// Helper function to deposit tokens (X or Y) to the vault's ATA
-    fn deposit_tokens(&self, is_x_to_y: bool, deposited: u64) -> Result<()> {
-        let (from, to) = match is_x_to_y {
+    fn deposit_tokens(&self, is_x_to_y: bool, deposited: u64, extra_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let (from, to, mint, token_program) = match is_x_to_y {
             true => (
                 self.x_user_ata.to_account_info(),
                 self.x_vault.to_account_info(),
+                &self.x_mint,
+                &self.x_token_program,
             ),
             false => (
                 self.y_user_ata.to_account_info(),
                 self.y_vault.to_account_info(),
+                &self.y_mint,
+                &self.y_token_program,
             ),
         };
 
-        let cpi_program = self.token_program.to_account_info();
+        let cpi_program = token_program.to_account_info();
 
-        let cpi_accounts = Transfer {
+        let cpi_accounts = TransferChecked {
             from,
+            mint: mint.to_account_info(),
             to,
             authority: self.payer.to_account_info(),
         };
 
         let ctx = CpiContext::new(cpi_program, cpi_accounts);
 
-        transfer(ctx, deposited)
+        transfer_checked_with_hook(ctx, deposited, mint.decimals, extra_accounts, &self.config.allowed_hooks)
     }
 
     /// Withdraw Tokens
     ///
     /// Helper function to withdraw tokens (X or Y) to the user's ATA
-    fn withdraw_tokens(&mut self, is_x_to_y: bool, withdrawn: u64) -> Result<()> {
-        let (from, to) = match is_x_to_y {
+    fn withdraw_tokens(&mut self, is_x_to_y: bool, withdrawn: u64, extra_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let (from, to, mint, token_program) = match is_x_to_y {
             true => (
                 self.y_vault.to_account_info(),
                 self.y_user_ata.to_account_info(),
+                &self.y_mint,
+                &self.y_token_program,
             ),
             false => (
                 self.x_vault.to_account_info(),
                 self.x_user_ata.to_account_info(),
+                &self.x_mint,
+                &self.x_token_program,
             ),
         };
 
-        let cpi_program = self.token_program.to_account_info();
+        let cpi_program = token_program.to_account_info();
 
-        let seeds = &[&b"auth"[..], &[self.config.auth_bump]];
+        let config_key = self.config.key();
+        let seeds = &[&b"auth"[..], config_key.as_ref(), &[self.config.auth_bump]];
 
         let signer_seeds = &[&seeds[..]];
 
-        let cpi_accounts = Transfer {
+        let cpi_accounts = TransferChecked {
             from,
+            mint: mint.to_account_info(),
             to,
             authority: self.auth.to_account_info(),
         };
 
         let ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
 
-        transfer(ctx, withdrawn)
+        transfer_checked_with_hook(ctx, withdrawn, mint.decimals, extra_accounts, &self.config.allowed_hooks)
     }
 }
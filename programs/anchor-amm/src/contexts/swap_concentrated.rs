@@ -0,0 +1,220 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    assert_non_zero, assert_not_expired, assert_not_locked, Config, ConcentratedPool,
+    errors::AmmError,
+    events::Swapped,
+    helpers::token::{amount_after_transfer_fee, amount_before_transfer_fee, transfer_checked_with_hook},
+};
+
+#[derive(Accounts)]
+pub struct SwapConcentrated<'info> {
+    #[account(mint::token_program = x_token_program)]
+    pub x_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = y_token_program)]
+    pub y_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    // We also need the 2 vaults where store X and Y mutables
+    #[account(
+        mut,
+        associated_token::mint = x_mint,
+        associated_token::token_program = x_token_program,
+        associated_token::authority = auth
+    )]
+    pub x_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = y_mint,
+        associated_token::token_program = y_token_program,
+        associated_token::authority = auth
+    )]
+    pub y_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        associated_token::mint = x_mint,
+        associated_token::token_program = x_token_program,
+        associated_token::authority = payer,
+    )]
+    pub x_user_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = y_mint,
+        associated_token::token_program = y_token_program,
+        associated_token::authority = payer,
+    )]
+    pub y_user_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        has_one = x_mint,
+        has_one = y_mint,
+        seeds = [b"config", config.seed.to_le_bytes().as_ref()],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        has_one = config,
+        seeds = [b"concentrated", config.key().as_ref()],
+        bump = pool.bump,
+    )]
+    pub pool: Box<Account<'info, ConcentratedPool>>,
+
+    /// CHECK: this is safe
+    #[account(
+        seeds = [b"auth", config.key().as_ref()],
+        bump = config.auth_bump,
+    )]
+    pub auth: UncheckedAccount<'info>,
+
+    // Each mint can belong to the legacy token program or to Token-2022
+    pub x_token_program: Interface<'info, TokenInterface>,
+    pub y_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SwapConcentrated<'info> {
+    /// Execute a swap from X to Y or Y to X in a concentrated pool, crossing as many ticks as needed
+    ///
+    /// # Arguments
+    ///
+    /// * `amount_in` - The maximum amount of input tokens to swap. If the pool runs out of liquidity only part of it is taken.
+    /// * `min_amount_out` - The minimum amount of output tokens the user expects to receive (to enforce slippage protection).
+    /// * `is_x_to_y` - Boolean indicating whether the swap is from X to Y (true) or Y to X (false).
+    /// * `expiration` - timestamp to restrict old swaps which can be expired
+    pub fn swap(
+        &mut self,
+        amount_in: u64,
+        min_amount_out: u64,
+        is_x_to_y: bool,
+        expiration: i64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        assert_non_zero!([amount_in]);
+        assert_not_locked!(self.config.swaps_paused());
+        assert_not_expired!(expiration);
+
+        // With transfer fee mints the pool only gets the net input and the user only gets the net output
+        let (mint_in, mint_out) = match is_x_to_y {
+            true => (self.x_mint.to_account_info(), self.y_mint.to_account_info()),
+            false => (self.y_mint.to_account_info(), self.x_mint.to_account_info()),
+        };
+        let amount_in_net = amount_after_transfer_fee(&mint_in, amount_in)?;
+
+        // The pool takes the protocol share out of every step before it grows the position fees
+        let (swap_result, protocol_amount) =
+            self.pool.swap(amount_in_net, is_x_to_y, self.config.fee, self.config.protocol_fee)?;
+
+        assert_non_zero!([swap_result.deposited, swap_result.withdrawn]);
+        require!(
+            amount_after_transfer_fee(&mint_out, swap_result.withdrawn)? >= min_amount_out,
+            AmmError::SlippageExceeded
+        );
+
+        self.config.book_protocol_fee(is_x_to_y, protocol_amount)?;
+        self.config.record_swap(is_x_to_y, swap_result.deposited, swap_result.withdrawn, swap_result.fee)?;
+
+        // Transfer the input tokens from the user to the vault
+        // The swap stops early when it runs out of liquidity, then the user only sends what was used
+        let amount_in_gross = match swap_result.deposited == amount_in_net {
+            true => amount_in,
+            false => amount_before_transfer_fee(&mint_in, swap_result.deposited)?,
+        };
+        self.deposit_tokens(is_x_to_y, amount_in_gross, remaining_accounts)?;
+
+        // Transfer the output tokens from the vault to the user
+        self.withdraw_tokens(!is_x_to_y, swap_result.withdrawn, remaining_accounts)?;
+
+        self.x_vault.reload()?;
+        self.y_vault.reload()?;
+
+        emit!(Swapped {
+            config: self.config.key(),
+            user: self.payer.key(),
+            is_x_to_y,
+            amount_in: swap_result.deposited,
+            amount_out: swap_result.withdrawn,
+            fee: swap_result.fee,
+            reserve_x: self.x_vault.amount,
+            reserve_y: self.y_vault.amount,
+        });
+
+        Ok(())
+    }
+
+    /// Deposit Tokens
+    ///
+    /// Helper function to deposit tokens (X or Y) to the vault's ATA
+    fn deposit_tokens(&self, is_x: bool, amount: u64, extra_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let (from, to, mint, token_program) = match is_x {
+            true => (
+                self.x_user_ata.to_account_info(),
+                self.x_vault.to_account_info(),
+                &self.x_mint,
+                &self.x_token_program,
+            ),
+            false => (
+                self.y_user_ata.to_account_info(),
+                self.y_vault.to_account_info(),
+                &self.y_mint,
+                &self.y_token_program,
+            ),
+        };
+
+        let cpi_accounts = TransferChecked {
+            from,
+            mint: mint.to_account_info(),
+            to,
+            authority: self.payer.to_account_info(),
+        };
+
+        let ctx = CpiContext::new(token_program.to_account_info(), cpi_accounts);
+
+        transfer_checked_with_hook(ctx, amount, mint.decimals, extra_accounts, &self.config.allowed_hooks)
+    }
+
+    /// Withdraw Tokens
+    ///
+    /// Helper function to withdraw tokens (X or Y) to the user's ATA
+    fn withdraw_tokens(&self, is_x: bool, amount: u64, extra_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let (from, to, mint, token_program) = match is_x {
+            true => (
+                self.x_vault.to_account_info(),
+                self.x_user_ata.to_account_info(),
+                &self.x_mint,
+                &self.x_token_program,
+            ),
+            false => (
+                self.y_vault.to_account_info(),
+                self.y_user_ata.to_account_info(),
+                &self.y_mint,
+                &self.y_token_program,
+            ),
+        };
+
+        let config_key = self.config.key();
+        let seeds = &[&b"auth"[..], config_key.as_ref(), &[self.config.auth_bump]];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = TransferChecked {
+            from,
+            mint: mint.to_account_info(),
+            to,
+            authority: self.auth.to_account_info(),
+        };
+
+        let ctx = CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signer_seeds);
+
+        transfer_checked_with_hook(ctx, amount, mint.decimals, extra_accounts, &self.config.allowed_hooks)
+    }
+}
@@ -2,7 +2,9 @@ use anchor_lang::prelude::*;
 
 use crate::{
     has_update_authority,  Config, 
-    errors::AmmError
+    errors::AmmError,
+    events::{PoolLocked, PoolUnlocked},
+    MAX_ALLOWED_HOOKS, PAUSE_ALL,
 };
 
 #[derive(Accounts)]
@@ -29,6 +31,11 @@ impl<'info> Update<'info> {
 
         self.config.locked = true;
 
+        emit!(PoolLocked {
+            config: self.config.key(),
+            authority: self.payer.key(),
+        });
+
         Ok(())
     }
 
@@ -37,6 +44,195 @@ impl<'info> Update<'info> {
 
         self.config.locked = false;
 
+        emit!(PoolUnlocked {
+            config: self.config.key(),
+            authority: self.payer.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Propose Protocol Fee
+    ///
+    /// Schedule the share of every swap fee (in basis points of the fee) booked for the treasury.
+    /// Like the swap fee, it can't be applied until `fee_delay` seconds have passed and proposing
+    /// again replaces the pending one and restarts the delay
+    pub fn propose_protocol_fee(&mut self, protocol_fee: u16, treasury: Pubkey) -> Result<()> {
+        has_update_authority!(self);
+
+        require!(protocol_fee <= 10000, AmmError::InvalidFee);
+
+        self.config.pending_protocol_fee = Some(protocol_fee);
+        self.config.pending_treasury = treasury;
+        self.config.protocol_fee_eta = Clock::get()?
+            .unix_timestamp
+            .checked_add(self.config.fee_delay)
+            .ok_or(AmmError::Overflow)?;
+
+        Ok(())
+    }
+
+    /// Apply Protocol Fee
+    ///
+    /// Set the pending protocol fee and treasury once the delay is over. Changing the treasury also
+    /// redirects the fees accrued and not collected yet
+    pub fn apply_protocol_fee(&mut self) -> Result<()> {
+        has_update_authority!(self);
+
+        let protocol_fee = self.config.pending_protocol_fee.ok_or(AmmError::NoPendingFee)?;
+        require!(
+            Clock::get()?.unix_timestamp >= self.config.protocol_fee_eta,
+            AmmError::FeeDelayNotElapsed
+        );
+
+        self.config.protocol_fee = protocol_fee;
+        self.config.treasury = self.config.pending_treasury;
+        self.config.pending_protocol_fee = None;
+        self.config.pending_treasury = Pubkey::default();
+        self.config.protocol_fee_eta = 0;
+
+        Ok(())
+    }
+
+    /// Set Flash Fee
+    ///
+    /// Fee in basis points of the borrowed amount charged on flash loans
+    pub fn set_flash_fee(&mut self, flash_fee: u16) -> Result<()> {
+        has_update_authority!(self);
+
+        require!(flash_fee <= 10000, AmmError::InvalidFee);
+
+        self.config.flash_fee = flash_fee;
+
+        Ok(())
+    }
+
+    /// Set Allowed Hooks
+    ///
+    /// Replace the transfer hook programs the pool accepts. Transfers of a mint whose hook
+    /// is not in the list will fail, so a hook can't be swapped for an unknown program
+    pub fn set_allowed_hooks(&mut self, hooks: Vec<Pubkey>) -> Result<()> {
+        has_update_authority!(self);
+
+        require!(hooks.len() <= MAX_ALLOWED_HOOKS, AmmError::TooManyHooks);
+
+        let mut allowed_hooks = [Pubkey::default(); MAX_ALLOWED_HOOKS];
+        allowed_hooks[..hooks.len()].copy_from_slice(&hooks);
+        self.config.allowed_hooks = allowed_hooks;
+
+        Ok(())
+    }
+
+    /// Set Paused
+    ///
+    /// Pause the operations in `flags` (PAUSE_SWAP, PAUSE_DEPOSIT, PAUSE_WITHDRAW). The rest keep their state
+    pub fn set_paused(&mut self, flags: u8) -> Result<()> {
+        has_update_authority!(self);
+
+        require!(flags & !PAUSE_ALL == 0, AmmError::InvalidPauseFlags);
+
+        self.config.paused |= flags;
+
+        Ok(())
+    }
+
+    /// Clear Paused
+    ///
+    /// Resume the operations in `flags`
+    pub fn clear_paused(&mut self, flags: u8) -> Result<()> {
+        has_update_authority!(self);
+
+        require!(flags & !PAUSE_ALL == 0, AmmError::InvalidPauseFlags);
+
+        self.config.paused &= !flags;
+
+        Ok(())
+    }
+
+    /// Set Withdraw Policy
+    ///
+    /// When enabled, withdrawals stay open any time swaps are paused (including the global lock)
+    pub fn set_withdraw_policy(&mut self, withdraw_on_swap_pause: bool) -> Result<()> {
+        has_update_authority!(self);
+
+        self.config.withdraw_on_swap_pause = withdraw_on_swap_pause;
+
+        Ok(())
+    }
+
+    /// Propose Authority
+    ///
+    /// First step to transfer the authority. Nothing changes until the new authority accepts it,
+    /// so a wrong key can't strand the pool. Proposing again replaces the pending authority
+    pub fn propose_authority(&mut self, new_authority: Pubkey) -> Result<()> {
+        has_update_authority!(self);
+
+        self.config.pending_authority = Some(new_authority);
+
+        Ok(())
+    }
+
+    /// Accept Authority
+    ///
+    /// Second step of the transfer, signed by the pending authority
+    pub fn accept_authority(&mut self) -> Result<()> {
+        let pending = self.config.pending_authority.ok_or(AmmError::NoPendingAuthority)?;
+        require_keys_eq!(pending, self.payer.key(), AmmError::InvalidAuthority);
+
+        self.config.authority = Some(pending);
+        self.config.pending_authority = None;
+
+        Ok(())
+    }
+
+    /// Renounce Authority
+    ///
+    /// Remove the authority for good. There is no way to set it again, so the config
+    /// becomes immutable (no lock, unlock or fee changes). The pool has to be open and without
+    /// protocol fees, otherwise it would stay paused or keep fees nobody can collect
+    pub fn renounce_authority(&mut self) -> Result<()> {
+        has_update_authority!(self);
+
+        require!(
+            !self.config.locked
+                && self.config.paused == 0
+                && self.config.protocol_fee == 0
+                && self.config.protocol_fees_x == 0
+                && self.config.protocol_fees_y == 0,
+            AmmError::CannotRenounce
+        );
+
+        self.config.authority = None;
+        self.config.pending_authority = None;
+        self.config.pending_fee = None;
+        self.config.fee_eta = 0;
+        self.config.pending_protocol_fee = None;
+        self.config.pending_treasury = Pubkey::default();
+        self.config.protocol_fee_eta = 0;
+
+        Ok(())
+    }
+
+    /// Propose Fee
+    ///
+    /// Schedule a new fee. It can't be applied until `fee_delay` seconds have passed, proposing
+    /// again replaces the pending fee and restarts the delay.
+    ///
+    /// The registry only allows one pool per pair and fee tier, so anyone can block apply_fee by
+    /// initializing a pool of the pair on the proposed tier during the delay. The authority has to
+    /// propose another tier then
+    pub fn propose_fee(&mut self, new_fee: u16) -> Result<()> {
+        has_update_authority!(self);
+
+        // The same fee would point the registry entry at itself
+        require!(new_fee <= 10000 && new_fee != self.config.fee, AmmError::InvalidFee);
+
+        self.config.pending_fee = Some(new_fee);
+        self.config.fee_eta = Clock::get()?
+            .unix_timestamp
+            .checked_add(self.config.fee_delay)
+            .ok_or(AmmError::Overflow)?;
+
         Ok(())
     }
 }
@@ -1,14 +1,14 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token_interface::{ Mint, TokenAccount },
-    token::{ transfer, burn, Token, Transfer, Burn },
+    token_interface::{ burn, Burn, Mint, TokenAccount, TokenInterface, TransferChecked },
 };
 
 use crate::{
-    assert_not_locked, assert_not_expired, assert_non_zero, Config,
-    helpers::{ ConstantProduct },
+    assert_not_locked, assert_not_expired, assert_non_zero, Config, Twamm,
     errors::AmmError,
+    events::LiquidityWithdrawn,
+    helpers::token::{amount_after_transfer_fee, is_native_mint, transfer_checked_with_hook, unwrap_sol},
 };
 
 #[derive(Accounts)]
@@ -18,12 +18,15 @@ pub struct Withdraw<'info> {
     pub payer: Signer<'info>,
 
     // We keep specifying the both mint pubkeys for X, Y & LP
+    #[account(mint::token_program = x_token_program)]
     pub x_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = y_token_program)]
     pub y_mint: Box<InterfaceAccount<'info, Mint>>,
     #[account(
         mut,
         seeds = [b"lp", config.key().as_ref()],
-        bump = config.lp_bump
+        bump = config.lp_bump,
+        mint::token_program = token_program,
     )]
     pub lp_mint: Box<InterfaceAccount<'info, Mint>>,
     
@@ -31,12 +34,14 @@ pub struct Withdraw<'info> {
     #[account(
         mut,
         associated_token::mint = x_mint,
+        associated_token::token_program = x_token_program,
         associated_token::authority = auth,
     )]
     pub x_vault: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         mut,
         associated_token::mint = y_mint,
+        associated_token::token_program = y_token_program,
         associated_token::authority = auth,
     )]
     pub y_vault: Box<InterfaceAccount<'info, TokenAccount>>,
@@ -44,6 +49,7 @@ pub struct Withdraw<'info> {
         init_if_needed,
         payer = payer,
         associated_token::mint = x_mint,
+        associated_token::token_program = x_token_program,
         associated_token::authority = payer,
     )]
     pub x_user_ata: Box<InterfaceAccount<'info, TokenAccount>>,
@@ -51,21 +57,24 @@ pub struct Withdraw<'info> {
         init_if_needed,
         payer = payer,
         associated_token::mint = y_mint,
+        associated_token::token_program = y_token_program,
         associated_token::authority = payer,
     )]
     pub y_user_ata: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         mut,
         associated_token::mint = lp_mint,
+        associated_token::token_program = token_program,
         associated_token::authority = payer,
     )]
     pub lp_user_ata: Box<InterfaceAccount<'info, TokenAccount>>,
     
     /// CHECK: just a pda for signing. no contains SOL
-    #[account(seeds = [b"auth"], bump = config.auth_bump)]
+    #[account(seeds = [b"auth", config.key().as_ref()], bump = config.auth_bump)]
     pub auth: UncheckedAccount<'info>,
 
     #[account(
+        mut,
         has_one = x_mint,
         has_one = y_mint,
         seeds = [
@@ -75,64 +84,121 @@ pub struct Withdraw<'info> {
         bump = config.config_bump,
     )]
     pub config: Account<'info, Config>,
+
+    // Long-term orders, their virtual trades run before the reserves are read
+    #[account(
+        mut,
+        has_one = config,
+        seeds = [b"twamm", config.key().as_ref()],
+        bump = twamm.bump,
+    )]
+    pub twamm: Box<Account<'info, Twamm>>,
     
-    pub token_program: Program<'info, Token>,
+    // Each mint can belong to the legacy token program or to Token-2022
+    pub x_token_program: Interface<'info, TokenInterface>,
+    pub y_token_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
 
 impl<'info> Withdraw<'info> {
     pub fn withdraw(
-        &self,
+        &mut self,
         amount: u64, // Amount of LP token to burn
         x_min: u64, // Min amount of X we are willing to withdraw
         y_min: u64, // Min amount of Y we are willing to withdraw
         expiration: i64,
+        unwrap_native: bool, // Receive native SOL instead of wSOL when a mint is wSOL
+        remaining_accounts: &[AccountInfo<'info>], // Extra accounts of the transfer hooks
     ) -> Result<()> {
-        assert_not_locked!(self.config.locked);
+        assert_not_locked!(self.config.withdrawals_paused());
+        self.config.check_idle()?;
         assert_not_expired!(expiration);
         assert_non_zero!([amount]);
 
-        let amounts = ConstantProduct::calculate_withdraw_amounts(
-            self.x_vault.amount,
-            self.y_vault.amount,
-            self.lp_mint.supply,
-            amount,
-            6
-        ).map_err(AmmError::from)?;
+        // Long-term orders trade first, so the withdrawal gets the current price
+        self.twamm.execute(&mut self.config, self.x_vault.amount, self.y_vault.amount)?;
+
+        // Protocol fees sitting in the vaults don't belong to the LPs
+        let (reserve_x, reserve_y) = self.config.reserves(self.x_vault.amount, self.y_vault.amount);
+        self.config.update_price_accumulators(reserve_x, reserve_y)?;
+
+        let amounts = self.config
+            .curve(reserve_x, reserve_y, self.lp_mint.supply)?
+            .withdraw_amounts(amount)
+            .map_err(AmmError::from)?;
 
-        // Check for slippage. As long the user wants to withdraw more than the min
-        require!(x_min <= amounts.token_x && y_min <= amounts.token_y, AmmError::SlippageExceeded);
+        // Check for slippage. As long the user receives more than the min (after the transfer fees of the mints)
+        let x_received = amount_after_transfer_fee(&self.x_mint.to_account_info(), amounts.token_x)?;
+        let y_received = amount_after_transfer_fee(&self.y_mint.to_account_info(), amounts.token_y)?;
+        require!(x_min <= x_received && y_min <= y_received, AmmError::SlippageExceeded);
         
         // As usual, we do the trick to try to remove in both
-        self.withdraw_tokens(true, amounts.token_x)?;
-        self.withdraw_tokens(false, amounts.token_y)?;
+        self.withdraw_tokens(true, amounts.token_x, remaining_accounts)?;
+        self.withdraw_tokens(false, amounts.token_y, remaining_accounts)?;
+
+        // Native SOL goes back to the user unwrapped when asked for
+        self.unwrap_if_native(true, unwrap_native)?;
+        self.unwrap_if_native(false, unwrap_native)?;
 
         // And we burn the lp tokens 
-        self.burn_lp_tokens(amount)
+        self.burn_lp_tokens(amount)?;
+
+        self.config.touch()?;
+
+        self.x_vault.reload()?;
+        self.y_vault.reload()?;
+        let (reserve_x, reserve_y) = self.config.reserves(self.x_vault.amount, self.y_vault.amount);
+
+        emit!(LiquidityWithdrawn {
+            config: self.config.key(),
+            user: self.payer.key(),
+            liquidity: amount as u128,
+            amount_x: amounts.token_x,
+            amount_y: amounts.token_y,
+            reserve_x,
+            reserve_y,
+        });
+
+        Ok(())
     }
 
     pub fn withdraw_tokens(
         &self,
         is_x: bool,
         amount:u64,
+        extra_accounts: &[AccountInfo<'info>],
     ) -> Result<()> {
         // To withdrawal we need to decide who is the from and to
-        let (from, to) = match is_x {
-            true => (self.x_vault.to_account_info(), self.x_user_ata.to_account_info()),
-            false => (self.y_vault.to_account_info(), self.y_user_ata.to_account_info())
+        let (from, to, mint, token_program) = match is_x {
+            true => (
+                self.x_vault.to_account_info(),
+                self.x_user_ata.to_account_info(),
+                &self.x_mint,
+                &self.x_token_program,
+            ),
+            false => (
+                self.y_vault.to_account_info(),
+                self.y_user_ata.to_account_info(),
+                &self.y_mint,
+                &self.y_token_program,
+            ),
         };
 
         // Define the transfer accounts 
-        let cpi_accounts = Transfer {
+        let cpi_accounts = TransferChecked {
             from,
+            mint: mint.to_account_info(),
             to,
             authority: self.auth.to_account_info(),
         };
         
         // PRepare the seed array (the program will use the auth PDA to sign it)
+        let config_key = self.config.key();
         let seeds = &[
             &b"auth"[..],
+            config_key.as_ref(),
             &[self.config.auth_bump],
         ];
 
@@ -140,13 +206,30 @@ impl<'info> Withdraw<'info> {
 
         // We define the CPI context
         let ctx = CpiContext::new_with_signer(
-            self.token_program.to_account_info(), 
+            token_program.to_account_info(), 
             cpi_accounts,
             signer_seeds
         );
 
         // We send the transaction
-        transfer(ctx, amount)
+        transfer_checked_with_hook(ctx, amount, mint.decimals, extra_accounts, &self.config.allowed_hooks)
+    }
+
+    /// Unwrap If Native
+    ///
+    /// Helper function to close the user wSOL account when the mint is native, the user asked for SOL
+    /// and the account was empty before, so the user gets SOL and the rent back
+    fn unwrap_if_native(&self, is_x: bool, unwrap_native: bool) -> Result<()> {
+        let (mint, user_ata, token_program) = match is_x {
+            true => (&self.x_mint, &self.x_user_ata, &self.x_token_program),
+            false => (&self.y_mint, &self.y_user_ata, &self.y_token_program),
+        };
+
+        if !unwrap_native || !is_native_mint(&mint.key()) {
+            return Ok(());
+        }
+
+        unwrap_sol(&self.payer, user_ata, token_program)
     }
 
     pub fn burn_lp_tokens(
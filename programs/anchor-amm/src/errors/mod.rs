@@ -1,6 +1,6 @@
 use anchor_lang::prelude::*;
 
-use crate::helpers::CurveError;
+use crate::helpers::{oracle::OracleError, CurveError};
 
 #[error_code]
 pub enum AmmError {
@@ -40,6 +40,72 @@ pub enum AmmError {
     InsufficientBalance,
     #[msg("Zero balance.")]
     ZeroBalance,
+    #[msg("Invalid curve parameters.")]
+    InvalidCurveParameters,
+    #[msg("This instruction is not supported by the pool curve.")]
+    UnsupportedCurve,
+    #[msg("Invalid tick range.")]
+    InvalidTickRange,
+    #[msg("The pool can't hold more initialized ticks.")]
+    TickListFull,
+    #[msg("Position still has liquidity or fees.")]
+    PositionNotEmpty,
+    #[msg("Mints must be sorted, x_mint < y_mint.")]
+    InvalidMintOrder,
+    #[msg("Fee delay is out of range.")]
+    InvalidFeeDelay,
+    #[msg("No fee change has been proposed.")]
+    NoPendingFee,
+    #[msg("The fee change delay hasn't passed yet.")]
+    FeeDelayNotElapsed,
+    #[msg("No authority transfer has been proposed.")]
+    NoPendingAuthority,
+    #[msg("Invalid pause flags.")]
+    InvalidPauseFlags,
+    #[msg("Invalid observations capacity.")]
+    InvalidObservationsCapacity,
+    #[msg("Invalid TWAP window.")]
+    InvalidTwapWindow,
+    #[msg("The TWAP window is older than the stored observations.")]
+    ObservationTooOld,
+    #[msg("The transfer hook program of the mint is not allowed by the pool.")]
+    TransferHookNotAllowed,
+    #[msg("Too many transfer hook programs.")]
+    TooManyHooks,
+    #[msg("A flash loan is active in the pool.")]
+    FlashLoanActive,
+    #[msg("No flash loan to repay.")]
+    NoFlashLoan,
+    #[msg("The flash loan repayment is missing from the transaction.")]
+    FlashRepayNotFound,
+    #[msg("Flash loans can't be borrowed through CPI.")]
+    FlashLoanCpiNotAllowed,
+    #[msg("The flash loan hasn't been repaid in full.")]
+    FlashLoanNotRepaid,
+    #[msg("The pool is in the middle of a flash swap callback.")]
+    ReentrancyDetected,
+    #[msg("Invalid flash swap callback program.")]
+    InvalidCallbackProgram,
+    #[msg("The flash swap input hasn't arrived in the vault.")]
+    FlashSwapNotPaid,
+    #[msg("Invalid pool accounts.")]
+    InvalidPool,
+    #[msg("Invalid swap route.")]
+    InvalidRoute,
+    #[msg("The pool price hasn't reached the order target.")]
+    OrderNotTriggered,
+    #[msg("Invalid long-term order duration.")]
+    InvalidOrderDuration,
+    #[msg("The pool can't hold more long-term order expiries.")]
+    TwammExpiriesFull,
+    #[msg("Invalid long-term order.")]
+    InvalidLongTermOrder,
+    #[msg("The pool already uses its own vaults.")]
+    AlreadyMigrated,
+    #[msg("The pool has to be unlocked, unpaused and without protocol fees to renounce its authority.")]
+    CannotRenounce,
+    #[msg("The curve weights have to be non-zero and add up to 100%.")]
+    InvalidWeights,
 }
 
 impl From<CurveError> for AmmError {
@@ -52,6 +118,18 @@ impl From<CurveError> for AmmError {
             CurveError::InsufficientBalance => AmmError::InsufficientBalance,
             CurveError::ZeroBalance => AmmError::ZeroBalance,
             CurveError::SlippageLimitExceeded => AmmError::SlippageExceeded,
+            CurveError::NoConvergence => AmmError::CurveError,
+            CurveError::InvalidWeights => AmmError::InvalidWeights,
         }
     }
-}
\ No newline at end of file
+}
+
+impl From<OracleError> for AmmError {
+    fn from(error: OracleError) -> AmmError {
+        match error {
+            OracleError::InvalidWindow => AmmError::InvalidTwapWindow,
+            OracleError::ObservationTooOld => AmmError::ObservationTooOld,
+            OracleError::Overflow => AmmError::Overflow,
+        }
+    }
+}
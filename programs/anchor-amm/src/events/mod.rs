@@ -0,0 +1,145 @@
+use anchor_lang::prelude::*;
+
+use crate::CurveType;
+
+// Events emitted by the pool instructions, so indexers can follow the pool activity
+// without diffing token balances. Reserves are always the post-operation ones
+
+#[event]
+pub struct PoolInitialized {
+    pub config: Pubkey,
+    pub payer: Pubkey,
+    pub authority: Option<Pubkey>,
+    pub x_mint: Pubkey,
+    pub y_mint: Pubkey,
+    pub fee: u16,
+    pub curve_type: CurveType,
+}
+
+#[event]
+pub struct LiquidityDeposited {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    // LP tokens minted, or liquidity added to the position in concentrated pools
+    pub liquidity: u128,
+    pub amount_x: u64,
+    pub amount_y: u64,
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+}
+
+#[event]
+pub struct LiquidityWithdrawn {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    // LP tokens burnt, or liquidity removed from the position in concentrated pools
+    pub liquidity: u128,
+    // Amounts sent to the user, including the position fees in concentrated pools
+    pub amount_x: u64,
+    pub amount_y: u64,
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+}
+
+#[event]
+pub struct Swapped {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub is_x_to_y: bool,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    // Fee paid in the input token, protocol share included
+    pub fee: u64,
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+}
+
+#[event]
+pub struct PoolLocked {
+    pub config: Pubkey,
+    pub authority: Pubkey,
+}
+
+#[event]
+pub struct PoolUnlocked {
+    pub config: Pubkey,
+    pub authority: Pubkey,
+}
+
+#[event]
+pub struct FlashLoanRepaid {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub is_x: bool,
+    pub amount: u64,
+    // Paid in the borrowed token, it stays in the vault for the LPs
+    pub fee: u64,
+}
+
+#[event]
+pub struct OrderPlaced {
+    pub config: Pubkey,
+    pub order: Pubkey,
+    pub owner: Pubkey,
+    pub is_x_to_y: bool,
+    // Escrowed amount, net of transfer fees
+    pub amount_in: u64,
+    pub target_price: u128,
+    pub min_amount_out: u64,
+    pub bounty: u64,
+}
+
+#[event]
+pub struct OrderFilled {
+    pub config: Pubkey,
+    pub order: Pubkey,
+    pub owner: Pubkey,
+    pub keeper: Pubkey,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    // Lamports paid to the keeper
+    pub bounty: u64,
+}
+
+#[event]
+pub struct OrderCancelled {
+    pub config: Pubkey,
+    pub order: Pubkey,
+    pub owner: Pubkey,
+    // Tokens returned from the escrow
+    pub amount_in: u64,
+}
+
+#[event]
+pub struct LongTermOrderPlaced {
+    pub config: Pubkey,
+    pub order: Pubkey,
+    pub owner: Pubkey,
+    pub is_x_to_y: bool,
+    // Deposited amount, net of transfer fees
+    pub amount_in: u64,
+    // Tokens sold per slot, Q64.64
+    pub sell_rate: u128,
+    pub start_slot: u64,
+    pub expiry: u64,
+}
+
+#[event]
+pub struct LongTermOrderClosed {
+    pub config: Pubkey,
+    pub order: Pubkey,
+    pub owner: Pubkey,
+    // Output bought by the order
+    pub amount_out: u64,
+    // Input the order didn't sell before it was closed
+    pub amount_unsold: u64,
+}
+
+#[event]
+pub struct VaultsMigrated {
+    pub config: Pubkey,
+    pub payer: Pubkey,
+    // Share of the legacy vaults moved to the pool vaults
+    pub amount_x: u64,
+    pub amount_y: u64,
+}
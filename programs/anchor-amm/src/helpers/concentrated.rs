@@ -0,0 +1,327 @@
+use crate::helpers::CurveError;
+
+// Math used by concentrated liquidity pools.
+//
+// Prices are stored as square roots in Q64.64 fixed point (sqrt(price) * 2^64), where price is Y per X.
+// Each tick is a 0.01% price step, price(tick) = 1.0001^tick.
+
+// Lowest tick supported, price ~ 2^-64.
+pub const MIN_TICK: i32 = -443_636;
+
+// Highest tick supported, price ~ 2^64.
+pub const MAX_TICK: i32 = 443_636;
+
+// 1.0 in Q64.64.
+pub const Q64: u128 = 1 << 64;
+
+// 2^64 / sqrt(1.0001)^(2^i), used to build sqrt(1.0001^tick) bit by bit.
+const TICK_FACTORS: [u128; 19] = [
+    18445821805675392311,
+    18444899583751176498,
+    18443055278223354162,
+    18439367220385604838,
+    18431993317065449817,
+    18417254355718160513,
+    18387811781193591352,
+    18329067761203520168,
+    18212142134806087854,
+    17980523815641551639,
+    17526086738831147013,
+    16651378430235024244,
+    15030750278693429944,
+    12247334978882834399,
+    8131365268884726200,
+    3584323654723342297,
+    696457651847595233,
+    26294789957452057,
+    37481735321082,
+];
+
+// Multiply two u128 into a 256 bit (high, low) pair.
+fn full_mul(a: u128, b: u128) -> (u128, u128) {
+    const MASK: u128 = u64::MAX as u128;
+    let (a1, a0) = (a >> 64, a & MASK);
+    let (b1, b0) = (b >> 64, b & MASK);
+
+    let p00 = a0 * b0;
+    let p01 = a0 * b1;
+    let p10 = a1 * b0;
+    let p11 = a1 * b1;
+
+    let mid = (p00 >> 64) + (p01 & MASK) + (p10 & MASK);
+    let low = (p00 & MASK) | (mid << 64);
+    let high = p11 + (p01 >> 64) + (p10 >> 64) + (mid >> 64);
+    (high, low)
+}
+
+// Calculate a * b / denominator with a 256 bit intermediate product.
+pub fn mul_div(a: u128, b: u128, denominator: u128, round_up: bool) -> Result<u128, CurveError> {
+    mul_add_div(a, b, 0, denominator, round_up)
+}
+
+// Calculate (a * b + addend) / denominator with a 256 bit intermediate.
+pub fn mul_add_div(a: u128, b: u128, addend: u128, denominator: u128, round_up: bool) -> Result<u128, CurveError> {
+    if denominator == 0 {
+        return Err(CurveError::Overflow)
+    }
+
+    let (high, low) = full_mul(a, b);
+    let (low, carry) = low.overflowing_add(addend);
+    let high = high.checked_add(carry as u128).ok_or(CurveError::Overflow)?;
+
+    // The result would not fit in 128 bits.
+    if high >= denominator {
+        return Err(CurveError::Overflow)
+    }
+
+    let (quotient, remainder) = if high == 0 {
+        (low / denominator, low % denominator)
+    } else {
+        // Restoring long division of (high, low) by the denominator.
+        let mut quotient = 0u128;
+        let mut remainder = high;
+        for i in (0..128).rev() {
+            let carry = remainder >> 127;
+            remainder = (remainder << 1) | ((low >> i) & 1);
+            quotient <<= 1;
+            if carry == 1 || remainder >= denominator {
+                remainder = remainder.wrapping_sub(denominator);
+                quotient |= 1;
+            }
+        }
+        (quotient, remainder)
+    };
+
+    if round_up && remainder > 0 {
+        quotient.checked_add(1).ok_or(CurveError::Overflow)
+    } else {
+        Ok(quotient)
+    }
+}
+
+// Calculate sqrt(1.0001^tick) in Q64.64.
+pub fn sqrt_price_at_tick(tick: i32) -> Result<u128, CurveError> {
+    if !(MIN_TICK..=MAX_TICK).contains(&tick) {
+        return Err(CurveError::InvalidPrecision)
+    }
+
+    // Multiply the factors of every bit set in |tick|, which gives sqrt(1.0001^-|tick|).
+    let abs_tick = tick.unsigned_abs();
+    let mut ratio = Q64;
+    for (i, factor) in TICK_FACTORS.iter().enumerate() {
+        if abs_tick & (1 << i) != 0 {
+            ratio = (ratio * factor) >> 64;
+        }
+    }
+
+    // Invert for positive ticks.
+    if tick > 0 {
+        ratio = u128::MAX / ratio;
+    }
+
+    Ok(ratio)
+}
+
+// Calculate the greatest tick whose sqrt price is lower or equal than `sqrt_price`.
+pub fn tick_at_sqrt_price(sqrt_price: u128) -> Result<i32, CurveError> {
+    if sqrt_price < sqrt_price_at_tick(MIN_TICK)? || sqrt_price > sqrt_price_at_tick(MAX_TICK)? {
+        return Err(CurveError::InvalidPrecision)
+    }
+
+    let (mut low, mut high) = (MIN_TICK, MAX_TICK);
+    while low < high {
+        let mid = low + (high - low + 1) / 2;
+        if sqrt_price_at_tick(mid)? <= sqrt_price {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+
+    Ok(low)
+}
+
+// Amount of X between two sqrt prices for some liquidity, L * (sqrt_b - sqrt_a) / (sqrt_a * sqrt_b).
+pub fn calculate_delta_x(sqrt_price_a: u128, sqrt_price_b: u128, liquidity: u128, round_up: bool) -> Result<u128, CurveError> {
+    let (lower, upper) = (sqrt_price_a.min(sqrt_price_b), sqrt_price_a.max(sqrt_price_b));
+    if lower == 0 {
+        return Err(CurveError::ZeroBalance)
+    }
+    let scaled = mul_div(liquidity, upper - lower, upper, round_up)?;
+    mul_div(scaled, Q64, lower, round_up)
+}
+
+// Amount of Y between two sqrt prices for some liquidity, L * (sqrt_b - sqrt_a).
+pub fn calculate_delta_y(sqrt_price_a: u128, sqrt_price_b: u128, liquidity: u128, round_up: bool) -> Result<u128, CurveError> {
+    let (lower, upper) = (sqrt_price_a.min(sqrt_price_b), sqrt_price_a.max(sqrt_price_b));
+    mul_div(liquidity, upper - lower, Q64, round_up)
+}
+
+// Calculate the sqrt price after adding an amount of input at the current liquidity. Rounds against the trader.
+pub fn calculate_next_sqrt_price_from_input(sqrt_price: u128, liquidity: u128, amount_in: u128, is_x_to_y: bool) -> Result<u128, CurveError> {
+    if liquidity == 0 {
+        return Err(CurveError::ZeroBalance)
+    }
+
+    match is_x_to_y {
+        // X in, price goes down: L * sqrt_p / (L + amount * sqrt_p)
+        true => {
+            let product = mul_div(amount_in, sqrt_price, Q64, false)?;
+            let denominator = liquidity.checked_add(product).ok_or(CurveError::Overflow)?;
+            mul_div(liquidity, sqrt_price, denominator, true)
+        }
+        // Y in, price goes up: sqrt_p + amount / L
+        false => {
+            let quotient = mul_div(amount_in, Q64, liquidity, false)?;
+            sqrt_price.checked_add(quotient).ok_or(CurveError::Overflow)
+        }
+    }
+}
+
+// Result of a single swap step within one initialized tick range.
+#[derive(Debug)]
+pub struct SwapStep {
+    pub sqrt_price_next: u128,  // Sqrt price reached at the end of the step.
+    pub amount_in: u128,  // Amount of input used, without the fee.
+    pub amount_out: u128,  // Amount of output released.
+    pub fee: u128,  // Fee charged on top of the input.
+}
+
+// Calculate how far an exact input swap gets before reaching `sqrt_price_target`.
+pub fn compute_swap_step(sqrt_price: u128, sqrt_price_target: u128, liquidity: u128, amount_remaining: u128, fee_basis_points: u16) -> Result<SwapStep, CurveError> {
+    if fee_basis_points >= 10_000 {
+        return Err(CurveError::InvalidFeeAmount)
+    }
+
+    let is_x_to_y = sqrt_price >= sqrt_price_target;
+    let fee_complement = (10_000 - fee_basis_points) as u128;
+    let amount_remaining_less_fee = mul_div(amount_remaining, fee_complement, 10_000, false)?;
+
+    // Input required to reach the target.
+    let amount_in_max = match is_x_to_y {
+        true => calculate_delta_x(sqrt_price_target, sqrt_price, liquidity, true)?,
+        false => calculate_delta_y(sqrt_price, sqrt_price_target, liquidity, true)?,
+    };
+
+    let (sqrt_price_next, amount_in) = if amount_remaining_less_fee >= amount_in_max {
+        (sqrt_price_target, amount_in_max)
+    } else {
+        let next = calculate_next_sqrt_price_from_input(sqrt_price, liquidity, amount_remaining_less_fee, is_x_to_y)?;
+        let amount_in = match is_x_to_y {
+            true => calculate_delta_x(next, sqrt_price, liquidity, true)?,
+            false => calculate_delta_y(sqrt_price, next, liquidity, true)?,
+        };
+        (next, amount_in)
+    };
+
+    let amount_out = match is_x_to_y {
+        true => calculate_delta_y(sqrt_price_next, sqrt_price, liquidity, false)?,
+        false => calculate_delta_x(sqrt_price, sqrt_price_next, liquidity, false)?,
+    };
+
+    // If the target was not reached the whole remainder is consumed, and the leftover goes to the fee.
+    let fee = if sqrt_price_next != sqrt_price_target {
+        amount_remaining.checked_sub(amount_in).ok_or(CurveError::Underflow)?
+    } else {
+        mul_div(amount_in, fee_basis_points as u128, fee_complement, true)?
+    };
+
+    Ok(SwapStep {
+        sqrt_price_next,
+        amount_in,
+        amount_out,
+        fee,
+    })
+}
+
+// Calculate the amounts of X and Y backing some liquidity between two ticks at the current price.
+pub fn calculate_amounts_for_liquidity(sqrt_price: u128, tick_current: i32, tick_lower: i32, tick_upper: i32, liquidity: u128, round_up: bool) -> Result<(u64, u64), CurveError> {
+    let sqrt_price_lower = sqrt_price_at_tick(tick_lower)?;
+    let sqrt_price_upper = sqrt_price_at_tick(tick_upper)?;
+
+    let (amount_x, amount_y) = if tick_current < tick_lower {
+        // Price below the range, all in X.
+        (calculate_delta_x(sqrt_price_lower, sqrt_price_upper, liquidity, round_up)?, 0)
+    } else if tick_current >= tick_upper {
+        // Price above the range, all in Y.
+        (0, calculate_delta_y(sqrt_price_lower, sqrt_price_upper, liquidity, round_up)?)
+    } else {
+        (
+            calculate_delta_x(sqrt_price, sqrt_price_upper, liquidity, round_up)?,
+            calculate_delta_y(sqrt_price_lower, sqrt_price, liquidity, round_up)?,
+        )
+    };
+
+    Ok((
+        u64::try_from(amount_x).map_err(|_| CurveError::Overflow)?,
+        u64::try_from(amount_y).map_err(|_| CurveError::Overflow)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::helpers::concentrated::*;
+
+    #[test]
+    fn mul_div_wide() {
+        // (2^100 * 2^100) / 2^120 = 2^80 needs a 256 bit intermediate product.
+        assert_eq!(mul_div(1 << 100, 1 << 100, 1 << 120, false).unwrap(), 1 << 80);
+        assert_eq!(mul_div(7, 3, 2, false).unwrap(), 10);
+        assert_eq!(mul_div(7, 3, 2, true).unwrap(), 11);
+        assert!(mul_div(u128::MAX, u128::MAX, 1, false).is_err());
+
+        // The addend carries into the high half.
+        assert_eq!(mul_add_div(u128::MAX, 2, 2, 4, false).unwrap(), 1 << 127);
+    }
+
+    #[test]
+    fn tick_math() {
+        // Tick 0 is a price of 1.
+        assert_eq!(sqrt_price_at_tick(0).unwrap(), Q64);
+
+        // Prices increase with ticks and tick_at_sqrt_price is the inverse.
+        for tick in [-100_000, -1, 1, 10, 69_082, 400_000] {
+            let sqrt_price = sqrt_price_at_tick(tick).unwrap();
+            assert!(sqrt_price > sqrt_price_at_tick(tick - 1).unwrap());
+            assert_eq!(tick_at_sqrt_price(sqrt_price).unwrap(), tick);
+            assert_eq!(tick_at_sqrt_price(sqrt_price + 1).unwrap(), tick);
+        }
+
+        // 1.0001^69082 ~ 1000, sqrt ~ 31.62
+        let sqrt_price = sqrt_price_at_tick(69_082).unwrap();
+        assert_eq!(sqrt_price >> 64, 31);
+
+        assert!(sqrt_price_at_tick(MAX_TICK + 1).is_err());
+    }
+
+    #[test]
+    fn liquidity_amounts() {
+        // A range around the current price needs both tokens, out of range ranges only one.
+        let sqrt_price = sqrt_price_at_tick(0).unwrap();
+        let (x, y) = calculate_amounts_for_liquidity(sqrt_price, 0, -100, 100, 1_000_000_000, true).unwrap();
+        assert!(x > 0 && y > 0);
+        assert!(x.abs_diff(y) <= 2);
+
+        let (x, y) = calculate_amounts_for_liquidity(sqrt_price, 0, 100, 200, 1_000_000_000, true).unwrap();
+        assert!(x > 0 && y == 0);
+
+        let (x, y) = calculate_amounts_for_liquidity(sqrt_price, 0, -200, -100, 1_000_000_000, true).unwrap();
+        assert!(x == 0 && y > 0);
+    }
+
+    #[test]
+    fn swap_step() {
+        // A small trade stays inside the range and consumes the whole input.
+        let sqrt_price = sqrt_price_at_tick(0).unwrap();
+        let target = sqrt_price_at_tick(-100).unwrap();
+        let step = compute_swap_step(sqrt_price, target, 1_000_000_000, 1_000, 30).unwrap();
+        assert!(step.sqrt_price_next < sqrt_price && step.sqrt_price_next > target);
+        assert_eq!(step.amount_in + step.fee, 1_000);
+        assert!(step.amount_out < step.amount_in);
+
+        // A large trade stops at the target and leaves input for the next range.
+        let step = compute_swap_step(sqrt_price, target, 1_000_000_000, 1_000_000_000, 30).unwrap();
+        assert_eq!(step.sqrt_price_next, target);
+        assert!(step.amount_in + step.fee < 1_000_000_000);
+    }
+}
@@ -1,6 +1,8 @@
 use std::error::Error;
 use std::fmt;
 
+use crate::helpers::concentrated::{mul_add_div, mul_div};
+
 // Macro to assert that no elements in the array are zero.
 macro_rules! assert_non_zero {
     ($array:expr) => {
@@ -37,6 +39,15 @@ macro_rules! swap_slippage {
     };
 }
 
+// Macro to enforce slippage limits on exact-output swaps.
+macro_rules! swap_exact_out_slippage {
+    ($amount:expr, $max_amount:expr) => {
+        if $amount > $max_amount {
+            return Err(CurveError::SlippageLimitExceeded)
+        }
+    };
+}
+
 // Enum to represent the token pair being swapped.
 #[derive(Debug)]
 pub enum LiquidityPair {
@@ -92,6 +103,8 @@ pub enum CurveError {
     InsufficientBalance,  // Error when there's an insufficient balance.
     ZeroBalance,  // Error when one of the balances is zero.
     SlippageLimitExceeded,  // Error when the slippage limit is exceeded.
+    NoConvergence,  // Error when an iterative solver does not converge.
+    InvalidWeights,  // Error when the weights are zero or don't add up to 100%.
 }
 
 impl Error for CurveError {}
@@ -102,6 +115,28 @@ impl fmt::Display for CurveError {
     }
 }
 
+// Trait implemented by every invariant a pool can be configured with.
+// Instruction handlers only talk to this trait, so new curves can be added without touching them.
+pub trait Curve {
+    // Swap an exact input amount with slippage protection on the output.
+    fn swap(&mut self, token_pair: LiquidityPair, amount: u64, min_withdrawn: u64) -> Result<SwapResult, CurveError>;
+
+    // Swap for an exact output amount with slippage protection on the input.
+    fn swap_exact_out(&mut self, token_pair: LiquidityPair, amount_out: u64, max_deposited: u64) -> Result<SwapResult, CurveError>;
+
+    // Calculate the amount of X and Y required to mint a specific amount of LP tokens.
+    fn deposit_amounts(&self, lp_tokens_to_mint: u64) -> Result<TokenAmounts, CurveError>;
+
+    // Calculate the amount of X and Y released when burning a specific amount of LP tokens.
+    fn withdraw_amounts(&self, lp_tokens_to_burn: u64) -> Result<TokenAmounts, CurveError>;
+
+    // Get the spot price of Token X in terms of Token Y.
+    fn spot_price_x(&self) -> Result<SpotPrice, CurveError>;
+
+    // Get the spot price of Token Y in terms of Token X.
+    fn spot_price_y(&self) -> Result<SpotPrice, CurveError>;
+}
+
 // Struct representing the Constant Product AMM curve.
 #[derive(Debug)]
 pub struct ConstantProduct {
@@ -239,6 +274,111 @@ impl ConstantProduct {
         Self::calculate_x_difference_from_y_swap(balance_y, balance_x, amount_x)
     }
 
+    // Calculate the amount of X (before fees) required to withdraw a specific amount of Y, rounding up against the trader.
+    pub fn calculate_x_required_for_y_out(balance_x: u64, balance_y: u64, amount_y: u64) -> Result<u64, CurveError> {
+        assert_non_zero!([balance_x, balance_y, amount_y]);
+        if amount_y >= balance_y {
+            return Err(CurveError::InsufficientBalance)
+        }
+        let numerator = (balance_x as u128).checked_mul(amount_y as u128).ok_or(CurveError::Overflow)?;
+        let denominator = (balance_y - amount_y) as u128;
+        let required = numerator
+            .checked_add(denominator - 1).ok_or(CurveError::Overflow)?
+            .checked_div(denominator).ok_or(CurveError::Overflow)?;
+        u64::try_from(required).map_err(|_| CurveError::Overflow)
+    }
+
+    // Calculate the amount of Y (before fees) required to withdraw a specific amount of X, rounding up against the trader.
+    pub fn calculate_y_required_for_x_out(balance_x: u64, balance_y: u64, amount_x: u64) -> Result<u64, CurveError> {
+        Self::calculate_x_required_for_y_out(balance_y, balance_x, amount_x)
+    }
+
+    // Gross up an amount so that, once the fee is deducted, at least `amount` remains. Rounds up against the trader.
+    pub fn calculate_amount_with_fee(amount: u64, fee_basis_points: u16) -> Result<u64, CurveError> {
+        if fee_basis_points >= 10_000 {
+            return Err(CurveError::InvalidFeeAmount)
+        }
+        let denominator = (10_000 - fee_basis_points) as u128;
+        let gross = (amount as u128)
+            .checked_mul(10_000).ok_or(CurveError::Overflow)?
+            .checked_add(denominator - 1).ok_or(CurveError::Overflow)?
+            .checked_div(denominator).ok_or(CurveError::Overflow)?;
+        u64::try_from(gross).map_err(|_| CurveError::Overflow)
+    }
+
+    // Split `amount` across several pools of the same pair to get the largest total output. Each pool is
+    // (balance_in, balance_out, fee_basis_points). Meant for clients, it uses floating point so it isn't
+    // built for the program.
+    // The optimum is reached when every used pool ends with the same marginal price, so pools are
+    // filled from the best price down: with gamma = 1 - fee, pool i gets (s * sqrt(gamma * in * out) - in) / gamma
+    // where `s` makes the amounts add up, and pools whose starting price is worse than that level get nothing.
+    #[cfg(not(target_os = "solana"))]
+    pub fn calculate_optimal_split(pools: &[(u64, u64, u16)], amount: u64) -> Result<Vec<u64>, CurveError> {
+        if pools.is_empty() {
+            return Err(CurveError::InsufficientBalance)
+        }
+        for (balance_in, balance_out, fee_basis_points) in pools {
+            assert_non_zero!([*balance_in, *balance_out]);
+            if *fee_basis_points >= 10_000 {
+                return Err(CurveError::InvalidFeeAmount)
+            }
+        }
+
+        let gamma = |fee_basis_points: u16| (10_000 - fee_basis_points) as f64 / 10_000.0;
+        let depth = |(balance_in, balance_out, fee_basis_points): (u64, u64, u16)| {
+            (gamma(fee_basis_points) * balance_in as f64 * balance_out as f64).sqrt()
+        };
+
+        // Best starting price first, gamma * out / in
+        let mut order: Vec<usize> = (0..pools.len()).collect();
+        order.sort_by(|&a, &b| {
+            let price = |(balance_in, balance_out, fee_basis_points): (u64, u64, u16)| {
+                gamma(fee_basis_points) * balance_out as f64 / balance_in as f64
+            };
+            price(pools[b]).total_cmp(&price(pools[a]))
+        });
+
+        // Add pools while the marginal price of the ones in use stays below the next starting price
+        let mut level = 0.0;
+        let mut used = 0;
+        let (mut sum_in, mut sum_depth) = (0.0, 0.0);
+        for (i, &index) in order.iter().enumerate() {
+            let (balance_in, _, fee_basis_points) = pools[index];
+            sum_in += balance_in as f64 / gamma(fee_basis_points);
+            sum_depth += depth(pools[index]) / gamma(fee_basis_points);
+            level = (amount as f64 + sum_in) / sum_depth;
+            used = i + 1;
+
+            match order.get(i + 1) {
+                Some(&next) if level * depth(pools[next]) > pools[next].0 as f64 => continue,
+                _ => break,
+            }
+        }
+
+        let mut split = vec![0u64; pools.len()];
+        for &index in order.iter().take(used) {
+            let (balance_in, _, fee_basis_points) = pools[index];
+            let leg = (level * depth(pools[index]) - balance_in as f64) / gamma(fee_basis_points);
+            split[index] = leg.max(0.0).floor() as u64;
+        }
+
+        // Floating point error can push the legs over `amount`, the excess comes off the largest ones
+        let mut total: u128 = split.iter().map(|leg| *leg as u128).sum();
+        while total > amount as u128 {
+            let largest = split.iter_mut().max().ok_or(CurveError::InsufficientBalance)?;
+            let excess = (total - amount as u128).min(*largest as u128);
+            *largest -= excess as u64;
+            total -= excess;
+        }
+
+        // Rounding leftovers go to the best pool, so the split always adds up to `amount`
+        split[order[0]] = split[order[0]]
+            .checked_add(amount - total as u64)
+            .ok_or(CurveError::Overflow)?;
+
+        Ok(split)
+    }
+
     ////////////////////
     // Getter methods //
     ////////////////////
@@ -343,6 +483,45 @@ impl ConstantProduct {
         })
     }
 
+    // Swap tokens for an exact output amount with slippage protection on the input.
+    // `token_pair` is the token being deposited, as in `swap`.
+    pub fn swap_exact_out(&mut self, token_pair: LiquidityPair, amount_out: u64, max_deposited: u64) -> Result<SwapResult, CurveError> {
+        // Depending on the token pair, calculate the effective input needed and the new balances.
+        let (new_x, new_y, effective_amount) = match token_pair {
+            LiquidityPair::TokenX => {
+                let delta_x = Self::calculate_x_required_for_y_out(self.balance_x, self.balance_y, amount_out)?;
+                let new_x = self.balance_x.checked_add(delta_x).ok_or(CurveError::Overflow)?;
+                let new_y = self.balance_y.checked_sub(amount_out).ok_or(CurveError::Underflow)?;
+                (new_x, new_y, delta_x)
+            }
+            LiquidityPair::TokenY => {
+                let delta_y = Self::calculate_y_required_for_x_out(self.balance_x, self.balance_y, amount_out)?;
+                let new_x = self.balance_x.checked_sub(amount_out).ok_or(CurveError::Underflow)?;
+                let new_y = self.balance_y.checked_add(delta_y).ok_or(CurveError::Overflow)?;
+                (new_x, new_y, delta_y)
+            }
+        };
+
+        // Gross up the effective amount so the fee is paid on top of it.
+        let amount = Self::calculate_amount_with_fee(effective_amount, self.fee_basis_points)?;
+
+        // Ensure that the deposited amount does not exceed the maximum slippage requirement.
+        swap_exact_out_slippage!(amount, max_deposited);
+
+        // Calculate the fee.
+        let fee = amount.checked_sub(effective_amount).ok_or(CurveError::Underflow)?;
+
+        // Update balances.
+        self.balance_x = new_x;
+        self.balance_y = new_y;
+
+        Ok(SwapResult {
+            deposited: amount,
+            fee,
+            withdrawn: amount_out,
+        })
+    }
+
     // Unsafe method to deposit liquidity without slippage protection.
     pub fn deposit_liquidity_unsafe(&mut self, amount_x: u64, amount_y: u64, lp_tokens_to_mint: u64) -> Result<DepositLiquidityResult, CurveError> {
         self.balance_x.checked_add(amount_x).ok_or(CurveError::Overflow)?;
@@ -374,9 +553,645 @@ impl ConstantProduct {
     }
 }
 
+impl Curve for ConstantProduct {
+    fn swap(&mut self, token_pair: LiquidityPair, amount: u64, min_withdrawn: u64) -> Result<SwapResult, CurveError> {
+        ConstantProduct::swap(self, token_pair, amount, min_withdrawn)
+    }
+
+    fn swap_exact_out(&mut self, token_pair: LiquidityPair, amount_out: u64, max_deposited: u64) -> Result<SwapResult, CurveError> {
+        ConstantProduct::swap_exact_out(self, token_pair, amount_out, max_deposited)
+    }
+
+    fn deposit_amounts(&self, lp_tokens_to_mint: u64) -> Result<TokenAmounts, CurveError> {
+        Self::calculate_deposit_amounts(self.balance_x, self.balance_y, self.total_lp_tokens, lp_tokens_to_mint, self.precision)
+    }
+
+    fn withdraw_amounts(&self, lp_tokens_to_burn: u64) -> Result<TokenAmounts, CurveError> {
+        Self::calculate_withdraw_amounts(self.balance_x, self.balance_y, self.total_lp_tokens, lp_tokens_to_burn, self.precision)
+    }
+
+    fn spot_price_x(&self) -> Result<SpotPrice, CurveError> {
+        self.get_spot_price_x()
+    }
+
+    fn spot_price_y(&self) -> Result<SpotPrice, CurveError> {
+        self.get_spot_price_y()
+    }
+}
+
+// Maximum number of Newton iterations used by the StableSwap solvers.
+const STABLE_SWAP_MAX_ITERATIONS: u8 = 255;
+
+// Number of tokens in a StableSwap pool.
+const STABLE_SWAP_N_COINS: u128 = 2;
+
+// Struct representing the StableSwap (amplified invariant) AMM curve.
+// Invariant: Ann * (X + Y) + D = Ann * D + D^3 / (4 * X * Y), with Ann = amp * 2.
+#[derive(Debug)]
+pub struct StableSwap {
+    balance_x: u64,  // Balance of Token X in the pool.
+    balance_y: u64,  // Balance of Token Y in the pool.
+    total_lp_tokens: u64,  // Total LP tokens issued for this pool.
+    fee_basis_points: u16,  // Fee taken for each operation, in basis points (1% = 100 basis points).
+    amp: u64,  // Amplification coefficient. Higher values flatten the curve around the peg.
+    precision: u32,  // Precision used for calculations to avoid rounding errors.
+}
+
+// Main Implementation of the StableSwap struct
+impl StableSwap {
+
+    // Initialize a new StableSwap curve.
+    pub fn init(balance_x: u64, balance_y: u64, initial_lp_tokens: u64, fee_basis_points: u16, amp: u64, precision: Option<u8>) -> Result<StableSwap, CurveError> {
+        // Assert non-zero values for X and Y balances and the amplification.
+        assert_non_zero!([balance_x, balance_y, amp]);
+
+        // Set precision, defaulting to 1,000,000 if not provided.
+        let precision = match precision {
+            Some(p) => 10u32.checked_pow(p as u32).ok_or(CurveError::InvalidPrecision)?,
+            None => 1_000_000,
+        };
+
+        // If no initial LP tokens are provided, set it to the maximum of X or Y to minimize rounding errors.
+        let total_lp_tokens = if initial_lp_tokens > 0 {
+            initial_lp_tokens
+        } else {
+            balance_x.max(balance_y)
+        };
+
+        Ok(StableSwap {
+            balance_x,
+            balance_y,
+            total_lp_tokens,
+            fee_basis_points,
+            amp,
+            precision,
+        })
+    }
+
+    ////////////////////
+    // Static methods //
+    ////////////////////
+
+    // Calculate D_P = D^3 / (4 * X * Y) without computing D^3 directly, D^2 alone can exceed 128 bits.
+    fn calculate_d_p(balance_x: u64, balance_y: u64, invariant: u128) -> Result<u128, CurveError> {
+        let d_p = mul_div(invariant, invariant, (balance_x as u128) * STABLE_SWAP_N_COINS, false)?;
+        mul_div(d_p, invariant, (balance_y as u128) * STABLE_SWAP_N_COINS, false)
+    }
+
+    // Calculate the invariant (D) for the pool using Newton's method.
+    pub fn calculate_invariant(balance_x: u64, balance_y: u64, amp: u64) -> Result<u128, CurveError> {
+        assert_non_zero!([balance_x, balance_y, amp]);
+
+        let sum = (balance_x as u128) + (balance_y as u128);
+        let ann = (amp as u128).checked_mul(STABLE_SWAP_N_COINS).ok_or(CurveError::Overflow)?;
+        let mut invariant = sum;
+
+        for _ in 0..STABLE_SWAP_MAX_ITERATIONS {
+            let d_p = Self::calculate_d_p(balance_x, balance_y, invariant)?;
+            let previous = invariant;
+
+            // D = (Ann * S + D_P * N) * D / ((Ann - 1) * D + (N + 1) * D_P), the product needs 256 bits
+            let numerator = ann
+                .checked_mul(sum).ok_or(CurveError::Overflow)?
+                .checked_add(d_p.checked_mul(STABLE_SWAP_N_COINS).ok_or(CurveError::Overflow)?).ok_or(CurveError::Overflow)?;
+            let denominator = (ann - 1)
+                .checked_mul(invariant).ok_or(CurveError::Overflow)?
+                .checked_add(d_p.checked_mul(STABLE_SWAP_N_COINS + 1).ok_or(CurveError::Overflow)?).ok_or(CurveError::Overflow)?;
+            invariant = mul_div(numerator, invariant, denominator, false)?;
+
+            if invariant.abs_diff(previous) <= 1 {
+                return Ok(invariant)
+            }
+        }
+
+        Err(CurveError::NoConvergence)
+    }
+
+    // Calculate the balance of the other token which keeps the invariant (D) after one balance changes to `new_balance`.
+    pub fn calculate_balance_for_invariant(new_balance: u64, invariant: u128, amp: u64) -> Result<u64, CurveError> {
+        assert_non_zero!([new_balance, amp]);
+
+        let ann = (amp as u128).checked_mul(STABLE_SWAP_N_COINS).ok_or(CurveError::Overflow)?;
+
+        // c = D^3 / (N^2 * X * Ann), b = X + D / Ann
+        let c = mul_div(invariant, invariant, (new_balance as u128) * STABLE_SWAP_N_COINS, false)?;
+        let c = mul_div(c, invariant, ann * STABLE_SWAP_N_COINS, false)?;
+        let b = (new_balance as u128)
+            .checked_add(invariant.checked_div(ann).ok_or(CurveError::Overflow)?).ok_or(CurveError::Overflow)?;
+
+        let mut balance = invariant;
+
+        for _ in 0..STABLE_SWAP_MAX_ITERATIONS {
+            let previous = balance;
+
+            // Y = (Y^2 + c) / (2 * Y + b - D), Y^2 needs 256 bits
+            let denominator = balance
+                .checked_mul(2).ok_or(CurveError::Overflow)?
+                .checked_add(b).ok_or(CurveError::Overflow)?
+                .checked_sub(invariant).ok_or(CurveError::Underflow)?;
+            balance = mul_add_div(balance, balance, c, denominator, false)?;
+
+            if balance.abs_diff(previous) <= 1 {
+                return u64::try_from(balance).map_err(|_| CurveError::Overflow)
+            }
+        }
+
+        Err(CurveError::NoConvergence)
+    }
+
+    // Calculate the amount of Y withdrawn when depositing a specific amount of X, rounding down against the trader.
+    pub fn calculate_y_difference_from_x_swap(balance_x: u64, balance_y: u64, amount_x: u64, amp: u64) -> Result<u64, CurveError> {
+        let invariant = Self::calculate_invariant(balance_x, balance_y, amp)?;
+        let new_x = balance_x.checked_add(amount_x).ok_or(CurveError::Overflow)?;
+        let new_y = Self::calculate_balance_for_invariant(new_x, invariant, amp)?;
+        Ok(balance_y.saturating_sub(new_y).saturating_sub(1))
+    }
+
+    // Calculate the amount of X withdrawn when depositing a specific amount of Y, rounding down against the trader.
+    pub fn calculate_x_difference_from_y_swap(balance_x: u64, balance_y: u64, amount_y: u64, amp: u64) -> Result<u64, CurveError> {
+        Self::calculate_y_difference_from_x_swap(balance_y, balance_x, amount_y, amp)
+    }
+
+    // Calculate the amount of X (before fees) required to withdraw a specific amount of Y, rounding up against the trader.
+    pub fn calculate_x_required_for_y_out(balance_x: u64, balance_y: u64, amount_y: u64, amp: u64) -> Result<u64, CurveError> {
+        assert_non_zero!([amount_y]);
+        if amount_y >= balance_y {
+            return Err(CurveError::InsufficientBalance)
+        }
+        let invariant = Self::calculate_invariant(balance_x, balance_y, amp)?;
+        let new_x = Self::calculate_balance_for_invariant(balance_y - amount_y, invariant, amp)?;
+        new_x
+            .checked_sub(balance_x).ok_or(CurveError::Underflow)?
+            .checked_add(1).ok_or(CurveError::Overflow)
+    }
+
+    // Calculate the amount of Y (before fees) required to withdraw a specific amount of X, rounding up against the trader.
+    pub fn calculate_y_required_for_x_out(balance_x: u64, balance_y: u64, amount_x: u64, amp: u64) -> Result<u64, CurveError> {
+        Self::calculate_x_required_for_y_out(balance_y, balance_x, amount_x, amp)
+    }
+
+    // Calculate the spot price of Token X in terms of Token Y, using the same convention as ConstantProduct (X / Y at the peg-free limit).
+    pub fn calculate_spot_price_x(balance_x: u64, balance_y: u64, amp: u64, precision: u32) -> Result<SpotPrice, CurveError> {
+        let invariant = Self::calculate_invariant(balance_x, balance_y, amp)?;
+        let d_p = Self::calculate_d_p(balance_x, balance_y, invariant)?;
+        let ann = (amp as u128) * STABLE_SWAP_N_COINS;
+
+        // The ratio of partial derivatives of the invariant, divided through by Y to keep it in range.
+        let numerator = ann
+            .checked_mul(balance_x as u128).ok_or(CurveError::Overflow)?
+            .checked_add(d_p.checked_mul(balance_x as u128).ok_or(CurveError::Overflow)? / balance_y as u128).ok_or(CurveError::Overflow)?;
+        let denominator = ann
+            .checked_mul(balance_x as u128).ok_or(CurveError::Overflow)?
+            .checked_add(d_p).ok_or(CurveError::Overflow)?;
+
+        Ok(
+            SpotPrice {
+                amount: numerator
+                    .checked_mul(precision as u128).ok_or(CurveError::Overflow)?
+                    .checked_div(denominator).ok_or(CurveError::Overflow)?,
+                precision
+            }
+        )
+    }
+
+    // Calculate the spot price of Token Y in terms of Token X.
+    pub fn calculate_spot_price_y(balance_x: u64, balance_y: u64, amp: u64, precision: u32) -> Result<SpotPrice, CurveError> {
+        Self::calculate_spot_price_x(balance_y, balance_x, amp, precision)
+    }
+
+    ////////////////////
+    // Setter methods //
+    ////////////////////
+
+    // Swap tokens with slippage protection.
+    pub fn swap(&mut self, token_pair: LiquidityPair, amount: u64, min_withdrawn: u64) -> Result<SwapResult, CurveError> {
+        // Calculate the effective amount after deducting the fee.
+        let effective_amount = (amount as u128)
+            .checked_mul((10_000 - self.fee_basis_points) as u128).ok_or(CurveError::Overflow)?
+            .checked_div(10_000).ok_or(CurveError::Overflow)? as u64;
+
+        // Depending on the token pair, calculate the new balances and the amount to withdraw.
+        let (new_x, new_y, withdrawn_amount) = match token_pair {
+            LiquidityPair::TokenX => {
+                let delta_y = Self::calculate_y_difference_from_x_swap(self.balance_x, self.balance_y, effective_amount, self.amp)?;
+                let new_x = self.balance_x.checked_add(effective_amount).ok_or(CurveError::Overflow)?;
+                let new_y = self.balance_y.checked_sub(delta_y).ok_or(CurveError::Underflow)?;
+                (new_x, new_y, delta_y)
+            }
+            LiquidityPair::TokenY => {
+                let delta_x = Self::calculate_x_difference_from_y_swap(self.balance_x, self.balance_y, effective_amount, self.amp)?;
+                let new_x = self.balance_x.checked_sub(delta_x).ok_or(CurveError::Underflow)?;
+                let new_y = self.balance_y.checked_add(effective_amount).ok_or(CurveError::Overflow)?;
+                (new_x, new_y, delta_x)
+            }
+        };
+
+        // Ensure that the withdrawn amount meets the minimum slippage requirement.
+        swap_slippage!(withdrawn_amount, min_withdrawn);
+
+        // Calculate the fee.
+        let fee = amount.checked_sub(effective_amount).ok_or(CurveError::Underflow)?;
+
+        // Update balances.
+        self.balance_x = new_x;
+        self.balance_y = new_y;
+
+        Ok(SwapResult {
+            deposited: amount,
+            fee,
+            withdrawn: withdrawn_amount,
+        })
+    }
+
+    // Swap tokens for an exact output amount with slippage protection on the input.
+    pub fn swap_exact_out(&mut self, token_pair: LiquidityPair, amount_out: u64, max_deposited: u64) -> Result<SwapResult, CurveError> {
+        // Depending on the token pair, calculate the effective input needed and the new balances.
+        let (new_x, new_y, effective_amount) = match token_pair {
+            LiquidityPair::TokenX => {
+                let delta_x = Self::calculate_x_required_for_y_out(self.balance_x, self.balance_y, amount_out, self.amp)?;
+                let new_x = self.balance_x.checked_add(delta_x).ok_or(CurveError::Overflow)?;
+                let new_y = self.balance_y.checked_sub(amount_out).ok_or(CurveError::Underflow)?;
+                (new_x, new_y, delta_x)
+            }
+            LiquidityPair::TokenY => {
+                let delta_y = Self::calculate_y_required_for_x_out(self.balance_x, self.balance_y, amount_out, self.amp)?;
+                let new_x = self.balance_x.checked_sub(amount_out).ok_or(CurveError::Underflow)?;
+                let new_y = self.balance_y.checked_add(delta_y).ok_or(CurveError::Overflow)?;
+                (new_x, new_y, delta_y)
+            }
+        };
+
+        // Gross up the effective amount so the fee is paid on top of it.
+        let amount = ConstantProduct::calculate_amount_with_fee(effective_amount, self.fee_basis_points)?;
+
+        // Ensure that the deposited amount does not exceed the maximum slippage requirement.
+        swap_exact_out_slippage!(amount, max_deposited);
+
+        // Calculate the fee.
+        let fee = amount.checked_sub(effective_amount).ok_or(CurveError::Underflow)?;
+
+        // Update balances.
+        self.balance_x = new_x;
+        self.balance_y = new_y;
+
+        Ok(SwapResult {
+            deposited: amount,
+            fee,
+            withdrawn: amount_out,
+        })
+    }
+}
+
+impl Curve for StableSwap {
+    fn swap(&mut self, token_pair: LiquidityPair, amount: u64, min_withdrawn: u64) -> Result<SwapResult, CurveError> {
+        StableSwap::swap(self, token_pair, amount, min_withdrawn)
+    }
+
+    fn swap_exact_out(&mut self, token_pair: LiquidityPair, amount_out: u64, max_deposited: u64) -> Result<SwapResult, CurveError> {
+        StableSwap::swap_exact_out(self, token_pair, amount_out, max_deposited)
+    }
+
+    // Liquidity is always added in proportion to the current balances, as with ConstantProduct.
+    fn deposit_amounts(&self, lp_tokens_to_mint: u64) -> Result<TokenAmounts, CurveError> {
+        ConstantProduct::calculate_deposit_amounts(self.balance_x, self.balance_y, self.total_lp_tokens, lp_tokens_to_mint, self.precision)
+    }
+
+    // Liquidity is always removed in proportion to the current balances, as with ConstantProduct.
+    fn withdraw_amounts(&self, lp_tokens_to_burn: u64) -> Result<TokenAmounts, CurveError> {
+        ConstantProduct::calculate_withdraw_amounts(self.balance_x, self.balance_y, self.total_lp_tokens, lp_tokens_to_burn, self.precision)
+    }
+
+    fn spot_price_x(&self) -> Result<SpotPrice, CurveError> {
+        Self::calculate_spot_price_x(self.balance_x, self.balance_y, self.amp, self.precision)
+    }
+
+    fn spot_price_y(&self) -> Result<SpotPrice, CurveError> {
+        Self::calculate_spot_price_y(self.balance_x, self.balance_y, self.amp, self.precision)
+    }
+}
+
+// Fixed point scale (18 decimals) used by the WeightedProduct power functions.
+const WAD: u128 = 1_000_000_000_000_000_000;
+
+// ln(2) scaled by WAD.
+const LN2_WAD: i128 = 693_147_180_559_945_309;
+
+// Upper bound of the relative error of `wad_pow`, scaled by WAD. Results are nudged by it to round against the trader.
+const MAX_POW_RELATIVE_ERROR: u128 = 10_000;
+
+// Weights are expressed in basis points and always add up to 10_000.
+const WEIGHT_BASIS_POINTS: u16 = 10_000;
+
+// A weighted swap can't move more than 30% of a balance at once, keeping the power functions in range.
+const MAX_WEIGHTED_RATIO_BASIS_POINTS: u128 = 3_000;
+
+// Natural logarithm of a WAD fixed point number.
+fn wad_ln(x: u128) -> Result<i128, CurveError> {
+    if x == 0 {
+        return Err(CurveError::InvalidPrecision)
+    }
+
+    // Reduce the range to [1, 2), ln(x) = k * ln(2) + ln(m)
+    let mut k: i128 = 0;
+    let mut m = x;
+    while m >= 2 * WAD {
+        m /= 2;
+        k += 1;
+    }
+    while m < WAD {
+        m *= 2;
+        k -= 1;
+    }
+
+    // ln(m) = 2 * (z + z^3 / 3 + z^5 / 5 + ...), with z = (m - 1) / (m + 1) <= 1/3
+    let z = (m - WAD) * WAD / (m + WAD);
+    let z_squared = z * z / WAD;
+    let mut term = z;
+    let mut sum = 0u128;
+    let mut n = 1u128;
+    while term > 0 {
+        sum += term / n;
+        term = term * z_squared / WAD;
+        n += 2;
+    }
+
+    Ok(k * LN2_WAD + 2 * sum as i128)
+}
+
+// Exponential of a WAD fixed point number.
+fn wad_exp(x: i128) -> Result<u128, CurveError> {
+    // Reduce the range to [0, ln(2)), exp(x) = 2^k * exp(r)
+    let k = x.div_euclid(LN2_WAD);
+    let r = x.rem_euclid(LN2_WAD) as u128;
+
+    // exp(r) = 1 + r + r^2 / 2! + ...
+    let mut term = WAD;
+    let mut sum = WAD;
+    let mut n = 1u128;
+    loop {
+        term = term * r / WAD / n;
+        if term == 0 {
+            break
+        }
+        sum += term;
+        n += 1;
+    }
+
+    if k >= 0 {
+        let shift = u32::try_from(k).map_err(|_| CurveError::Overflow)?;
+        sum.checked_mul(2u128.checked_pow(shift).ok_or(CurveError::Overflow)?).ok_or(CurveError::Overflow)
+    } else {
+        let shift = u32::try_from(-k).map_err(|_| CurveError::Underflow)?;
+        Ok(sum.checked_shr(shift).unwrap_or(0))
+    }
+}
+
+// Raise a WAD fixed point base to a WAD fixed point exponent, rounded up by the maximum relative error.
+fn wad_pow_up(base: u128, exponent: u128) -> Result<u128, CurveError> {
+    let exponent = i128::try_from(exponent).map_err(|_| CurveError::Overflow)?;
+    let power = wad_exp(
+        wad_ln(base)?
+            .checked_mul(exponent).ok_or(CurveError::Overflow)?
+            / WAD as i128,
+    )?;
+    power
+        .checked_add(power.checked_mul(MAX_POW_RELATIVE_ERROR).ok_or(CurveError::Overflow)? / WAD + 1)
+        .ok_or(CurveError::Overflow)
+}
+
+// Struct representing the Weighted (Balancer-style) AMM curve.
+// Invariant: X^weight_x * Y^weight_y = K, with the weights in basis points adding up to 10_000.
+#[derive(Debug)]
+pub struct WeightedProduct {
+    balance_x: u64,  // Balance of Token X in the pool.
+    balance_y: u64,  // Balance of Token Y in the pool.
+    total_lp_tokens: u64,  // Total LP tokens issued for this pool.
+    fee_basis_points: u16,  // Fee taken for each operation, in basis points (1% = 100 basis points).
+    weight_x: u16,  // Weight of Token X, in basis points.
+    weight_y: u16,  // Weight of Token Y, in basis points.
+    precision: u32,  // Precision used for calculations to avoid rounding errors.
+}
+
+// Main Implementation of the WeightedProduct struct
+impl WeightedProduct {
+
+    // Initialize a new Weighted curve.
+    pub fn init(balance_x: u64, balance_y: u64, initial_lp_tokens: u64, fee_basis_points: u16, weight_x: u16, weight_y: u16, precision: Option<u8>) -> Result<WeightedProduct, CurveError> {
+        // Assert non-zero values for X and Y balances.
+        assert_non_zero!([balance_x, balance_y]);
+
+        // Both weights are required and have to add up to 100%.
+        if weight_x == 0 || weight_y == 0 || weight_x.checked_add(weight_y) != Some(WEIGHT_BASIS_POINTS) {
+            return Err(CurveError::InvalidWeights)
+        }
+
+        // Set precision, defaulting to 1,000,000 if not provided.
+        let precision = match precision {
+            Some(p) => 10u32.checked_pow(p as u32).ok_or(CurveError::InvalidPrecision)?,
+            None => 1_000_000,
+        };
+
+        // If no initial LP tokens are provided, set it to the maximum of X or Y to minimize rounding errors.
+        let total_lp_tokens = if initial_lp_tokens > 0 {
+            initial_lp_tokens
+        } else {
+            balance_x.max(balance_y)
+        };
+
+        Ok(WeightedProduct {
+            balance_x,
+            balance_y,
+            total_lp_tokens,
+            fee_basis_points,
+            weight_x,
+            weight_y,
+            precision,
+        })
+    }
+
+    ////////////////////
+    // Static methods //
+    ////////////////////
+
+    // Calculate the amount withdrawn when depositing `amount_in`.
+    // out = balance_out * (1 - (balance_in / (balance_in + amount_in)) ^ (weight_in / weight_out)), rounded down.
+    pub fn calculate_out_given_in(balance_in: u64, weight_in: u16, balance_out: u64, weight_out: u16, amount_in: u64) -> Result<u64, CurveError> {
+        assert_non_zero!([balance_in, balance_out, amount_in]);
+        if (amount_in as u128) * 10_000 > (balance_in as u128) * MAX_WEIGHTED_RATIO_BASIS_POINTS {
+            return Err(CurveError::InsufficientBalance)
+        }
+
+        let new_balance_in = (balance_in as u128) + (amount_in as u128);
+        let base = (balance_in as u128)
+            .checked_mul(WAD).ok_or(CurveError::Overflow)?
+            .checked_add(new_balance_in - 1).ok_or(CurveError::Overflow)?
+            / new_balance_in;
+        let exponent = (weight_in as u128) * WAD / weight_out as u128;
+        let power = wad_pow_up(base, exponent)?.min(WAD);
+
+        let withdrawn = (balance_out as u128)
+            .checked_mul(WAD - power).ok_or(CurveError::Overflow)?
+            / WAD;
+        u64::try_from(withdrawn).map_err(|_| CurveError::Overflow)
+    }
+
+    // Calculate the amount (before fees) required to withdraw `amount_out`.
+    // in = balance_in * ((balance_out / (balance_out - amount_out)) ^ (weight_out / weight_in) - 1), rounded up.
+    pub fn calculate_in_given_out(balance_in: u64, weight_in: u16, balance_out: u64, weight_out: u16, amount_out: u64) -> Result<u64, CurveError> {
+        assert_non_zero!([balance_in, balance_out, amount_out]);
+        if (amount_out as u128) * 10_000 > (balance_out as u128) * MAX_WEIGHTED_RATIO_BASIS_POINTS {
+            return Err(CurveError::InsufficientBalance)
+        }
+
+        let new_balance_out = (balance_out - amount_out) as u128;
+        let base = (balance_out as u128)
+            .checked_mul(WAD).ok_or(CurveError::Overflow)?
+            .checked_add(new_balance_out - 1).ok_or(CurveError::Overflow)?
+            / new_balance_out;
+        let exponent = (weight_out as u128) * WAD / weight_in as u128;
+        let power = wad_pow_up(base, exponent)?.max(WAD);
+
+        let required = (balance_in as u128)
+            .checked_mul(power - WAD).ok_or(CurveError::Overflow)?
+            .checked_add(WAD - 1).ok_or(CurveError::Overflow)?
+            / WAD;
+        u64::try_from(required).map_err(|_| CurveError::Overflow)
+    }
+
+    // Calculate the spot price of Token X in terms of Token Y, (X / weight_x) / (Y / weight_y), matching the ConstantProduct convention.
+    pub fn calculate_spot_price_x(balance_x: u64, balance_y: u64, weight_x: u16, weight_y: u16, precision: u32) -> Result<SpotPrice, CurveError> {
+        assert_non_zero!([balance_x, balance_y]);
+        Ok(
+            SpotPrice {
+                amount: (balance_x as u128)
+                    .checked_mul(weight_y as u128).ok_or(CurveError::Overflow)?
+                    .checked_mul(precision as u128).ok_or(CurveError::Overflow)?
+                    .checked_div((balance_y as u128).checked_mul(weight_x as u128).ok_or(CurveError::Overflow)?).ok_or(CurveError::Overflow)?,
+                precision
+            }
+        )
+    }
+
+    // Calculate the spot price of Token Y in terms of Token X.
+    pub fn calculate_spot_price_y(balance_x: u64, balance_y: u64, weight_x: u16, weight_y: u16, precision: u32) -> Result<SpotPrice, CurveError> {
+        Self::calculate_spot_price_x(balance_y, balance_x, weight_y, weight_x, precision)
+    }
+
+    ////////////////////
+    // Setter methods //
+    ////////////////////
+
+    // Swap tokens with slippage protection.
+    pub fn swap(&mut self, token_pair: LiquidityPair, amount: u64, min_withdrawn: u64) -> Result<SwapResult, CurveError> {
+        // Calculate the effective amount after deducting the fee.
+        let effective_amount = (amount as u128)
+            .checked_mul((10_000 - self.fee_basis_points) as u128).ok_or(CurveError::Overflow)?
+            .checked_div(10_000).ok_or(CurveError::Overflow)? as u64;
+
+        // Depending on the token pair, calculate the new balances and the amount to withdraw.
+        let (new_x, new_y, withdrawn_amount) = match token_pair {
+            LiquidityPair::TokenX => {
+                let delta_y = Self::calculate_out_given_in(self.balance_x, self.weight_x, self.balance_y, self.weight_y, effective_amount)?;
+                let new_x = self.balance_x.checked_add(effective_amount).ok_or(CurveError::Overflow)?;
+                let new_y = self.balance_y.checked_sub(delta_y).ok_or(CurveError::Underflow)?;
+                (new_x, new_y, delta_y)
+            }
+            LiquidityPair::TokenY => {
+                let delta_x = Self::calculate_out_given_in(self.balance_y, self.weight_y, self.balance_x, self.weight_x, effective_amount)?;
+                let new_x = self.balance_x.checked_sub(delta_x).ok_or(CurveError::Underflow)?;
+                let new_y = self.balance_y.checked_add(effective_amount).ok_or(CurveError::Overflow)?;
+                (new_x, new_y, delta_x)
+            }
+        };
+
+        // Ensure that the withdrawn amount meets the minimum slippage requirement.
+        swap_slippage!(withdrawn_amount, min_withdrawn);
+
+        // Calculate the fee.
+        let fee = amount.checked_sub(effective_amount).ok_or(CurveError::Underflow)?;
+
+        // Update balances.
+        self.balance_x = new_x;
+        self.balance_y = new_y;
+
+        Ok(SwapResult {
+            deposited: amount,
+            fee,
+            withdrawn: withdrawn_amount,
+        })
+    }
+
+    // Swap tokens for an exact output amount with slippage protection on the input.
+    pub fn swap_exact_out(&mut self, token_pair: LiquidityPair, amount_out: u64, max_deposited: u64) -> Result<SwapResult, CurveError> {
+        // Depending on the token pair, calculate the effective input needed and the new balances.
+        let (new_x, new_y, effective_amount) = match token_pair {
+            LiquidityPair::TokenX => {
+                let delta_x = Self::calculate_in_given_out(self.balance_x, self.weight_x, self.balance_y, self.weight_y, amount_out)?;
+                let new_x = self.balance_x.checked_add(delta_x).ok_or(CurveError::Overflow)?;
+                let new_y = self.balance_y.checked_sub(amount_out).ok_or(CurveError::Underflow)?;
+                (new_x, new_y, delta_x)
+            }
+            LiquidityPair::TokenY => {
+                let delta_y = Self::calculate_in_given_out(self.balance_y, self.weight_y, self.balance_x, self.weight_x, amount_out)?;
+                let new_x = self.balance_x.checked_sub(amount_out).ok_or(CurveError::Underflow)?;
+                let new_y = self.balance_y.checked_add(delta_y).ok_or(CurveError::Overflow)?;
+                (new_x, new_y, delta_y)
+            }
+        };
+
+        // Gross up the effective amount so the fee is paid on top of it.
+        let amount = ConstantProduct::calculate_amount_with_fee(effective_amount, self.fee_basis_points)?;
+
+        // Ensure that the deposited amount does not exceed the maximum slippage requirement.
+        swap_exact_out_slippage!(amount, max_deposited);
+
+        // Calculate the fee.
+        let fee = amount.checked_sub(effective_amount).ok_or(CurveError::Underflow)?;
+
+        // Update balances.
+        self.balance_x = new_x;
+        self.balance_y = new_y;
+
+        Ok(SwapResult {
+            deposited: amount,
+            fee,
+            withdrawn: amount_out,
+        })
+    }
+}
+
+impl Curve for WeightedProduct {
+    fn swap(&mut self, token_pair: LiquidityPair, amount: u64, min_withdrawn: u64) -> Result<SwapResult, CurveError> {
+        WeightedProduct::swap(self, token_pair, amount, min_withdrawn)
+    }
+
+    fn swap_exact_out(&mut self, token_pair: LiquidityPair, amount_out: u64, max_deposited: u64) -> Result<SwapResult, CurveError> {
+        WeightedProduct::swap_exact_out(self, token_pair, amount_out, max_deposited)
+    }
+
+    // Proportional joins keep the pool weights untouched.
+    fn deposit_amounts(&self, lp_tokens_to_mint: u64) -> Result<TokenAmounts, CurveError> {
+        ConstantProduct::calculate_deposit_amounts(self.balance_x, self.balance_y, self.total_lp_tokens, lp_tokens_to_mint, self.precision)
+    }
+
+    // Proportional exits keep the pool weights untouched.
+    fn withdraw_amounts(&self, lp_tokens_to_burn: u64) -> Result<TokenAmounts, CurveError> {
+        ConstantProduct::calculate_withdraw_amounts(self.balance_x, self.balance_y, self.total_lp_tokens, lp_tokens_to_burn, self.precision)
+    }
+
+    fn spot_price_x(&self) -> Result<SpotPrice, CurveError> {
+        Self::calculate_spot_price_x(self.balance_x, self.balance_y, self.weight_x, self.weight_y, self.precision)
+    }
+
+    fn spot_price_y(&self) -> Result<SpotPrice, CurveError> {
+        Self::calculate_spot_price_y(self.balance_x, self.balance_y, self.weight_x, self.weight_y, self.precision)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::helpers::{ConstantProduct, LiquidityPair};
+    use crate::helpers::{ConstantProduct, Curve, CurveError, LiquidityPair, StableSwap, WeightedProduct};
+    use crate::MAX_AMP;
 
     #[test]
     fn swap_balance() {
@@ -431,6 +1246,47 @@ mod tests {
         assert_eq!(pool.balance_y, 30);
     }
 
+    #[test]
+    fn optimal_split() {
+        // Two equal pools share the trade, up to rounding
+        let split = ConstantProduct::calculate_optimal_split(&[(1_000_000, 1_000_000, 30), (1_000_000, 1_000_000, 30)], 10_000).unwrap();
+        assert_eq!(split.iter().sum::<u64>(), 10_000);
+        assert!(split[0].abs_diff(split[1]) <= 2);
+
+        // A deeper pool gets the larger share, and the split always adds up
+        let split = ConstantProduct::calculate_optimal_split(&[(1_000_000, 1_000_000, 30), (4_000_000, 4_000_000, 30)], 100_000).unwrap();
+        assert_eq!(split.iter().sum::<u64>(), 100_000);
+        assert!(split[1] > 3 * split[0]);
+
+        // A small trade only goes to the pool with the better price
+        let split = ConstantProduct::calculate_optimal_split(&[(1_000_000, 1_000_000, 30), (1_000_000, 1_100_000, 30)], 1_000).unwrap();
+        assert_eq!(split, vec![0, 1_000]);
+
+        // Floats round both halves of u64::MAX up to 2^63, the excess is taken back off
+        let split = ConstantProduct::calculate_optimal_split(&[(1_000_000, 1_000_000, 30), (1_000_000, 1_000_000, 30)], u64::MAX).unwrap();
+        assert_eq!(split.iter().map(|leg| *leg as u128).sum::<u128>(), u64::MAX as u128);
+    }
+
+    #[test]
+    fn optimal_split_beats_single_pool() {
+        let pools = [(1_000_000, 2_000_000, 30), (3_000_000, 6_000_000, 100)];
+        let amount = 500_000;
+
+        let output = |split: &[u64]| -> u64 {
+            split.iter().zip(pools.iter()).filter(|(leg, _)| **leg > 0).map(|(leg, (balance_in, balance_out, fee))| {
+                ConstantProduct::init(*balance_in, *balance_out, 0, *fee, None)
+                    .unwrap()
+                    .swap(LiquidityPair::TokenX, *leg, 0)
+                    .unwrap()
+                    .withdrawn
+            }).sum()
+        };
+
+        let split = ConstantProduct::calculate_optimal_split(&pools, amount).unwrap();
+        assert!(output(&split) >= output(&[amount, 0]));
+        assert!(output(&split) >= output(&[0, amount]));
+    }
+
     #[test]
     fn swap_balance_with_fee() {
         // If we start with 20 of token X and 30 of token Y and precision of 1, K should equal 600
@@ -449,6 +1305,48 @@ mod tests {
         assert_eq!(pool.balance_y, 25);
     }
 
+    #[test]
+    fn swap_exact_out_balance() {
+        // If we start with 20 of token X and 30 of token Y, withdrawing 6 of token Y should cost 5 of token X.
+        // The final balances should be - Token X: 25, Token Y: 24.
+        let mut pool = ConstantProduct::init(20, 30, 0, 0, None).unwrap();
+        let res = pool.swap_exact_out(LiquidityPair::TokenX, 6, 5).unwrap();
+        assert_eq!(res.deposited, 5);
+        assert_eq!(res.withdrawn, 6);
+        assert_eq!(res.fee, 0);
+        assert_eq!(pool.balance_x, 25);
+        assert_eq!(pool.balance_y, 24);
+
+        // Withdrawing 4 of token X in the other direction should cost ceil(24 * 4 / 21) = 5 of token Y.
+        let res = pool.swap_exact_out(LiquidityPair::TokenY, 4, 5).unwrap();
+        assert_eq!(res.deposited, 5);
+        assert_eq!(res.withdrawn, 4);
+        assert_eq!(pool.balance_x, 21);
+        assert_eq!(pool.balance_y, 29);
+    }
+
+    #[test]
+    fn swap_exact_out_with_fee() {
+        // With a 1% fee the 5 of token X needed for 6 of token Y is grossed up to 6, rounding against the trader.
+        let mut pool = ConstantProduct::init(20, 30, 0, 100, None).unwrap();
+        let res = pool.swap_exact_out(LiquidityPair::TokenX, 6, 6).unwrap();
+        assert_eq!(res.deposited, 6);
+        assert_eq!(res.withdrawn, 6);
+        assert_eq!(res.fee, 1);
+        assert_eq!(pool.balance_x, 25);
+        assert_eq!(pool.balance_y, 24);
+    }
+
+    #[test]
+    fn swap_exact_out_slippage() {
+        // Asking for 6 of token Y while paying at most 4 of token X should fail.
+        let mut pool = ConstantProduct::init(20, 30, 0, 0, None).unwrap();
+        assert!(pool.swap_exact_out(LiquidityPair::TokenX, 6, 4).is_err());
+
+        // The pool can never be fully drained.
+        assert!(pool.swap_exact_out(LiquidityPair::TokenX, 30, u64::MAX).is_err());
+    }
+
     #[test]
     fn deposit_liquidity() {
         // If we start with 30 of token X and 30 of token Y and precision of 1, K should equal 900
@@ -470,6 +1368,125 @@ mod tests {
         assert_eq!(r.burned_lp_tokens, 30);
     }
 
+    #[test]
+    fn curve_trait() {
+        // The trait object should quote exactly what the concrete curve quotes.
+        let mut curve: Box<dyn Curve> = Box::new(ConstantProduct::init(20, 30, 20, 0, None).unwrap());
+        let res = curve.swap(LiquidityPair::TokenX, 5, 6).unwrap();
+        assert_eq!(res.withdrawn, 6);
+
+        // Minting half of the LP supply requires half of each balance.
+        let amounts = curve.deposit_amounts(10).unwrap();
+        assert_eq!(amounts.token_x, 12);
+        assert_eq!(amounts.token_y, 12);
+
+        let amounts = curve.withdraw_amounts(10).unwrap();
+        assert_eq!(amounts.token_x, 13);
+        assert_eq!(amounts.token_y, 12);
+    }
+
+    #[test]
+    fn stable_swap_balance() {
+        // A balanced stable pool keeps the invariant close to the sum of the balances.
+        let mut pool = StableSwap::init(1_000_000, 1_000_000, 0, 0, 100, None).unwrap();
+        assert_eq!(StableSwap::calculate_invariant(pool.balance_x, pool.balance_y, pool.amp).unwrap(), 2_000_000);
+
+        // Swapping 10% of the pool should cost far less slippage than x*y=k, which would only return 90_909.
+        let res = pool.swap(LiquidityPair::TokenX, 100_000, 99_000).unwrap();
+        assert_eq!(res.deposited, 100_000);
+        assert!(res.withdrawn > 99_000 && res.withdrawn < 100_000);
+        assert_eq!(pool.balance_x, 1_100_000);
+        assert_eq!(pool.balance_y, 1_000_000 - res.withdrawn);
+
+        // The invariant never decreases because rounding goes against the trader.
+        assert!(StableSwap::calculate_invariant(pool.balance_x, pool.balance_y, pool.amp).unwrap() >= 2_000_000);
+    }
+
+    #[test]
+    fn stable_swap_exact_out() {
+        // Buying back the output of an exact-in swap should cost at least the original input.
+        let mut quote = StableSwap::init(1_000_000, 1_000_000, 0, 30, 100, None).unwrap();
+        let exact_in = quote.swap(LiquidityPair::TokenY, 50_000, 0).unwrap();
+
+        let mut pool = StableSwap::init(1_000_000, 1_000_000, 0, 30, 100, None).unwrap();
+        let exact_out = pool.swap_exact_out(LiquidityPair::TokenY, exact_in.withdrawn, u64::MAX).unwrap();
+        assert_eq!(exact_out.withdrawn, exact_in.withdrawn);
+        assert!(exact_out.deposited >= exact_in.deposited - 2 && exact_out.deposited <= exact_in.deposited + 2);
+
+        // Slippage protection on the input.
+        assert!(pool.swap_exact_out(LiquidityPair::TokenY, 10_000, 10_000).is_err());
+    }
+
+    #[test]
+    fn stable_swap_large_balances() {
+        // D^2 and Ann * S * D don't fit in 128 bits for large balances and a high amplification.
+        let cases = [(100_000_000_000_000_000, 1_000), (100_000_000_000_000_000, MAX_AMP), (10_000_000_000_000_000_000, MAX_AMP)];
+        for (balance, amp) in cases {
+            let invariant = StableSwap::calculate_invariant(balance, balance, amp).unwrap();
+            assert_eq!(invariant, 2 * balance as u128);
+
+            // Swapping 1% of the pool stays close to the peg.
+            let mut pool = StableSwap::init(balance, balance, 0, 0, amp, None).unwrap();
+            let res = pool.swap(LiquidityPair::TokenX, balance / 100, 0).unwrap();
+            assert!(res.withdrawn > balance / 100 * 99 / 100 && res.withdrawn < balance / 100);
+            assert!(StableSwap::calculate_invariant(pool.balance_x, pool.balance_y, amp).unwrap() >= invariant);
+
+            // Buying the output back costs at least what was sold.
+            let mut pool = StableSwap::init(balance, balance, 0, 0, amp, None).unwrap();
+            let exact_out = pool.swap_exact_out(LiquidityPair::TokenX, res.withdrawn, u64::MAX).unwrap();
+            assert!(exact_out.deposited >= res.deposited - 2);
+        }
+    }
+
+    #[test]
+    fn stable_swap_spot_price() {
+        // At the peg both prices are 1, and pushing X into the pool makes X cheaper than Y (X / Y convention).
+        let pool = StableSwap::init(1_000_000, 1_000_000, 0, 0, 100, Some(6)).unwrap();
+        assert_eq!(pool.spot_price_x().unwrap().amount, 1_000_000);
+
+        let pool = StableSwap::init(1_500_000, 500_000, 0, 0, 100, Some(6)).unwrap();
+        let price_x = pool.spot_price_x().unwrap().amount;
+        assert!(price_x > 1_000_000 && price_x < 3_000_000);
+    }
+
+    #[test]
+    fn weighted_equal_weights() {
+        // A 50/50 weighted pool behaves like x*y=k, which returns 90_909 for this trade.
+        let mut pool = WeightedProduct::init(1_000_000, 1_000_000, 0, 0, 5_000, 5_000, None).unwrap();
+        let res = pool.swap(LiquidityPair::TokenX, 100_000, 0).unwrap();
+        assert!(res.withdrawn == 90_908 || res.withdrawn == 90_909);
+        assert_eq!(pool.balance_x, 1_100_000);
+    }
+
+    #[test]
+    fn weighted_80_20() {
+        // With X weighted 80%, selling 5% of X returns 1 - (1 / 1.05)^4 = 17.73% of Y.
+        let mut pool = WeightedProduct::init(1_000_000, 1_000_000, 0, 0, 8_000, 2_000, None).unwrap();
+        let res = pool.swap(LiquidityPair::TokenX, 50_000, 0).unwrap();
+        assert!(res.withdrawn == 177_296 || res.withdrawn == 177_297);
+
+        // Asking for that output the other way round costs the same input, rounded up.
+        let mut pool = WeightedProduct::init(1_000_000, 1_000_000, 0, 0, 8_000, 2_000, None).unwrap();
+        let res = pool.swap_exact_out(LiquidityPair::TokenX, 177_297, u64::MAX).unwrap();
+        assert!(res.deposited == 50_000 || res.deposited == 50_001);
+
+        // Spot price of X follows (X / 0.8) / (Y / 0.2).
+        let pool = WeightedProduct::init(4_000_000, 1_000_000, 0, 0, 8_000, 2_000, Some(6)).unwrap();
+        assert_eq!(pool.spot_price_x().unwrap().amount, 1_000_000);
+    }
+
+    #[test]
+    fn weighted_limits() {
+        // Weights have to add up to 100% and swaps can't move more than 30% of a balance.
+        assert!(matches!(
+            WeightedProduct::init(1_000, 1_000, 0, 0, 8_000, 1_000, None),
+            Err(CurveError::InvalidWeights)
+        ));
+        let mut pool = WeightedProduct::init(1_000_000, 1_000_000, 0, 0, 5_000, 5_000, None).unwrap();
+        assert!(pool.swap(LiquidityPair::TokenX, 300_001, 0).is_err());
+        assert!(pool.swap_exact_out(LiquidityPair::TokenX, 300_001, u64::MAX).is_err());
+    }
+
     #[test]
     fn spot_price() {
         let pool = ConstantProduct::init(10, 10, 0, 100, Some(0)).unwrap();
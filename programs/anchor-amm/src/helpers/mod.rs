@@ -2,4 +2,14 @@ pub mod asserts;
 /* pub use asserts::*; */
 
 pub mod curve;
-pub use curve::*;
\ No newline at end of file
+pub use curve::*;
+
+pub mod concentrated;
+
+pub mod oracle;
+
+pub mod token;
+
+pub mod pool;
+
+pub mod twamm;
@@ -0,0 +1,153 @@
+use crate::{helpers::concentrated::mul_div, Observation};
+
+// Math used by the pool oracle.
+//
+// Observations are snapshots of the Config accumulators. The cumulative values grow by
+// value * seconds, so the average over a window is the difference of two snapshots divided by its length.
+
+#[derive(Debug)]
+pub enum OracleError {
+    InvalidWindow,
+    ObservationTooOld,
+    Overflow,
+}
+
+/// Integer square root, rounded down
+pub fn sqrt(value: u128) -> u128 {
+    if value < 2 {
+        return value;
+    }
+
+    // Newton iteration from a power of two above the root
+    let mut x = 1u128 << ((128 - value.leading_zeros()).div_ceil(2));
+    loop {
+        let y = (x + value / x) >> 1;
+        if y >= x {
+            return x;
+        }
+        x = y;
+    }
+}
+
+/// Observe
+///
+/// Cumulative values at `target`, interpolating between the two observations around it.
+/// `index` is the position of the newest observation, the oldest one is the next in the ring
+pub fn observe(observations: &[Observation], index: usize, target: i64) -> Result<Observation, OracleError> {
+    let len = observations.len();
+    let oldest = (index + 1) % len;
+    let at = |i: usize| observations[(oldest + i) % len];
+
+    let newest = at(len - 1);
+    if target >= newest.timestamp {
+        return Ok(newest);
+    }
+    if target < at(0).timestamp {
+        return Err(OracleError::ObservationTooOld);
+    }
+
+    // Last observation at or before the target, observations are sorted by time from `oldest`
+    let (mut low, mut high) = (0, len - 1);
+    while high - low > 1 {
+        let mid = (low + high) / 2;
+        match at(mid).timestamp <= target {
+            true => low = mid,
+            false => high = mid,
+        }
+    }
+
+    let (before, after) = (at(low), at(high));
+    if before.timestamp == target {
+        return Ok(before);
+    }
+
+    let elapsed = (target - before.timestamp) as u128;
+    let span = (after.timestamp - before.timestamp) as u128;
+    let interpolate = |a: u128, b: u128| -> Result<u128, OracleError> {
+        let delta = mul_div(b.wrapping_sub(a), elapsed, span, false).map_err(|_| OracleError::Overflow)?;
+        Ok(a.wrapping_add(delta))
+    };
+
+    Ok(Observation {
+        timestamp: target,
+        price_cumulative: interpolate(before.price_cumulative, after.price_cumulative)?,
+        liquidity_cumulative: interpolate(before.liquidity_cumulative, after.liquidity_cumulative)?,
+    })
+}
+
+/// TWAP
+///
+/// Time weighted average price (Q64.64, X in Y) and liquidity over the `window` seconds
+/// before the newest observation
+pub fn twap(observations: &[Observation], index: usize, window: u32) -> Result<(u128, u128), OracleError> {
+    if window == 0 || observations.is_empty() {
+        return Err(OracleError::InvalidWindow);
+    }
+
+    let newest = observations[index];
+    let older = observe(observations, index, newest.timestamp - window as i64)?;
+
+    Ok((
+        newest.price_cumulative.wrapping_sub(older.price_cumulative) / window as u128,
+        newest.liquidity_cumulative.wrapping_sub(older.liquidity_cumulative) / window as u128,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{helpers::oracle::*, Observation};
+
+    fn observation(timestamp: i64, price: u128, liquidity: u128) -> Observation {
+        Observation { timestamp, price_cumulative: price, liquidity_cumulative: liquidity }
+    }
+
+    #[test]
+    fn sqrt_rounds_down() {
+        assert_eq!(sqrt(0), 0);
+        assert_eq!(sqrt(1), 1);
+        assert_eq!(sqrt(15), 3);
+        assert_eq!(sqrt(16), 4);
+        assert_eq!(sqrt(u128::MAX), u64::MAX as u128);
+    }
+
+    #[test]
+    fn twap_interpolates() {
+        // Price 2 between t=100 and t=200, then 4 until t=300
+        let observations = [
+            observation(100, 0, 0),
+            observation(200, 200, 1000),
+            observation(300, 600, 2000),
+        ];
+
+        assert_eq!(twap(&observations, 2, 100).unwrap(), (4, 10));
+        assert_eq!(twap(&observations, 2, 200).unwrap(), (3, 10));
+        // 150 seconds back falls in the middle of the first interval
+        assert_eq!(twap(&observations, 2, 150).unwrap(), (3, 10));
+        assert!(matches!(twap(&observations, 2, 201), Err(OracleError::ObservationTooOld)));
+        assert!(matches!(twap(&observations, 2, 0), Err(OracleError::InvalidWindow)));
+    }
+
+    #[test]
+    fn twap_wrapped_ring() {
+        // Newest at index 0, the oldest is the next one
+        let observations = [
+            observation(400, 900, 0),
+            observation(200, 200, 0),
+            observation(300, 600, 0),
+        ];
+
+        assert_eq!(twap(&observations, 0, 200).unwrap().0, 3);
+        assert!(matches!(twap(&observations, 0, 250), Err(OracleError::ObservationTooOld)));
+    }
+
+    #[test]
+    fn twap_wrapping_accumulators() {
+        // Accumulators overflowed between the two observations
+        let observations = [
+            observation(100, u128::MAX - 49, 0),
+            observation(200, 50, 0),
+        ];
+
+        assert_eq!(twap(&observations, 1, 100).unwrap().0, 1);
+    }
+}
@@ -0,0 +1,230 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::get_associated_token_address_with_program_id,
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    assert_non_zero, assert_not_locked, Config, Observation, Observations, Twamm,
+    errors::AmmError,
+    events::Swapped,
+    helpers::{token::transfer_checked_with_hook, LiquidityPair, SwapResult},
+};
+
+/// Pool
+///
+/// Accounts of one pool passed through the remaining accounts, for instructions which touch
+/// a variable number of pools. They get the same checks the Swap context does
+pub struct Pool<'info> {
+    pub config: Box<Account<'info, Config>>,
+    pub x_mint: Box<InterfaceAccount<'info, Mint>>,
+    pub y_mint: Box<InterfaceAccount<'info, Mint>>,
+    pub x_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub y_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub observations: Box<Account<'info, Observations>>,
+    pub auth: &'info AccountInfo<'info>,
+    pub x_token_program: Interface<'info, TokenInterface>,
+    pub y_token_program: Interface<'info, TokenInterface>,
+    pub twamm: Box<Account<'info, Twamm>>,
+}
+
+impl<'info> Pool<'info> {
+    // config, x_mint, y_mint, x_vault, y_vault, observations, auth, x_token_program, y_token_program, twamm
+    pub const ACCOUNTS: usize = 10;
+
+    /// Load
+    ///
+    /// Deserialize and check the accounts of a pool, in the order of `ACCOUNTS`
+    pub fn load(accounts: &'info [AccountInfo<'info>]) -> Result<Self> {
+        require!(accounts.len() == Self::ACCOUNTS, AmmError::InvalidPool);
+
+        let config = Box::new(Account::<Config>::try_from(&accounts[0])?);
+        let config_key = config.key();
+
+        let config_address = Pubkey::create_program_address(
+            &[b"config", config.seed.to_le_bytes().as_ref(), &[config.config_bump]],
+            &crate::ID,
+        )
+        .map_err(|_| AmmError::InvalidPool)?;
+        require_keys_eq!(config_key, config_address, AmmError::InvalidPool);
+
+        assert_not_locked!(config.swaps_paused());
+        config.check_idle()?;
+
+        let x_mint = Box::new(InterfaceAccount::<Mint>::try_from(&accounts[1])?);
+        let y_mint = Box::new(InterfaceAccount::<Mint>::try_from(&accounts[2])?);
+        require_keys_eq!(x_mint.key(), config.x_mint, AmmError::InvalidToken);
+        require_keys_eq!(y_mint.key(), config.y_mint, AmmError::InvalidToken);
+
+        let auth = &accounts[6];
+        let auth_address = Pubkey::create_program_address(
+            &[b"auth", config_key.as_ref(), &[config.auth_bump]],
+            &crate::ID,
+        )
+        .map_err(|_| AmmError::InvalidPool)?;
+        require_keys_eq!(auth.key(), auth_address, AmmError::InvalidPool);
+
+        let x_token_program = Interface::<TokenInterface>::try_from(&accounts[7])?;
+        let y_token_program = Interface::<TokenInterface>::try_from(&accounts[8])?;
+        require_keys_eq!(*accounts[1].owner, x_token_program.key(), AmmError::InvalidToken);
+        require_keys_eq!(*accounts[2].owner, y_token_program.key(), AmmError::InvalidToken);
+
+        // Anyone can create token accounts owned by `auth`, only the ATAs are the vaults
+        let x_vault = Box::new(InterfaceAccount::<TokenAccount>::try_from(&accounts[3])?);
+        let y_vault = Box::new(InterfaceAccount::<TokenAccount>::try_from(&accounts[4])?);
+        require_keys_eq!(
+            x_vault.key(),
+            get_associated_token_address_with_program_id(&auth_address, &x_mint.key(), &x_token_program.key()),
+            AmmError::InvalidPool
+        );
+        require_keys_eq!(
+            y_vault.key(),
+            get_associated_token_address_with_program_id(&auth_address, &y_mint.key(), &y_token_program.key()),
+            AmmError::InvalidPool
+        );
+
+        let observations = Box::new(Account::<Observations>::try_from(&accounts[5])?);
+        require_keys_eq!(observations.config, config_key, AmmError::InvalidPool);
+
+        let twamm = Box::new(Account::<Twamm>::try_from(&accounts[9])?);
+        require_keys_eq!(twamm.config, config_key, AmmError::InvalidPool);
+
+        Ok(Self {
+            config,
+            x_mint,
+            y_mint,
+            x_vault,
+            y_vault,
+            observations,
+            auth,
+            x_token_program,
+            y_token_program,
+            twamm,
+        })
+    }
+
+    /// Direction
+    ///
+    /// True if `mint_in` is X, so the pool swaps X to Y
+    pub fn direction(&self, mint_in: &Pubkey) -> Result<bool> {
+        match *mint_in {
+            mint if mint == self.config.x_mint => Ok(true),
+            mint if mint == self.config.y_mint => Ok(false),
+            _ => err!(AmmError::InvalidRoute),
+        }
+    }
+
+    /// Mint Out
+    pub fn mint_out(&self, is_x_to_y: bool) -> &InterfaceAccount<'info, Mint> {
+        match is_x_to_y {
+            true => &self.y_mint,
+            false => &self.x_mint,
+        }
+    }
+
+    /// Vault In
+    pub fn vault_in(&self, is_x_to_y: bool) -> &InterfaceAccount<'info, TokenAccount> {
+        match is_x_to_y {
+            true => &self.x_vault,
+            false => &self.y_vault,
+        }
+    }
+
+    /// Swap
+    ///
+    /// Quote an exact input swap and book it in the pool (oracle, protocol fee and stats) like
+    /// Swap::swap does. `amount_in` is what the vault receives, the tokens are moved by the caller
+    pub fn swap(&mut self, is_x_to_y: bool, amount_in: u64) -> Result<SwapResult> {
+        // Long-term orders trade first, up to the current slot
+        self.twamm.execute(&mut self.config, self.x_vault.amount, self.y_vault.amount)?;
+
+        // Price accumulators use the reserves from before the trade
+        let (reserve_x, reserve_y) = self.config.reserves(self.x_vault.amount, self.y_vault.amount);
+        self.config.update_price_accumulators(reserve_x, reserve_y)?;
+        self.observations.write(Observation {
+            timestamp: self.config.last_price_timestamp,
+            price_cumulative: self.config.price_x_cumulative,
+            liquidity_cumulative: self.config.liquidity_cumulative,
+        });
+
+        // The LP supply doesn't take part in swaps
+        let mut curve = self.config.curve(reserve_x, reserve_y, 0)?;
+
+        let pair = match is_x_to_y {
+            true => LiquidityPair::TokenX,
+            false => LiquidityPair::TokenY,
+        };
+
+        let swap_result = curve
+            .swap(pair, amount_in, 0)
+            .map_err(AmmError::from)?;
+
+        assert_non_zero!([swap_result.deposited, swap_result.withdrawn]);
+
+        // Part of the fee stays in the vault for the protocol
+        self.config.accrue_protocol_fee(is_x_to_y, swap_result.fee)?;
+        self.config.record_swap(is_x_to_y, swap_result.deposited, swap_result.withdrawn, swap_result.fee)?;
+
+        Ok(swap_result)
+    }
+
+    /// Transfer Out
+    ///
+    /// Send the output of a swap from the vault to `to`, signed by the pool `auth`
+    pub fn transfer_out(
+        &self,
+        is_x_to_y: bool,
+        amount: u64,
+        to: AccountInfo<'info>,
+        extra_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let (from, mint, token_program) = match is_x_to_y {
+            true => (self.y_vault.to_account_info(), &self.y_mint, &self.y_token_program),
+            false => (self.x_vault.to_account_info(), &self.x_mint, &self.x_token_program),
+        };
+
+        let cpi_accounts = TransferChecked {
+            from,
+            mint: mint.to_account_info(),
+            to,
+            authority: self.auth.clone(),
+        };
+
+        let config_key = self.config.key();
+        let seeds = &[&b"auth"[..], config_key.as_ref(), &[self.config.auth_bump]];
+        let signer_seeds = &[&seeds[..]];
+
+        let ctx = CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+
+        transfer_checked_with_hook(ctx, amount, mint.decimals, extra_accounts, &self.config.allowed_hooks)
+    }
+
+    /// Finish
+    ///
+    /// Emit the swap event with the reserves after the transfers and save the pool accounts,
+    /// they are not part of the instruction context so Anchor doesn't do it for us
+    pub fn finish(&mut self, user: Pubkey, is_x_to_y: bool, swap_result: &SwapResult) -> Result<()> {
+        self.x_vault.reload()?;
+        self.y_vault.reload()?;
+        let (reserve_x, reserve_y) = self.config.reserves(self.x_vault.amount, self.y_vault.amount);
+
+        emit!(Swapped {
+            config: self.config.key(),
+            user,
+            is_x_to_y,
+            amount_in: swap_result.deposited,
+            amount_out: swap_result.withdrawn,
+            fee: swap_result.fee,
+            reserve_x,
+            reserve_y,
+        });
+
+        self.config.exit(&crate::ID)?;
+        self.observations.exit(&crate::ID)?;
+        self.twamm.exit(&crate::ID)
+    }
+}
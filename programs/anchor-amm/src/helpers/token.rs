@@ -0,0 +1,183 @@
+use anchor_lang::{
+    prelude::*,
+    system_program::{self, Transfer},
+};
+use anchor_spl::{
+    token::spl_token,
+    token_2022::{
+        self,
+        spl_token_2022::{
+            extension::{
+                transfer_fee::{TransferFeeAmount, TransferFeeConfig},
+                transfer_hook, BaseStateWithExtensions, StateWithExtensions,
+            },
+            onchain::invoke_transfer_checked,
+            state::{Account as TokenAccountState, Mint},
+        },
+    },
+    token_interface::{
+        close_account, sync_native, transfer_checked, CloseAccount, SyncNative, TokenAccount, TokenInterface,
+        TransferChecked,
+    },
+};
+
+use crate::errors::AmmError;
+
+// Token-2022 mints with the TransferFee extension withhold part of every transfer in the
+// receiving account. The pool has to price with what really arrives, not with what was sent.
+
+/// With Transfer Fee
+///
+/// Run `f` with the transfer fee extension of the mint and the current epoch, if the mint has one
+fn with_transfer_fee<T>(mint: &AccountInfo, f: impl FnOnce(&TransferFeeConfig, u64) -> Option<T>) -> Result<Option<T>> {
+    // Legacy mints can't have extensions
+    if *mint.owner != token_2022::ID {
+        return Ok(None);
+    }
+
+    let data = mint.try_borrow_data()?;
+    let state = StateWithExtensions::<Mint>::unpack(&data)?;
+
+    match state.get_extension::<TransferFeeConfig>() {
+        Ok(config) => {
+            let value = f(config, Clock::get()?.epoch).ok_or(AmmError::Overflow)?;
+            Ok(Some(value))
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+/// Amount After Transfer Fee
+///
+/// Net amount received when `amount` is transferred
+pub fn amount_after_transfer_fee(mint: &AccountInfo, amount: u64) -> Result<u64> {
+    let fee = with_transfer_fee(mint, |config, epoch| config.calculate_epoch_fee(epoch, amount))?;
+
+    Ok(amount.checked_sub(fee.unwrap_or(0)).ok_or(AmmError::Underflow)?)
+}
+
+/// Amount Before Transfer Fee
+///
+/// Gross amount to transfer so that exactly `amount` is received
+pub fn amount_before_transfer_fee(mint: &AccountInfo, amount: u64) -> Result<u64> {
+    let fee = with_transfer_fee(mint, |config, epoch| config.calculate_inverse_epoch_fee(epoch, amount))?;
+
+    Ok(amount.checked_add(fee.unwrap_or(0)).ok_or(AmmError::Overflow)?)
+}
+
+/// Has Withheld Fees
+///
+/// Token-2022 accounts which received transfer fee tokens can't be closed until the withheld
+/// fees are harvested to the mint
+pub fn has_withheld_fees(token_account: &AccountInfo) -> Result<bool> {
+    if *token_account.owner != token_2022::ID {
+        return Ok(false);
+    }
+
+    let data = token_account.try_borrow_data()?;
+    let state = StateWithExtensions::<TokenAccountState>::unpack(&data)?;
+
+    Ok(state
+        .get_extension::<TransferFeeAmount>()
+        .map(|fee| u64::from(fee.withheld_amount) > 0)
+        .unwrap_or(false))
+}
+
+/// Transfer Hook Program
+///
+/// Program called by Token-2022 on every transfer of the mint, if it has the TransferHook extension
+pub fn transfer_hook_program(mint: &AccountInfo) -> Result<Option<Pubkey>> {
+    if *mint.owner != token_2022::ID {
+        return Ok(None);
+    }
+
+    let data = mint.try_borrow_data()?;
+    let state = StateWithExtensions::<Mint>::unpack(&data)?;
+
+    Ok(transfer_hook::get_program_id(&state))
+}
+
+/// Transfer Checked With Hook
+///
+/// Same as `transfer_checked`, but it also works for mints with a transfer hook. The hook program
+/// has to be allowed by the pool, and the extra accounts it needs are picked from `extra_accounts`
+pub fn transfer_checked_with_hook<'info>(
+    ctx: CpiContext<'_, '_, '_, 'info, TransferChecked<'info>>,
+    amount: u64,
+    decimals: u8,
+    extra_accounts: &[AccountInfo<'info>],
+    allowed_hooks: &[Pubkey],
+) -> Result<()> {
+    let hook_program = match transfer_hook_program(&ctx.accounts.mint)? {
+        Some(hook_program) => hook_program,
+        None => return transfer_checked(ctx, amount, decimals),
+    };
+
+    require!(allowed_hooks.contains(&hook_program), AmmError::TransferHookNotAllowed);
+
+    invoke_transfer_checked(
+        ctx.program.key,
+        ctx.accounts.from,
+        ctx.accounts.mint,
+        ctx.accounts.to,
+        ctx.accounts.authority,
+        extra_accounts,
+        amount,
+        decimals,
+        ctx.signer_seeds,
+    )
+    .map_err(Into::into)
+}
+
+/// Is Native Mint
+///
+/// True for the wrapped SOL mint of both token programs
+pub fn is_native_mint(mint: &Pubkey) -> bool {
+    *mint == spl_token::native_mint::ID || *mint == token_2022::spl_token_2022::native_mint::ID
+}
+
+/// Wrap SOL
+///
+/// Move `amount` lamports from the owner to its wSOL account and sync it, so users who ask to pay
+/// with SOL don't need to wrap it before trading. The wSOL the owner already held is not spent
+pub fn wrap_sol<'info>(
+    owner: &Signer<'info>,
+    token_account: &InterfaceAccount<'info, TokenAccount>,
+    amount: u64,
+    system_program: &Program<'info, System>,
+    token_program: &Interface<'info, TokenInterface>,
+) -> Result<()> {
+    let accounts = Transfer {
+        from: owner.to_account_info(),
+        to: token_account.to_account_info(),
+    };
+    system_program::transfer(CpiContext::new(system_program.to_account_info(), accounts), amount)?;
+
+    let accounts = SyncNative {
+        account: token_account.to_account_info(),
+    };
+    sync_native(CpiContext::new(token_program.to_account_info(), accounts))
+}
+
+/// Unwrap SOL
+///
+/// Close the wSOL account of the owner, so its wrapped SOL and the rent go back as lamports. Only
+/// accounts which were empty when the instruction started (`token_account` isn't reloaded), like the
+/// ones it creates, are closed. Otherwise the wSOL the owner already held would be unwrapped too
+pub fn unwrap_sol<'info>(
+    owner: &Signer<'info>,
+    token_account: &InterfaceAccount<'info, TokenAccount>,
+    token_program: &Interface<'info, TokenInterface>,
+) -> Result<()> {
+    if token_account.amount > 0 {
+        return Ok(());
+    }
+
+    let accounts = CloseAccount {
+        account: token_account.to_account_info(),
+        destination: owner.to_account_info(),
+        authority: owner.to_account_info(),
+    };
+
+    close_account(CpiContext::new(token_program.to_account_info(), accounts))
+}
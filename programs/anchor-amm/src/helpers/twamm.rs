@@ -0,0 +1,133 @@
+use crate::helpers::{concentrated::mul_div, ConstantProduct, CurveError, LiquidityPair, SwapResult};
+
+// Math used by the long-term orders (TWAMM).
+//
+// Orders of both sides sell at a constant rate per slot. Over a period, the two flows trade with each
+// other at the pool price first, as in the TWAMM paper, and only what is left of the bigger side goes
+// through the constant product curve. Flows which cancel out don't move the price nor pay the swap fee.
+
+/// Virtual Trade
+///
+/// Outcome of the long-term orders of both sides over some slots
+#[derive(Debug)]
+pub struct VirtualTrade {
+    // Sold by the orders of each side
+    pub sold_x: u64,
+    pub sold_y: u64,
+    // Bought by the orders of each side, X sellers buy Y and Y sellers buy X
+    pub bought_x: u64,
+    pub bought_y: u64,
+    // What is left of the bigger side, swapped against the pool, and its direction
+    pub swap: Option<(bool, SwapResult)>,
+}
+
+/// Virtual Trade
+///
+/// Net `sold_x` against `sold_y` at the pool price and swap the rest through the curve
+pub fn virtual_trade(sold_x: u64, sold_y: u64, reserve_x: u64, reserve_y: u64, fee: u16) -> Result<VirtualTrade, CurveError> {
+    let mut trade = VirtualTrade {
+        sold_x,
+        sold_y,
+        bought_x: 0,
+        bought_y: 0,
+        swap: None,
+    };
+
+    if sold_x == 0 && sold_y == 0 {
+        return Ok(trade);
+    }
+
+    // Value of the X sold, in Y at the pool price
+    let sold_x_in_y = mul_div(sold_x as u128, reserve_y as u128, reserve_x as u128, false)?;
+
+    let (is_x_to_y, excess) = if sold_x_in_y >= sold_y as u128 {
+        // Y sellers are matched in full, the rest of the X goes through the curve
+        trade.bought_x = to_u64(mul_div(sold_y as u128, reserve_x as u128, reserve_y as u128, false)?)?;
+        trade.bought_y = sold_y;
+        (true, sold_x.checked_sub(trade.bought_x).ok_or(CurveError::Underflow)?)
+    } else {
+        // X sellers are matched in full, the rest of the Y goes through the curve
+        trade.bought_x = sold_x;
+        trade.bought_y = to_u64(sold_x_in_y)?;
+        (false, sold_y.checked_sub(trade.bought_y).ok_or(CurveError::Underflow)?)
+    };
+
+    if excess == 0 {
+        return Ok(trade);
+    }
+
+    let pair = match is_x_to_y {
+        true => LiquidityPair::TokenX,
+        false => LiquidityPair::TokenY,
+    };
+
+    let swap_result = ConstantProduct::init(reserve_x, reserve_y, 0, fee, Some(6))?.swap(pair, excess, 0)?;
+
+    match is_x_to_y {
+        true => trade.bought_y = trade.bought_y.checked_add(swap_result.withdrawn).ok_or(CurveError::Overflow)?,
+        false => trade.bought_x = trade.bought_x.checked_add(swap_result.withdrawn).ok_or(CurveError::Overflow)?,
+    }
+    trade.swap = Some((is_x_to_y, swap_result));
+
+    Ok(trade)
+}
+
+fn to_u64(value: u128) -> Result<u64, CurveError> {
+    u64::try_from(value).map_err(|_| CurveError::Overflow)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::helpers::{twamm::*, ConstantProduct, LiquidityPair};
+
+    #[test]
+    fn one_side_is_a_plain_swap() {
+        let trade = virtual_trade(1_000, 0, 100_000, 200_000, 30).unwrap();
+        let swap_result = ConstantProduct::init(100_000, 200_000, 0, 30, Some(6))
+            .unwrap()
+            .swap(LiquidityPair::TokenX, 1_000, 0)
+            .unwrap();
+
+        assert_eq!(trade.bought_x, 0);
+        assert_eq!(trade.bought_y, swap_result.withdrawn);
+
+        let (is_x_to_y, leg) = trade.swap.unwrap();
+        assert!(is_x_to_y);
+        assert_eq!(leg.deposited, 1_000);
+    }
+
+    #[test]
+    fn balanced_flows_skip_the_curve() {
+        // 2 Y per X, both sides cancel out at the pool price
+        let trade = virtual_trade(1_000, 2_000, 100_000, 200_000, 30).unwrap();
+
+        assert_eq!(trade.bought_x, 1_000);
+        assert_eq!(trade.bought_y, 2_000);
+        assert!(trade.swap.is_none());
+    }
+
+    #[test]
+    fn netting_beats_separate_swaps() {
+        let trade = virtual_trade(3_000, 2_000, 100_000, 200_000, 30).unwrap();
+
+        // Y sellers get 1_000 X at the pool price, the other 2_000 X are swapped
+        assert_eq!(trade.bought_x, 1_000);
+        let (is_x_to_y, leg) = trade.swap.unwrap();
+        assert!(is_x_to_y);
+        assert_eq!(leg.deposited, 2_000);
+
+        // Swapping both sides separately, X sellers would get less Y for the same 3_000 X
+        let alone = ConstantProduct::init(100_000, 200_000, 0, 30, Some(6))
+            .unwrap()
+            .swap(LiquidityPair::TokenX, 3_000, 0)
+            .unwrap();
+        assert!(trade.bought_y > alone.withdrawn);
+
+        // Y heavy side mirrors it
+        let trade = virtual_trade(500, 4_000, 100_000, 200_000, 30).unwrap();
+        assert_eq!(trade.bought_y, 1_000);
+        let (is_x_to_y, leg) = trade.swap.unwrap();
+        assert!(!is_x_to_y);
+        assert_eq!(leg.deposited, 3_000);
+    }
+}
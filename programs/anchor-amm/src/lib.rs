@@ -5,6 +5,7 @@ declare_id!("2oxkz3u24B8YKFnfm1VvE1ydWfmiAyqQryT41eyk1G2B");
 mod constants;
 mod contexts;
 mod errors;
+mod events;
 mod helpers;
 mod state;
 
@@ -21,19 +22,22 @@ pub mod anchor_amm {
         ctx: Context<Initialize>,
         seed: u64,
         fee: u16,
-        authority: Option<Pubkey>
+        fee_delay: i64,
+        authority: Option<Pubkey>,
+        curve_type: CurveType,
     ) -> Result<()> {
-        ctx.accounts.initialize(seed, fee, authority, &ctx.bumps)
+        ctx.accounts.initialize(seed, fee, fee_delay, authority, curve_type, &ctx.bumps)
     }
 
-    pub fn deposit(
-        ctx: Context<Deposit>,
+    pub fn deposit<'info>(
+        ctx: Context<'_, '_, '_, 'info, Deposit<'info>>,
         amount: u64,
         x_max: u64,
         y_max: u64,
         expiration: i64,
+        wrap_native: bool,
     ) -> Result<()> {
-        ctx.accounts.deposit(amount, x_max, y_max, expiration)
+        ctx.accounts.deposit(amount, x_max, y_max, expiration, wrap_native, ctx.remaining_accounts)
     }
 
     pub fn lock(ctx: Context<Update>) -> Result<()> {
@@ -44,24 +48,233 @@ pub mod anchor_amm {
         ctx.accounts.unlock()
     }
 
-    pub fn swap(
-        ctx: Context<Swap>,
+    pub fn propose_protocol_fee(ctx: Context<Update>, protocol_fee: u16, treasury: Pubkey) -> Result<()> {
+        ctx.accounts.propose_protocol_fee(protocol_fee, treasury)
+    }
+
+    pub fn apply_protocol_fee(ctx: Context<Update>) -> Result<()> {
+        ctx.accounts.apply_protocol_fee()
+    }
+
+    pub fn collect_protocol_fees<'info>(ctx: Context<'_, '_, '_, 'info, CollectProtocolFees<'info>>) -> Result<()> {
+        ctx.accounts.collect_protocol_fees(ctx.remaining_accounts)
+    }
+
+    pub fn set_flash_fee(ctx: Context<Update>, flash_fee: u16) -> Result<()> {
+        ctx.accounts.set_flash_fee(flash_fee)
+    }
+
+    pub fn set_allowed_hooks(ctx: Context<Update>, hooks: Vec<Pubkey>) -> Result<()> {
+        ctx.accounts.set_allowed_hooks(hooks)
+    }
+
+    pub fn set_paused(ctx: Context<Update>, flags: u8) -> Result<()> {
+        ctx.accounts.set_paused(flags)
+    }
+
+    pub fn clear_paused(ctx: Context<Update>, flags: u8) -> Result<()> {
+        ctx.accounts.clear_paused(flags)
+    }
+
+    pub fn set_withdraw_policy(ctx: Context<Update>, withdraw_on_swap_pause: bool) -> Result<()> {
+        ctx.accounts.set_withdraw_policy(withdraw_on_swap_pause)
+    }
+
+    pub fn propose_authority(ctx: Context<Update>, new_authority: Pubkey) -> Result<()> {
+        ctx.accounts.propose_authority(new_authority)
+    }
+
+    pub fn accept_authority(ctx: Context<Update>) -> Result<()> {
+        ctx.accounts.accept_authority()
+    }
+
+    pub fn renounce_authority(ctx: Context<Update>) -> Result<()> {
+        ctx.accounts.renounce_authority()
+    }
+
+    pub fn propose_fee(ctx: Context<Update>, new_fee: u16) -> Result<()> {
+        ctx.accounts.propose_fee(new_fee)
+    }
+
+    pub fn apply_fee(ctx: Context<ApplyFee>) -> Result<()> {
+        ctx.accounts.apply_fee(&ctx.bumps)
+    }
+
+    pub fn swap<'info>(
+        ctx: Context<'_, '_, '_, 'info, Swap<'info>>,
         amount_in: u64,
         min_amount_out: u64,
         is_x_to_y: bool,
-        expiration: i64
+        expiration: i64,
+        wrap_native: bool,
     ) -> Result<()> {
-        ctx.accounts.swap(amount_in, min_amount_out, is_x_to_y, expiration)
+        ctx.accounts.swap(amount_in, min_amount_out, is_x_to_y, expiration, wrap_native, ctx.remaining_accounts)
     }
 
-    pub fn withdraw(
-        ctx: Context<Withdraw>,
+    pub fn swap_exact_out<'info>(
+        ctx: Context<'_, '_, '_, 'info, Swap<'info>>,
+        amount_out: u64,
+        max_amount_in: u64,
+        is_x_to_y: bool,
+        expiration: i64,
+        wrap_native: bool,
+    ) -> Result<()> {
+        ctx.accounts.swap_exact_out(amount_out, max_amount_in, is_x_to_y, expiration, wrap_native, ctx.remaining_accounts)
+    }
+
+    pub fn flash_swap<'info>(
+        ctx: Context<'_, '_, '_, 'info, Swap<'info>>,
+        amount_in: u64,
+        min_amount_out: u64,
+        is_x_to_y: bool,
+        expiration: i64,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        ctx.accounts.flash_swap(amount_in, min_amount_out, is_x_to_y, expiration, data, ctx.remaining_accounts)
+    }
+
+    pub fn route_swap<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RouteSwap<'info>>,
+        amount_in: u64,
+        min_amount_out: u64,
+        hops: u8,
+        expiration: i64,
+    ) -> Result<()> {
+        ctx.accounts.route_swap(amount_in, min_amount_out, hops, expiration, ctx.remaining_accounts)
+    }
+
+    pub fn split_swap<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RouteSwap<'info>>,
+        amounts: Vec<u64>,
+        min_amount_out: u64,
+        expiration: i64,
+    ) -> Result<()> {
+        ctx.accounts.split_swap(amounts, min_amount_out, expiration, ctx.remaining_accounts)
+    }
+
+    pub fn withdraw<'info>(
+        ctx: Context<'_, '_, '_, 'info, Withdraw<'info>>,
         amount: u64,
         x_min: u64,
         y_min: u64,
         expiration: i64,
+        unwrap_native: bool,
+    ) -> Result<()> {
+        ctx.accounts.withdraw(amount, x_min, y_min, expiration, unwrap_native, ctx.remaining_accounts)
+    }
+
+    pub fn migrate_vaults<'info>(ctx: Context<'_, '_, '_, 'info, MigrateVaults<'info>>) -> Result<()> {
+        ctx.accounts.migrate_vaults(&ctx.bumps, ctx.remaining_accounts)
+    }
+
+    pub fn flash_borrow<'info>(
+        ctx: Context<'_, '_, '_, 'info, FlashLoan<'info>>,
+        amount: u64,
+        is_x: bool,
+    ) -> Result<()> {
+        ctx.accounts.flash_borrow(amount, is_x, ctx.remaining_accounts)
+    }
+
+    pub fn flash_repay<'info>(ctx: Context<'_, '_, '_, 'info, FlashLoan<'info>>) -> Result<()> {
+        ctx.accounts.flash_repay(ctx.remaining_accounts)
+    }
+
+    pub fn grow_observations(ctx: Context<GrowObservations>, new_capacity: u16) -> Result<()> {
+        ctx.accounts.grow_observations(new_capacity)
+    }
+
+    pub fn init_observations(ctx: Context<InitObservations>) -> Result<()> {
+        ctx.accounts.init_observations(&ctx.bumps)
+    }
+
+    pub fn place_order<'info>(
+        ctx: Context<'_, '_, '_, 'info, PlaceOrder<'info>>,
+        id: u64,
+        amount_in: u64,
+        target_price: u128,
+        bounty: u64,
+    ) -> Result<()> {
+        ctx.accounts.place_order(id, amount_in, target_price, bounty, &ctx.bumps, ctx.remaining_accounts)
+    }
+
+    pub fn fill_order<'info>(ctx: Context<'_, '_, '_, 'info, FillOrder<'info>>) -> Result<()> {
+        ctx.accounts.fill_order(ctx.remaining_accounts)
+    }
+
+    pub fn cancel_order<'info>(ctx: Context<'_, '_, '_, 'info, CancelOrder<'info>>) -> Result<()> {
+        ctx.accounts.cancel_order(ctx.remaining_accounts)
+    }
+
+    pub fn place_long_term_order<'info>(
+        ctx: Context<'_, '_, '_, 'info, PlaceLongTermOrder<'info>>,
+        id: u64,
+        amount_in: u64,
+        is_x_to_y: bool,
+        slots: u64,
+    ) -> Result<()> {
+        ctx.accounts.place_long_term_order(id, amount_in, is_x_to_y, slots, &ctx.bumps, ctx.remaining_accounts)
+    }
+
+    pub fn close_long_term_order<'info>(ctx: Context<'_, '_, '_, 'info, CloseLongTermOrder<'info>>) -> Result<()> {
+        ctx.accounts.close_long_term_order(ctx.remaining_accounts)
+    }
+
+    pub fn init_twamm(ctx: Context<InitTwamm>) -> Result<()> {
+        ctx.accounts.init_twamm(&ctx.bumps)
+    }
+
+    pub fn initialize_concentrated(
+        ctx: Context<InitializeConcentrated>,
+        seed: u64,
+        fee: u16,
+        fee_delay: i64,
+        authority: Option<Pubkey>,
+        tick_spacing: u16,
+        sqrt_price: u128,
+    ) -> Result<()> {
+        ctx.accounts.initialize_concentrated(seed, fee, fee_delay, authority, tick_spacing, sqrt_price, &ctx.bumps)
+    }
+
+    pub fn open_position(
+        ctx: Context<OpenPosition>,
+        tick_lower: i32,
+        tick_upper: i32,
+    ) -> Result<()> {
+        ctx.accounts.open_position(tick_lower, tick_upper, &ctx.bumps)
+    }
+
+    pub fn increase_liquidity<'info>(
+        ctx: Context<'_, '_, '_, 'info, ModifyPosition<'info>>,
+        liquidity: u128,
+        x_max: u64,
+        y_max: u64,
+        expiration: i64,
+    ) -> Result<()> {
+        ctx.accounts.increase_liquidity(liquidity, x_max, y_max, expiration, ctx.remaining_accounts)
+    }
+
+    pub fn decrease_liquidity<'info>(
+        ctx: Context<'_, '_, '_, 'info, ModifyPosition<'info>>,
+        liquidity: u128,
+        x_min: u64,
+        y_min: u64,
+        expiration: i64,
+    ) -> Result<()> {
+        ctx.accounts.decrease_liquidity(liquidity, x_min, y_min, expiration, ctx.remaining_accounts)
+    }
+
+    pub fn close_position(ctx: Context<ClosePosition>) -> Result<()> {
+        ctx.accounts.close_position()
+    }
+
+    pub fn swap_concentrated<'info>(
+        ctx: Context<'_, '_, '_, 'info, SwapConcentrated<'info>>,
+        amount_in: u64,
+        min_amount_out: u64,
+        is_x_to_y: bool,
+        expiration: i64
     ) -> Result<()> {
-        ctx.accounts.withdraw(amount, x_min, y_min, expiration)
+        ctx.accounts.swap(amount_in, min_amount_out, is_x_to_y, expiration, ctx.remaining_accounts)
     }
 
 }
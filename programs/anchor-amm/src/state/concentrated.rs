@@ -0,0 +1,334 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    errors::AmmError,
+    helpers::{concentrated::*, SwapResult},
+    I32_L, PUBKEY_L, U128_L, U8_L,
+};
+
+/// Maximum number of initialized ticks a concentrated pool can hold
+pub const MAX_TICKS: usize = 96;
+
+/// Tick struct keeps the liquidity that gets added or removed when the price crosses it
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default)]
+pub struct Tick {
+    // Tick index, the price at this tick is 1.0001^index
+    pub index: i32,
+    // Liquidity added when crossing the tick from left to right (removed when going right to left)
+    pub liquidity_net: i128,
+    // Total liquidity referencing this tick, when it gets to zero the tick is removed
+    pub liquidity_gross: u128,
+    // Fees per unit of liquidity (Q64.64) accumulated on the other side of the tick
+    pub fee_growth_outside_x: u128,
+    pub fee_growth_outside_y: u128,
+}
+
+impl Tick {
+    pub const LEN: usize = I32_L + U128_L*4;
+}
+
+/// ConcentratedPool struct holds the price and liquidity of a concentrated liquidity pool.
+/// Ticks are kept sorted in a bounded list, so a swap always sees every initialized tick it crosses
+#[account]
+pub struct ConcentratedPool {
+    // Config this pool belongs to
+    pub config: Pubkey,
+    // Current sqrt price in Q64.64
+    pub sqrt_price: u128,
+    // Greatest tick whose price is lower or equal than the current price
+    pub tick_current: i32,
+    // Liquidity active at the current price
+    pub liquidity: u128,
+    // Fees per unit of liquidity (Q64.64) accumulated since the pool was created
+    pub fee_growth_global_x: u128,
+    pub fee_growth_global_y: u128,
+    // Initialized ticks sorted by index
+    pub ticks: Vec<Tick>,
+    pub bump: u8,
+}
+
+impl ConcentratedPool {
+    pub const INIT_SPACE: usize = 8 + PUBKEY_L + U128_L + I32_L + U128_L*3 + 4 + Tick::LEN*MAX_TICKS + U8_L;
+
+    pub fn init(
+        &mut self,
+        config: Pubkey,
+        sqrt_price: u128,
+        bump: u8,
+    ) -> Result<()> {
+        self.config = config;
+        self.sqrt_price = sqrt_price;
+        self.tick_current = tick_at_sqrt_price(sqrt_price).map_err(AmmError::from)?;
+        self.liquidity = 0;
+        self.fee_growth_global_x = 0;
+        self.fee_growth_global_y = 0;
+        self.ticks = Vec::new();
+        self.bump = bump;
+
+        Ok(())
+    }
+
+    /// Update Tick
+    ///
+    /// Add (or remove, with a negative delta) liquidity referencing a tick.
+    /// The tick gets created the first time it's used and removed once nothing references it
+    pub fn update_tick(&mut self, index: i32, liquidity_delta: i128, is_upper: bool) -> Result<()> {
+        let position = self.ticks.binary_search_by_key(&index, |t| t.index);
+
+        let i = match position {
+            Ok(i) => i,
+            Err(i) => {
+                require!(liquidity_delta > 0, AmmError::InvalidTickRange);
+                require!(self.ticks.len() < MAX_TICKS, AmmError::TickListFull);
+
+                // By convention all the fees so far happened below the tick if it's at or below the current price
+                let (fee_growth_outside_x, fee_growth_outside_y) = match index <= self.tick_current {
+                    true => (self.fee_growth_global_x, self.fee_growth_global_y),
+                    false => (0, 0),
+                };
+
+                self.ticks.insert(i, Tick {
+                    index,
+                    liquidity_net: 0,
+                    liquidity_gross: 0,
+                    fee_growth_outside_x,
+                    fee_growth_outside_y,
+                });
+                i
+            }
+        };
+
+        let tick = &mut self.ticks[i];
+        tick.liquidity_gross = tick.liquidity_gross
+            .checked_add_signed(liquidity_delta)
+            .ok_or(AmmError::Overflow)?;
+
+        // The lower tick adds liquidity when crossed upwards, the upper one removes it
+        let net_delta = if is_upper { liquidity_delta.checked_neg().ok_or(AmmError::Overflow)? } else { liquidity_delta };
+        tick.liquidity_net = tick.liquidity_net.checked_add(net_delta).ok_or(AmmError::Overflow)?;
+
+        if tick.liquidity_gross == 0 {
+            self.ticks.remove(i);
+        }
+
+        Ok(())
+    }
+
+    /// Fee Growth Inside
+    ///
+    /// Fees per unit of liquidity accumulated between two ticks, following the Uniswap v3 accounting
+    pub fn fee_growth_inside(&self, tick_lower: i32, tick_upper: i32) -> Result<(u128, u128)> {
+        let lower = self.get_tick(tick_lower)?;
+        let upper = self.get_tick(tick_upper)?;
+
+        let (below_x, below_y) = match self.tick_current >= tick_lower {
+            true => (lower.fee_growth_outside_x, lower.fee_growth_outside_y),
+            false => (
+                self.fee_growth_global_x.wrapping_sub(lower.fee_growth_outside_x),
+                self.fee_growth_global_y.wrapping_sub(lower.fee_growth_outside_y),
+            ),
+        };
+
+        let (above_x, above_y) = match self.tick_current < tick_upper {
+            true => (upper.fee_growth_outside_x, upper.fee_growth_outside_y),
+            false => (
+                self.fee_growth_global_x.wrapping_sub(upper.fee_growth_outside_x),
+                self.fee_growth_global_y.wrapping_sub(upper.fee_growth_outside_y),
+            ),
+        };
+
+        Ok((
+            self.fee_growth_global_x.wrapping_sub(below_x).wrapping_sub(above_x),
+            self.fee_growth_global_y.wrapping_sub(below_y).wrapping_sub(above_y),
+        ))
+    }
+
+    fn get_tick(&self, index: i32) -> Result<Tick> {
+        let i = self.ticks
+            .binary_search_by_key(&index, |t| t.index)
+            .map_err(|_| AmmError::InvalidTickRange)?;
+        Ok(self.ticks[i])
+    }
+
+    /// Swap
+    ///
+    /// Exact input swap which walks the sorted ticks, crossing them while there is input left.
+    /// The `protocol_fee` share of every step fee is taken out before the rest is accrued to the
+    /// liquidity active in the step. Returns the swap and the protocol amount
+    pub fn swap(&mut self, amount_in: u64, is_x_to_y: bool, fee: u16, protocol_fee: u16) -> Result<(SwapResult, u64)> {
+        let mut amount_remaining = amount_in as u128;
+        let mut amount_out = 0u128;
+        let mut fee_total = 0u128;
+        let mut protocol_total = 0u128;
+
+        while amount_remaining > 0 {
+            // Next initialized tick in the direction of the swap, or the edge of the price range
+            let next_tick = match is_x_to_y {
+                true => self.ticks.iter().rev().find(|t| t.index <= self.tick_current).map(|t| t.index),
+                false => self.ticks.iter().find(|t| t.index > self.tick_current).map(|t| t.index),
+            };
+            let target_tick = next_tick.unwrap_or(if is_x_to_y { MIN_TICK } else { MAX_TICK });
+            let sqrt_price_target = sqrt_price_at_tick(target_tick).map_err(AmmError::from)?;
+
+            // We are sitting at the edge with nothing left to cross
+            if self.sqrt_price == sqrt_price_target && next_tick.is_none() {
+                break;
+            }
+
+            let step = compute_swap_step(self.sqrt_price, sqrt_price_target, self.liquidity, amount_remaining, fee)
+                .map_err(AmmError::from)?;
+
+            let used = step.amount_in.checked_add(step.fee).ok_or(AmmError::Overflow)?;
+            amount_remaining = amount_remaining.checked_sub(used).ok_or(AmmError::Underflow)?;
+            amount_out = amount_out.checked_add(step.amount_out).ok_or(AmmError::Overflow)?;
+            fee_total = fee_total.checked_add(step.fee).ok_or(AmmError::Overflow)?;
+
+            // The protocol share never reaches the fee growth, so positions can't claim it
+            let protocol_part = step.fee.checked_mul(protocol_fee as u128).ok_or(AmmError::Overflow)? / 10000;
+            protocol_total = protocol_total.checked_add(protocol_part).ok_or(AmmError::Overflow)?;
+
+            // The rest is shared by the liquidity active during this step
+            if self.liquidity > 0 {
+                let growth = mul_div(step.fee - protocol_part, Q64, self.liquidity, false).map_err(AmmError::from)?;
+                match is_x_to_y {
+                    true => self.fee_growth_global_x = self.fee_growth_global_x.wrapping_add(growth),
+                    false => self.fee_growth_global_y = self.fee_growth_global_y.wrapping_add(growth),
+                }
+            }
+
+            self.sqrt_price = step.sqrt_price_next;
+
+            if step.sqrt_price_next == sqrt_price_target {
+                match next_tick {
+                    Some(index) => self.cross_tick(index, is_x_to_y)?,
+                    // Out of range, whatever is left stays with the user
+                    None => break,
+                }
+            } else {
+                self.tick_current = tick_at_sqrt_price(self.sqrt_price).map_err(AmmError::from)?;
+            }
+        }
+
+        let deposited = (amount_in as u128).checked_sub(amount_remaining).ok_or(AmmError::Underflow)?;
+
+        let swap_result = SwapResult {
+            deposited: u64::try_from(deposited).map_err(|_| AmmError::Overflow)?,
+            withdrawn: u64::try_from(amount_out).map_err(|_| AmmError::Overflow)?,
+            fee: u64::try_from(fee_total).map_err(|_| AmmError::Overflow)?,
+        };
+
+        Ok((swap_result, u64::try_from(protocol_total).map_err(|_| AmmError::Overflow)?))
+    }
+
+    fn cross_tick(&mut self, index: i32, is_x_to_y: bool) -> Result<()> {
+        let i = self.ticks
+            .binary_search_by_key(&index, |t| t.index)
+            .map_err(|_| AmmError::InvalidTickRange)?;
+        let (global_x, global_y) = (self.fee_growth_global_x, self.fee_growth_global_y);
+
+        let tick = &mut self.ticks[i];
+        tick.fee_growth_outside_x = global_x.wrapping_sub(tick.fee_growth_outside_x);
+        tick.fee_growth_outside_y = global_y.wrapping_sub(tick.fee_growth_outside_y);
+
+        // Going left we undo what the tick added going right
+        let liquidity_net = match is_x_to_y {
+            true => tick.liquidity_net.checked_neg().ok_or(AmmError::Overflow)?,
+            false => tick.liquidity_net,
+        };
+        self.liquidity = self.liquidity
+            .checked_add_signed(liquidity_net)
+            .ok_or(AmmError::Overflow)?;
+
+        self.tick_current = match is_x_to_y {
+            true => index - 1,
+            false => index,
+        };
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::prelude::Pubkey;
+
+    use crate::{helpers::concentrated::*, ConcentratedPool};
+
+    fn pool_with_ranges(ranges: &[(i32, i32, u128)]) -> ConcentratedPool {
+        let mut pool = ConcentratedPool {
+            config: Pubkey::default(),
+            sqrt_price: 0,
+            tick_current: 0,
+            liquidity: 0,
+            fee_growth_global_x: 0,
+            fee_growth_global_y: 0,
+            ticks: Vec::new(),
+            bump: 0,
+        };
+        pool.init(Pubkey::default(), sqrt_price_at_tick(0).unwrap(), 0).unwrap();
+
+        for (lower, upper, liquidity) in ranges {
+            pool.update_tick(*lower, *liquidity as i128, false).unwrap();
+            pool.update_tick(*upper, *liquidity as i128, true).unwrap();
+            if *lower <= pool.tick_current && pool.tick_current < *upper {
+                pool.liquidity += liquidity;
+            }
+        }
+        pool
+    }
+
+    #[test]
+    fn swap_crosses_ticks() {
+        // A narrow range around the price and a wider one behind it
+        let mut pool = pool_with_ranges(&[(-10, 10, 1_000_000_000), (-1_000, 1_000, 1_000_000_000)]);
+        assert_eq!(pool.liquidity, 2_000_000_000);
+
+        // A large sale of X pushes the price below tick -10, leaving only the wide range active
+        let (res, _) = pool.swap(5_000_000, true, 30, 0).unwrap();
+        assert_eq!(res.deposited, 5_000_000);
+        assert!(res.withdrawn > 0 && res.withdrawn < res.deposited);
+        assert!(pool.tick_current < -10);
+        assert_eq!(pool.liquidity, 1_000_000_000);
+        assert!(pool.fee_growth_global_x > 0);
+
+        // Buying it back crosses the tick again and restores the liquidity
+        pool.swap(4_000_000, false, 30, 0).unwrap();
+        assert!(pool.tick_current >= -10 && pool.tick_current < 10);
+        assert_eq!(pool.liquidity, 2_000_000_000);
+    }
+
+    #[test]
+    fn swap_stops_without_liquidity() {
+        // Once the only range is exhausted the rest of the input is not taken
+        let mut pool = pool_with_ranges(&[(-10, 10, 1_000_000)]);
+        let (res, _) = pool.swap(1_000_000_000, false, 0, 0).unwrap();
+        assert!(res.deposited < 1_000_000_000);
+        assert_eq!(pool.liquidity, 0);
+        assert_eq!(pool.sqrt_price, sqrt_price_at_tick(MAX_TICK).unwrap());
+    }
+
+    #[test]
+    fn protocol_share_is_not_accrued_to_positions() {
+        let mut lp_only = pool_with_ranges(&[(-1_000, 1_000, 1_000_000_000)]);
+        let mut shared = pool_with_ranges(&[(-1_000, 1_000, 1_000_000_000)]);
+
+        let (res, protocol) = lp_only.swap(1_000_000, true, 30, 0).unwrap();
+        assert_eq!(protocol, 0);
+
+        // Half of the fee goes to the protocol, only the other half grows the positions
+        let (shared_res, protocol) = shared.swap(1_000_000, true, 30, 5_000).unwrap();
+        assert_eq!(shared_res.fee, res.fee);
+        assert_eq!(protocol, res.fee / 2);
+        let growth = mul_div((res.fee - protocol) as u128, Q64, shared.liquidity, false).unwrap();
+        assert_eq!(shared.fee_growth_global_x, growth);
+    }
+
+    #[test]
+    fn ticks_are_removed_when_empty() {
+        let mut pool = pool_with_ranges(&[(-10, 10, 1_000)]);
+        assert_eq!(pool.ticks.len(), 2);
+        pool.update_tick(-10, -1_000, false).unwrap();
+        pool.update_tick(10, -1_000, true).unwrap();
+        assert!(pool.ticks.is_empty());
+    }
+}
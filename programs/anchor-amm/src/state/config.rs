@@ -1,8 +1,114 @@
-use anchor_lang::prelude::*;
+use anchor_lang::{prelude::*, Discriminator};
 
-use crate::{BOOL_L, OPTION_L, PUBKEY_L, U16_L, U64_L, U8_L};
+use crate::{
+    errors::AmmError,
+    helpers::{concentrated::Q64, oracle::sqrt, ConstantProduct, Curve, StableSwap, WeightedProduct},
+    BOOL_L, I64_L, MAX_ALLOWED_HOOKS, MAX_AMP, MAX_TICK_SPACING, MIN_FEE_DELAY, MIN_WEIGHT, OPTION_L, PAUSE_DEPOSIT, PAUSE_SWAP, PAUSE_WITHDRAW, PUBKEY_L, U128_L, U16_L, U64_L, U8_L,
+};
 
+/// Curve Type
+///
+/// Invariant used by the pool to price swaps and liquidity. It is picked at initialize time
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CurveType {
+    ConstantProduct,
+    // Amplified invariant for pegged pairs. `amp` flattens the curve around the peg
+    StableSwap { amp: u64 },
+    // Balancer-style weighted product. Weights are in basis points and add up to 10000
+    Weighted { weight_x: u16, weight_y: u16 },
+    // Liquidity provided in tick ranges through positions instead of LP tokens
+    Concentrated { tick_spacing: u16 },
+}
+
+impl CurveType {
+    // Discriminator plus the largest variant
+    pub const LEN: usize = U8_L + U64_L;
+
+    /// Validate
+    ///
+    /// Check the curve parameters before they get stored in the config
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            CurveType::ConstantProduct => Ok(()),
+            CurveType::StableSwap { amp } => {
+                require!(*amp > 0 && *amp <= MAX_AMP, AmmError::InvalidCurveParameters);
+                Ok(())
+            }
+            CurveType::Weighted { weight_x, weight_y } => {
+                require!(
+                    *weight_x >= MIN_WEIGHT
+                        && *weight_y >= MIN_WEIGHT
+                        && *weight_x as u32 + *weight_y as u32 == 10000,
+                    AmmError::InvalidWeights
+                );
+                Ok(())
+            }
+            CurveType::Concentrated { tick_spacing } => {
+                require!(*tick_spacing > 0 && *tick_spacing <= MAX_TICK_SPACING, AmmError::InvalidCurveParameters);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Outstanding Loan
+///
+/// Flash loan borrowed from one of the vaults. The vault has to get back to `vault_balance + fee`
+/// before the end of the transaction
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutstandingLoan {
+    pub is_x: bool,
+    pub amount: u64,
+    pub fee: u64,
+    // Vault balance before the tokens left it
+    pub vault_balance: u64,
+}
+
+impl OutstandingLoan {
+    pub const LEN: usize = BOOL_L + U64_L*3;
+}
+
+/// Legacy Config
+///
+/// Layout of the configs created while the vaults belonged to the global `[b"auth"]` PDA. They keep
+/// the Config discriminator and are converted to the current layout by migrate_vaults
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct LegacyConfig {
+    pub seed: u64,
+    pub authority: Option<Pubkey>,
+    pub x_mint: Pubkey,
+    pub y_mint: Pubkey,
+    pub fee: u16,
+    pub locked: bool,
+    pub auth_bump: u8,
+    pub config_bump: u8,
+    pub lp_bump: u8,
+}
+
+impl LegacyConfig {
+    pub const INIT_SPACE: usize = 8 + U64_L + OPTION_L + PUBKEY_L*3 + U16_L + BOOL_L + U8_L*3;
+
+    /// Load
+    ///
+    /// Read a config account in the legacy layout. Configs in the current layout are already migrated
+    pub fn load(info: &AccountInfo) -> Result<Self> {
+        require_keys_eq!(*info.owner, crate::ID, AmmError::InvalidPool);
 
+        let data = info.try_borrow_data()?;
+        require!(data.len() == Self::INIT_SPACE, AmmError::AlreadyMigrated);
+        require!(data[..8] == Config::DISCRIMINATOR, ErrorCode::AccountDiscriminatorMismatch);
+
+        Self::deserialize(&mut &data[8..]).map_err(|_| error!(ErrorCode::AccountDidNotDeserialize))
+    }
+
+    /// Config Key
+    ///
+    /// Address of the config, derived from its seed like in Initialize
+    pub fn config_key(&self) -> Result<Pubkey> {
+        Pubkey::create_program_address(&[b"config", self.seed.to_le_bytes().as_ref(), &[self.config_bump]], &crate::ID)
+            .map_err(|_| error!(AmmError::InvalidPool))
+    }
+}
 
 /// Config struct will save most of the important information for the LP
 #[account]
@@ -11,13 +117,69 @@ pub struct Config {
     pub seed: u64,
     // Optioanl public key which will have the right to change the configuration 
     pub authority: Option<Pubkey>,
+    // Authority proposed by the current one. It only takes over once it accepts
+    pub pending_authority: Option<Pubkey>,
     // Public keys for the X and Y accounts containing relevant information of the Token Account
     pub x_mint: Pubkey,
     pub y_mint: Pubkey,
     // How much is going to cost to the users to utilize this LP
     pub fee: u16,
+    // Share of the swap fee (basis points of the fee) which goes to the protocol instead of the LPs
+    pub protocol_fee: u16,
+    // Owner of the token accounts receiving the protocol fees
+    pub treasury: Pubkey,
+    // Protocol fees accrued in the vaults and not collected yet. They don't belong to the LPs
+    pub protocol_fees_x: u64,
+    pub protocol_fees_y: u64,
+    // Fee proposed by the authority and the time it can be applied. Changes are delayed
+    // by `fee_delay` seconds so LPs and traders can see them coming
+    pub pending_fee: Option<u16>,
+    pub fee_eta: i64,
+    pub fee_delay: i64,
+    // Protocol fee and treasury proposed by the authority. They wait for the same `fee_delay`,
+    // as the protocol fee changes the LP share of every swap
+    pub pending_protocol_fee: Option<u16>,
+    pub pending_treasury: Pubkey,
+    pub protocol_fee_eta: i64,
     // Variable to allow or lock the LP  
     pub locked: bool,
+    // Pause bits per operation (PAUSE_SWAP, PAUSE_DEPOSIT, PAUSE_WITHDRAW)
+    pub paused: u8,
+    // If true, withdrawals stay open while swaps are paused, so LPs can always exit during an incident
+    pub withdraw_on_swap_pause: bool,
+    // Curve used to calculate swaps, deposits and withdrawals
+    pub curve_type: CurveType,
+    // Cumulative statistics of the pool, so dashboards don't need to replay every transaction.
+    // Volume and fees are counted per token in its own units
+    pub volume_x: u128,
+    pub volume_y: u128,
+    pub fees_x: u128,
+    pub fees_y: u128,
+    pub swap_count: u64,
+    // Slot of the last swap, deposit or withdraw
+    pub last_update_slot: u64,
+    // Time weighted price accumulators (Q64.64 price * seconds, wrapping), like Uniswap v2.
+    // price_x is the price of X in Y (reserve_y / reserve_x) and price_y the opposite
+    pub price_x_cumulative: u128,
+    pub price_y_cumulative: u128,
+    // Geometric mean of the reserves, sqrt(reserve_x * reserve_y), times seconds
+    pub liquidity_cumulative: u128,
+    pub last_price_timestamp: i64,
+    // Transfer hook programs allowed for Token-2022 mints of the pool, unused slots are the default key
+    pub allowed_hooks: [Pubkey; MAX_ALLOWED_HOOKS],
+    // Flash loan fee in basis points of the borrowed amount. It stays in the vault, so it goes to the LPs
+    pub flash_fee: u16,
+    // Flash loan borrowed and not repaid yet, it only lives between flash_borrow and flash_repay
+    pub flash_loan: Option<OutstandingLoan>,
+    // Reentrancy guard, set while a flash swap hands control to the borrower program
+    pub in_callback: bool,
+    // Tokens in the vaults which belong to the long-term orders, unsold input and proceeds not
+    // withdrawn yet. Like the protocol fees, they don't belong to the LPs
+    pub twamm_balance_x: u64,
+    pub twamm_balance_y: u64,
+    // True once the pool keeps its tokens in the vaults of its own `auth`. New pools start like that,
+    // pools created before get it from migrate_vaults
+    pub migrated: bool,
     // We save the bumps to perform better the PDA seed discovery 
     pub auth_bump: u8,
     pub config_bump: u8,
@@ -25,7 +187,10 @@ pub struct Config {
 }
 
 impl Config {
-    pub const INIT_SPACE: usize = 8 + U64_L + OPTION_L + PUBKEY_L*3 + U16_L + BOOL_L + U8_L*3;
+    pub const INIT_SPACE: usize = 8 + U64_L + (OPTION_L + PUBKEY_L)*2 + PUBKEY_L*2 + U16_L + U16_L + PUBKEY_L + U64_L*2 + OPTION_L + U16_L + I64_L*2
+        + OPTION_L + U16_L + PUBKEY_L + I64_L + BOOL_L + U8_L + BOOL_L + CurveType::LEN
+        + U128_L*4 + U64_L*2 + U128_L*3 + I64_L + PUBKEY_L*MAX_ALLOWED_HOOKS
+        + U16_L + OPTION_L + OutstandingLoan::LEN + BOOL_L + U64_L*2 + BOOL_L + U8_L*3;
 
     pub fn init(
         &mut self,
@@ -34,18 +199,355 @@ impl Config {
         x_mint: Pubkey,
         y_mint: Pubkey,
         fee: u16,
+        fee_delay: i64,
+        curve_type: CurveType,
         auth_bump: u8,
         config_bump: u8,
         lp_bump: u8,
     ) {
         self.seed = seed;
         self.authority = authority;
+        self.pending_authority = None;
         self.x_mint = x_mint;
         self.y_mint = y_mint;
         self.fee = fee;
+        self.protocol_fee = 0;
+        self.treasury = Pubkey::default();
+        self.protocol_fees_x = 0;
+        self.protocol_fees_y = 0;
+        self.pending_fee = None;
+        self.fee_eta = 0;
+        self.fee_delay = fee_delay;
+        self.pending_protocol_fee = None;
+        self.pending_treasury = Pubkey::default();
+        self.protocol_fee_eta = 0;
         self.locked = false;
+        self.paused = 0;
+        self.withdraw_on_swap_pause = false;
+        self.curve_type = curve_type;
+        self.volume_x = 0;
+        self.volume_y = 0;
+        self.fees_x = 0;
+        self.fees_y = 0;
+        self.swap_count = 0;
+        self.last_update_slot = 0;
+        self.price_x_cumulative = 0;
+        self.price_y_cumulative = 0;
+        self.liquidity_cumulative = 0;
+        self.last_price_timestamp = 0;
+        self.allowed_hooks = [Pubkey::default(); MAX_ALLOWED_HOOKS];
+        self.flash_fee = 0;
+        self.flash_loan = None;
+        self.in_callback = false;
+        self.twamm_balance_x = 0;
+        self.twamm_balance_y = 0;
+        self.migrated = true;
         self.auth_bump = auth_bump;
         self.config_bump = config_bump; 
         self.lp_bump = lp_bump; 
     }
-} 
\ No newline at end of file
+
+    /// Migrate
+    ///
+    /// Fill a config grown from the legacy layout. The fields the legacy layout didn't have start
+    /// like in a new pool
+    pub fn migrate(&mut self, legacy: &LegacyConfig, auth_bump: u8) {
+        self.init(
+            legacy.seed,
+            legacy.authority,
+            legacy.x_mint,
+            legacy.y_mint,
+            legacy.fee,
+            MIN_FEE_DELAY,
+            CurveType::ConstantProduct,
+            auth_bump,
+            legacy.config_bump,
+            legacy.lp_bump,
+        );
+        self.locked = legacy.locked;
+    }
+
+    /// Curve
+    ///
+    /// Build the curve configured for this pool from the current balances and LP supply
+    pub fn curve(&self, balance_x: u64, balance_y: u64, lp_supply: u64) -> Result<Box<dyn Curve>> {
+        let curve: Box<dyn Curve> = match self.curve_type {
+            CurveType::ConstantProduct => Box::new(
+                ConstantProduct::init(
+                    balance_x,
+                    balance_y,
+                    lp_supply,
+                    self.fee,
+                    Some(6), // Assuming 6 decimal precision for calculations
+                )
+                .map_err(AmmError::from)?,
+            ),
+            CurveType::StableSwap { amp } => Box::new(
+                StableSwap::init(
+                    balance_x,
+                    balance_y,
+                    lp_supply,
+                    self.fee,
+                    amp,
+                    Some(6),
+                )
+                .map_err(AmmError::from)?,
+            ),
+            CurveType::Weighted { weight_x, weight_y } => Box::new(
+                WeightedProduct::init(
+                    balance_x,
+                    balance_y,
+                    lp_supply,
+                    self.fee,
+                    weight_x,
+                    weight_y,
+                    Some(6),
+                )
+                .map_err(AmmError::from)?,
+            ),
+            // Concentrated pools can't be priced from the vault balances, they use their own instructions
+            CurveType::Concentrated { .. } => return err!(AmmError::UnsupportedCurve),
+        };
+
+        Ok(curve)
+    }
+
+    /// Reserves
+    ///
+    /// Vault balances which belong to the LPs, this is without the protocol fees not collected yet
+    /// and without the tokens of the long-term orders
+    pub fn reserves(&self, vault_x: u64, vault_y: u64) -> (u64, u64) {
+        (
+            vault_x.saturating_sub(self.protocol_fees_x).saturating_sub(self.twamm_balance_x),
+            vault_y.saturating_sub(self.protocol_fees_y).saturating_sub(self.twamm_balance_y),
+        )
+    }
+
+    /// Accrue Protocol Fee
+    ///
+    /// Book the protocol share of a swap fee. The fee is always paid in the deposited token
+    pub fn accrue_protocol_fee(&mut self, is_x: bool, fee: u64) -> Result<()> {
+        let amount = (fee as u128)
+            .checked_mul(self.protocol_fee as u128)
+            .ok_or(AmmError::Overflow)?
+            / 10000;
+        let amount = u64::try_from(amount).map_err(|_| AmmError::Overflow)?;
+
+        self.book_protocol_fee(is_x, amount)
+    }
+
+    /// Book Protocol Fee
+    ///
+    /// Add an amount already split from the swap fee to the protocol fees, for pools which take the
+    /// protocol share themselves like the concentrated ones
+    pub fn book_protocol_fee(&mut self, is_x: bool, amount: u64) -> Result<()> {
+        match is_x {
+            true => self.protocol_fees_x = self.protocol_fees_x.checked_add(amount).ok_or(AmmError::Overflow)?,
+            false => self.protocol_fees_y = self.protocol_fees_y.checked_add(amount).ok_or(AmmError::Overflow)?,
+        }
+
+        Ok(())
+    }
+
+    /// Record Swap
+    ///
+    /// Add a swap to the pool statistics. The fee is paid in the deposited token
+    pub fn record_swap(&mut self, is_x_to_y: bool, deposited: u64, withdrawn: u64, fee: u64) -> Result<()> {
+        let (amount_x, amount_y) = match is_x_to_y {
+            true => (deposited, withdrawn),
+            false => (withdrawn, deposited),
+        };
+
+        self.volume_x = self.volume_x.saturating_add(amount_x as u128);
+        self.volume_y = self.volume_y.saturating_add(amount_y as u128);
+
+        match is_x_to_y {
+            true => self.fees_x = self.fees_x.saturating_add(fee as u128),
+            false => self.fees_y = self.fees_y.saturating_add(fee as u128),
+        }
+
+        self.swap_count = self.swap_count.saturating_add(1);
+
+        self.touch()
+    }
+
+    /// Touch
+    ///
+    /// Save the slot of the last operation on the pool
+    pub fn touch(&mut self) -> Result<()> {
+        self.last_update_slot = Clock::get()?.slot;
+
+        Ok(())
+    }
+
+    /// Update Price Accumulators
+    ///
+    /// Add the price of the reserves times the seconds elapsed since the last update. It has to be
+    /// called before the reserves change, so the price used is the one that held during that time.
+    /// Consumers take the difference of two readings and divide it by the time between them
+    pub fn update_price_accumulators(&mut self, reserve_x: u64, reserve_y: u64) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let elapsed = now.saturating_sub(self.last_price_timestamp);
+
+        // An empty pool has no price, we only move the timestamp forward
+        if elapsed > 0 && self.last_price_timestamp > 0 && reserve_x > 0 && reserve_y > 0 {
+            let price_x = ((reserve_y as u128) * Q64) / reserve_x as u128;
+            let price_y = ((reserve_x as u128) * Q64) / reserve_y as u128;
+            let liquidity = sqrt(reserve_x as u128 * reserve_y as u128);
+
+            // Overflow is expected and fine, consumers only use differences
+            self.price_x_cumulative = self.price_x_cumulative.wrapping_add(price_x.wrapping_mul(elapsed as u128));
+            self.price_y_cumulative = self.price_y_cumulative.wrapping_add(price_y.wrapping_mul(elapsed as u128));
+            self.liquidity_cumulative = self.liquidity_cumulative.wrapping_add(liquidity.wrapping_mul(elapsed as u128));
+        }
+
+        self.last_price_timestamp = now;
+
+        Ok(())
+    }
+
+    /// Flash Fee Amount
+    ///
+    /// Fee charged for borrowing `amount`, rounded up so small loans can't skip it
+    pub fn flash_fee_amount(&self, amount: u64) -> Result<u64> {
+        let fee = (amount as u128)
+            .checked_mul(self.flash_fee as u128)
+            .ok_or(AmmError::Overflow)?
+            .div_ceil(10000);
+
+        u64::try_from(fee).map_err(|_| error!(AmmError::Overflow))
+    }
+
+    /// Check Idle
+    ///
+    /// The vaults don't match the pool accounting while a flash loan is out or a flash swap
+    /// callback is running, so nothing else can touch the pool until they finish
+    pub fn check_idle(&self) -> Result<()> {
+        require!(self.flash_loan.is_none(), AmmError::FlashLoanActive);
+        require!(!self.in_callback, AmmError::ReentrancyDetected);
+
+        Ok(())
+    }
+
+    /// Swaps Paused
+    ///
+    /// Swaps are stopped by the global lock or by their own pause bit
+    pub fn swaps_paused(&self) -> bool {
+        self.locked || self.paused & PAUSE_SWAP != 0
+    }
+
+    /// Deposits Paused
+    pub fn deposits_paused(&self) -> bool {
+        self.locked || self.paused & PAUSE_DEPOSIT != 0
+    }
+
+    /// Withdrawals Paused
+    ///
+    /// Same as the others, unless the pool policy keeps withdrawals open while swaps are paused
+    pub fn withdrawals_paused(&self) -> bool {
+        if self.withdraw_on_swap_pause && self.swaps_paused() {
+            return false;
+        }
+
+        self.locked || self.paused & PAUSE_WITHDRAW != 0
+    }
+
+    /// Tick Spacing
+    ///
+    /// Spacing between usable ticks, only available for concentrated pools
+    pub fn tick_spacing(&self) -> Result<u16> {
+        match self.curve_type {
+            CurveType::Concentrated { tick_spacing } => Ok(tick_spacing),
+            _ => err!(AmmError::UnsupportedCurve),
+        }
+    }
+} 
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::prelude::*;
+
+    use anchor_lang::Discriminator;
+
+    use crate::{errors::AmmError, Config, CurveType, LegacyConfig, OutstandingLoan, MIN_FEE_DELAY};
+
+    fn config() -> Config {
+        let mut config = Config::try_deserialize_unchecked(&mut &[0u8; Config::INIT_SPACE][..]).unwrap();
+        config.init(0, None, Pubkey::default(), Pubkey::default(), 30, 0, CurveType::ConstantProduct, 0, 0, 0);
+        config
+    }
+
+    #[test]
+    fn busy_pool_is_not_idle() {
+        let mut config = config();
+        assert!(config.check_idle().is_ok());
+
+        // The runtime stops a borrower program calling back into the pool, this is what is left if it didn't
+        config.in_callback = true;
+        assert_eq!(config.check_idle().unwrap_err(), error!(AmmError::ReentrancyDetected));
+
+        config.in_callback = false;
+        config.flash_loan = Some(OutstandingLoan { is_x: true, amount: 1, fee: 0, vault_balance: 1 });
+        assert_eq!(config.check_idle().unwrap_err(), error!(AmmError::FlashLoanActive));
+    }
+
+    #[test]
+    fn weights_must_add_up() {
+        let weighted = |weight_x, weight_y| CurveType::Weighted { weight_x, weight_y }.validate();
+        assert!(weighted(8_000, 2_000).is_ok());
+
+        // Weights which don't add up to 100%, or are below the minimum, get their own error
+        assert_eq!(weighted(8_000, 1_000).unwrap_err(), error!(AmmError::InvalidWeights));
+        assert_eq!(weighted(0, 10_000).unwrap_err(), error!(AmmError::InvalidWeights));
+    }
+
+    fn legacy_data() -> Vec<u8> {
+        let legacy = LegacyConfig {
+            seed: 7,
+            authority: Some(Pubkey::new_unique()),
+            x_mint: Pubkey::new_unique(),
+            y_mint: Pubkey::new_unique(),
+            fee: 30,
+            locked: true,
+            auth_bump: 254,
+            config_bump: 253,
+            lp_bump: 252,
+        };
+
+        let mut data = Config::DISCRIMINATOR.to_vec();
+        legacy.serialize(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn legacy_config_is_loaded_once() {
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = legacy_data();
+        assert_eq!(data.len(), LegacyConfig::INIT_SPACE);
+
+        let info = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &crate::ID, false, 0);
+        let legacy = LegacyConfig::load(&info).unwrap();
+
+        // The converted config keeps what the legacy one had, the rest starts like a new pool
+        let mut config = config();
+        config.migrate(&legacy, 9);
+        assert_eq!((config.seed, config.authority, config.fee, config.locked), (7, legacy.authority, 30, true));
+        assert_eq!((config.x_mint, config.y_mint), (legacy.x_mint, legacy.y_mint));
+        assert_eq!((config.auth_bump, config.config_bump, config.lp_bump), (9, 253, 252));
+        assert_eq!((config.fee_delay, config.curve_type, config.protocol_fee), (MIN_FEE_DELAY, CurveType::ConstantProduct, 0));
+        assert!(config.migrated);
+
+        // A config in the current layout was already migrated
+        let mut lamports = 0;
+        let mut data = vec![0u8; Config::INIT_SPACE];
+        data[..8].copy_from_slice(&Config::DISCRIMINATOR);
+        let info = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &crate::ID, false, 0);
+        assert_eq!(LegacyConfig::load(&info).unwrap_err(), error!(AmmError::AlreadyMigrated));
+
+        // And accounts of other programs are never legacy configs
+        let (mut lamports, mut data, owner) = (0, legacy_data(), Pubkey::new_unique());
+        let info = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &owner, false, 0);
+        assert_eq!(LegacyConfig::load(&info).unwrap_err(), error!(AmmError::InvalidPool));
+    }
+}
@@ -0,0 +1,28 @@
+use anchor_lang::prelude::*;
+
+use crate::{BOOL_L, PUBKEY_L, U128_L, U64_L, U8_L};
+
+/// LongTermOrder struct is an order selling its input at a constant rate until its expiry. The
+/// tokens sit in the pool vaults and are traded lazily by the pool Twamm account
+#[account]
+pub struct LongTermOrder {
+    // Config of the pool the order sells into
+    pub config: Pubkey,
+    // The only one allowed to close the order, it gets the proceeds, the unsold input and the rent
+    pub owner: Pubkey,
+    // Picked by the owner, so it can have several orders on the same pool
+    pub id: u64,
+    pub is_x_to_y: bool,
+    // Tokens sold per slot, Q64.64
+    pub sell_rate: u128,
+    // Earnings factor of its side when the order started selling
+    pub earnings_factor: u128,
+    // The order sells from `start_slot` up to `expiry`
+    pub start_slot: u64,
+    pub expiry: u64,
+    pub bump: u8,
+}
+
+impl LongTermOrder {
+    pub const INIT_SPACE: usize = 8 + PUBKEY_L*2 + U64_L + BOOL_L + U128_L*2 + U64_L*2 + U8_L;
+}
@@ -1,2 +1,21 @@
 pub mod config;
-pub use config::*;
\ No newline at end of file
+pub use config::*;
+
+pub mod concentrated;
+pub use concentrated::*;
+
+pub mod position;
+pub use position::*;
+pub mod registry;
+pub use registry::*;
+
+pub mod observations;
+pub use observations::*;
+
+pub mod order;
+pub use order::*;
+
+pub mod twamm;
+pub use twamm::*;
+pub mod long_term_order;
+pub use long_term_order::*;
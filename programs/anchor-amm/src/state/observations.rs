@@ -0,0 +1,127 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    errors::AmmError,
+    helpers::oracle::twap,
+    I64_L, PUBKEY_L, U128_L, U16_L, U8_L, VEC_L,
+};
+
+/// Observation struct is a snapshot of the pool price and liquidity accumulators
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Observation {
+    pub timestamp: i64,
+    // Config.price_x_cumulative at that time
+    pub price_cumulative: u128,
+    // Config.liquidity_cumulative at that time
+    pub liquidity_cumulative: u128,
+}
+
+impl Observation {
+    pub const LEN: usize = I64_L + U128_L*2;
+}
+
+/// Observations struct is a ring buffer of observations, so a TWAP can be read for any window
+/// still held in it without the consumer storing earlier values. Anyone can grow its capacity
+#[account]
+pub struct Observations {
+    // Config of the observed pool
+    pub config: Pubkey,
+    // Position of the newest observation
+    pub index: u16,
+    // Number of observations the account has room for
+    pub capacity: u16,
+    pub observations: Vec<Observation>,
+    pub bump: u8,
+}
+
+impl Observations {
+    /// Space
+    ///
+    /// Account size needed to hold `capacity` observations
+    pub fn space(capacity: u16) -> usize {
+        8 + PUBKEY_L + U16_L*2 + VEC_L + Observation::LEN * capacity as usize + U8_L
+    }
+
+    pub fn init(&mut self, config: Pubkey, bump: u8) {
+        self.config = config;
+        self.index = 0;
+        self.capacity = 1;
+        self.observations = Vec::with_capacity(1);
+        self.bump = bump;
+    }
+
+    /// Write
+    ///
+    /// Save a new observation, at most one per second. The ring only uses new slots once it
+    /// gets to its end, so observations stay sorted by time from the oldest one after a grow
+    pub fn write(&mut self, observation: Observation) {
+        let len = self.observations.len();
+
+        if len == 0 {
+            self.observations.push(observation);
+            self.index = 0;
+            return;
+        }
+
+        if self.observations[self.index as usize].timestamp >= observation.timestamp {
+            return;
+        }
+
+        let next = self.index as usize + 1;
+        if next < len {
+            self.observations[next] = observation;
+            self.index = next as u16;
+        } else if len < self.capacity as usize {
+            self.observations.push(observation);
+            self.index = next as u16;
+        } else {
+            self.observations[0] = observation;
+            self.index = 0;
+        }
+    }
+
+    /// TWAP
+    ///
+    /// Average price (Q64.64, X in Y) and liquidity over the `window` seconds before the newest observation
+    pub fn twap(&self, window: u32) -> Result<(u128, u128)> {
+        twap(&self.observations, self.index as usize, window).map_err(|e| AmmError::from(e).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Observation, Observations};
+
+    fn observations(capacity: u16) -> Observations {
+        Observations {
+            config: Default::default(),
+            index: 0,
+            capacity,
+            observations: vec![],
+            bump: 0,
+        }
+    }
+
+    fn at(timestamp: i64) -> Observation {
+        Observation { timestamp, price_cumulative: timestamp as u128, liquidity_cumulative: 0 }
+    }
+
+    #[test]
+    fn write_wraps_and_grows() {
+        let mut ring = observations(2);
+        ring.write(at(1));
+        ring.write(at(1)); // same second, ignored
+        ring.write(at(2));
+        ring.write(at(3));
+        assert_eq!(ring.observations, vec![at(3), at(2)]);
+        assert_eq!(ring.index, 0);
+
+        // After growing, the oldest slot keeps being overwritten until the end of the ring
+        ring.capacity = 3;
+        ring.write(at(4));
+        assert_eq!(ring.observations, vec![at(3), at(4)]);
+        ring.write(at(5));
+        assert_eq!(ring.observations, vec![at(3), at(4), at(5)]);
+        assert_eq!(ring.index, 2);
+    }
+}
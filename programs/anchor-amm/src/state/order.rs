@@ -0,0 +1,100 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::AmmError, BOOL_L, PUBKEY_L, SPOT_PRICE_PRECISION, U128_L, U64_L, U8_L};
+
+/// Order struct is a resting limit order on a pool. The input tokens wait in an escrow ATA owned
+/// by the order until a keeper fills it through the pool or the owner cancels it
+#[account]
+pub struct Order {
+    // Config of the pool the order is filled against
+    pub config: Pubkey,
+    // The only one allowed to cancel the order, it gets the output, the escrow back and the rent
+    pub owner: Pubkey,
+    // Picked by the owner, so it can have several orders on the same pool
+    pub id: u64,
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub is_x_to_y: bool,
+    // Tokens in the escrow, net of transfer fees
+    pub amount_in: u64,
+    // Spot price of X (X per Y, with SPOT_PRICE_PRECISION) which triggers the order. Selling X fills
+    // at or below it, selling Y at or above it
+    pub target_price: u128,
+    // Output of the escrow at the target price. Fees and price impact can't take a fill below it
+    pub min_amount_out: u64,
+    // Lamports paid to the keeper who fills the order, held by the order account on top of the rent
+    pub bounty: u64,
+    pub bump: u8,
+}
+
+impl Order {
+    pub const INIT_SPACE: usize = 8 + PUBKEY_L*4 + U64_L + BOOL_L + U64_L + U128_L + U64_L*2 + U8_L;
+
+    /// Amount Out At Target
+    ///
+    /// Output of the escrowed amount if it was traded exactly at the target price
+    pub fn amount_out_at_target(&self) -> Result<u64> {
+        let amount = match self.is_x_to_y {
+            true => (self.amount_in as u128)
+                .checked_mul(SPOT_PRICE_PRECISION)
+                .and_then(|amount| amount.checked_div(self.target_price)),
+            false => (self.amount_in as u128)
+                .checked_mul(self.target_price)
+                .map(|amount| amount / SPOT_PRICE_PRECISION),
+        }
+        .ok_or(AmmError::Overflow)?;
+
+        u64::try_from(amount).map_err(|_| error!(AmmError::Overflow))
+    }
+
+    /// Is Triggered
+    ///
+    /// True once the spot price of X has crossed the target in favour of the order
+    pub fn is_triggered(&self, spot_price_x: u128) -> bool {
+        match self.is_x_to_y {
+            true => spot_price_x <= self.target_price,
+            false => spot_price_x >= self.target_price,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Order;
+
+    fn order(is_x_to_y: bool) -> Order {
+        Order {
+            config: Default::default(),
+            owner: Default::default(),
+            id: 0,
+            input_mint: Default::default(),
+            output_mint: Default::default(),
+            is_x_to_y,
+            amount_in: 100,
+            target_price: 2_000_000,
+            min_amount_out: 0,
+            bounty: 0,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn triggers_on_the_right_side() {
+        // Selling X waits for Y to get cheap in X
+        assert!(!order(true).is_triggered(2_000_001));
+        assert!(order(true).is_triggered(2_000_000));
+        assert!(order(true).is_triggered(1_500_000));
+
+        // Selling Y waits for Y to get expensive in X
+        assert!(!order(false).is_triggered(1_999_999));
+        assert!(order(false).is_triggered(2_000_000));
+        assert!(order(false).is_triggered(2_500_000));
+    }
+
+    #[test]
+    fn amount_out_at_target() {
+        // 2 X per Y: 100 X buy 50 Y, and 100 Y buy 200 X
+        assert_eq!(order(true).amount_out_at_target().unwrap(), 50);
+        assert_eq!(order(false).amount_out_at_target().unwrap(), 200);
+    }
+}
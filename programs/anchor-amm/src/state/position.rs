@@ -0,0 +1,78 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    errors::AmmError,
+    helpers::concentrated::{mul_div, Q64},
+    I32_L, PUBKEY_L, U128_L, U64_L, U8_L,
+};
+
+/// Position struct replaces the LP tokens in concentrated pools. It tracks the liquidity
+/// a user provides between two ticks and the fees it has earned
+#[account]
+pub struct Position {
+    // Config of the pool this position belongs to
+    pub config: Pubkey,
+    // The only one allowed to modify or close the position
+    pub owner: Pubkey,
+    // Price range of the position
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    // Liquidity provided in the range
+    pub liquidity: u128,
+    // Fee growth inside the range the last time the position was touched
+    pub fee_growth_inside_x: u128,
+    pub fee_growth_inside_y: u128,
+    // Fees earned and not collected yet
+    pub fees_owed_x: u64,
+    pub fees_owed_y: u64,
+    pub bump: u8,
+}
+
+impl Position {
+    pub const INIT_SPACE: usize = 8 + PUBKEY_L*2 + I32_L*2 + U128_L*3 + U64_L*2 + U8_L;
+
+    pub fn init(
+        &mut self,
+        config: Pubkey,
+        owner: Pubkey,
+        tick_lower: i32,
+        tick_upper: i32,
+        bump: u8,
+    ) {
+        self.config = config;
+        self.owner = owner;
+        self.tick_lower = tick_lower;
+        self.tick_upper = tick_upper;
+        self.liquidity = 0;
+        self.fee_growth_inside_x = 0;
+        self.fee_growth_inside_y = 0;
+        self.fees_owed_x = 0;
+        self.fees_owed_y = 0;
+        self.bump = bump;
+    }
+
+    /// Update Fees
+    ///
+    /// Accrue the fees earned since the last update with the current fee growth inside the range
+    pub fn update_fees(&mut self, fee_growth_inside_x: u128, fee_growth_inside_y: u128) -> Result<()> {
+        let earned_x = mul_div(
+            fee_growth_inside_x.wrapping_sub(self.fee_growth_inside_x),
+            self.liquidity,
+            Q64,
+            false,
+        ).map_err(AmmError::from)?;
+        let earned_y = mul_div(
+            fee_growth_inside_y.wrapping_sub(self.fee_growth_inside_y),
+            self.liquidity,
+            Q64,
+            false,
+        ).map_err(AmmError::from)?;
+
+        self.fees_owed_x = self.fees_owed_x.saturating_add(u64::try_from(earned_x).unwrap_or(u64::MAX));
+        self.fees_owed_y = self.fees_owed_y.saturating_add(u64::try_from(earned_y).unwrap_or(u64::MAX));
+        self.fee_growth_inside_x = fee_growth_inside_x;
+        self.fee_growth_inside_y = fee_growth_inside_y;
+
+        Ok(())
+    }
+}
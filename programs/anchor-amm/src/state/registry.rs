@@ -0,0 +1,36 @@
+use anchor_lang::prelude::*;
+
+use crate::{PUBKEY_L, U16_L, U8_L};
+
+/// Pool Registry struct is the canonical entry of a pool. It's derived from the sorted mint pair
+/// and the fee tier, so anyone can find the pool of a pair without knowing its random seed
+#[account]
+pub struct PoolRegistry {
+    // Config of the registered pool
+    pub config: Pubkey,
+    // Sorted mints of the pool, x_mint < y_mint
+    pub x_mint: Pubkey,
+    pub y_mint: Pubkey,
+    // Fee tier of the pool
+    pub fee: u16,
+    pub bump: u8,
+}
+
+impl PoolRegistry {
+    pub const INIT_SPACE: usize = 8 + PUBKEY_L*3 + U16_L + U8_L;
+
+    pub fn init(
+        &mut self,
+        config: Pubkey,
+        x_mint: Pubkey,
+        y_mint: Pubkey,
+        fee: u16,
+        bump: u8,
+    ) {
+        self.config = config;
+        self.x_mint = x_mint;
+        self.y_mint = y_mint;
+        self.fee = fee;
+        self.bump = bump;
+    }
+}
@@ -0,0 +1,442 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    errors::AmmError,
+    helpers::{
+        concentrated::{mul_div, Q64},
+        twamm::{virtual_trade, VirtualTrade},
+    },
+    Config, LongTermOrder, MAX_TWAMM_EXPIRIES, PUBKEY_L, TWAMM_EARNINGS_PRECISION, TWAMM_INTERVAL, TWAMM_INTERVALS_PER_ORDER,
+    U128_L, U32_L, U64_L, U8_L, VEC_L,
+};
+
+/// Twamm Expiry
+///
+/// Long-term orders ending at the same slot. Their sell rates stop there, and the earnings factors
+/// are saved when the slot is crossed, so the orders can be settled any time later
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TwammExpiry {
+    pub slot: u64,
+    // Sell rates of the orders ending here
+    pub sell_rate_x: u128,
+    pub sell_rate_y: u128,
+    // Twamm earnings factors at `slot`, only set once it has been crossed
+    pub earnings_factor_x: u128,
+    pub earnings_factor_y: u128,
+    // Orders not closed yet, the expiry is removed with the last one
+    pub orders: u32,
+}
+
+impl TwammExpiry {
+    pub const LEN: usize = U64_L + U128_L*4 + U32_L;
+}
+
+/// Twamm struct holds the long-term orders of a pool. Their virtual trades are executed lazily, from
+/// `last_slot` up to the current slot, before anything else touches the reserves
+#[account]
+pub struct Twamm {
+    // Config of the pool
+    pub config: Pubkey,
+    // Tokens sold per slot by the active orders of each side, Q64.64
+    pub sell_rate_x: u128,
+    pub sell_rate_y: u128,
+    // Tokens bought per unit of sell rate (TWAMM_EARNINGS_PRECISION), X sellers earn Y and Y sellers
+    // earn X. They wrap, orders only use differences
+    pub earnings_factor_x: u128,
+    pub earnings_factor_y: u128,
+    // Virtual trades have been executed up to this slot
+    pub last_slot: u64,
+    // Sorted by slot
+    pub expiries: Vec<TwammExpiry>,
+    pub bump: u8,
+}
+
+impl Twamm {
+    pub const INIT_SPACE: usize = 8 + PUBKEY_L + U128_L*4 + U64_L + VEC_L + TwammExpiry::LEN * MAX_TWAMM_EXPIRIES + U8_L;
+
+    pub fn init(&mut self, config: Pubkey, slot: u64, bump: u8) {
+        self.config = config;
+        self.sell_rate_x = 0;
+        self.sell_rate_y = 0;
+        self.earnings_factor_x = 0;
+        self.earnings_factor_y = 0;
+        self.last_slot = slot;
+        self.expiries = Vec::with_capacity(MAX_TWAMM_EXPIRIES);
+        self.bump = bump;
+    }
+
+    /// Execute
+    ///
+    /// Run the virtual trades of the long-term orders up to the current slot, one period per expiry
+    /// crossed. Virtual trades are swaps, so nothing is sold while swaps are paused or the pool is
+    /// empty and `last_slot` stays behind. Expiries don't move, so once the pool trades again the slots
+    /// missed are sold in one trade per period, at the price of that moment, like any swap that size
+    pub fn execute(&mut self, config: &mut Config, vault_x: u64, vault_y: u64) -> Result<()> {
+        let slot = Clock::get()?.slot;
+        if slot <= self.last_slot || config.swaps_paused() {
+            return Ok(());
+        }
+
+        // Price accumulators use the reserves from before the virtual trades
+        let (reserve_x, reserve_y) = config.reserves(vault_x, vault_y);
+        config.update_price_accumulators(reserve_x, reserve_y)?;
+
+        while self.last_slot < slot {
+            let next = self
+                .expiries
+                .iter()
+                .map(|expiry| expiry.slot)
+                .find(|expiry| *expiry > self.last_slot)
+                .map_or(slot, |expiry| expiry.min(slot));
+
+            if self.sell_rate_x > 0 || self.sell_rate_y > 0 {
+                let (reserve_x, reserve_y) = config.reserves(vault_x, vault_y);
+                if reserve_x == 0 || reserve_y == 0 {
+                    break;
+                }
+
+                let sold_x = sold_amount(self.sell_rate_x, next - self.last_slot)?;
+                let sold_y = sold_amount(self.sell_rate_y, next - self.last_slot)?;
+                let trade = virtual_trade(sold_x, sold_y, reserve_x, reserve_y, config.fee).map_err(AmmError::from)?;
+
+                // Part of the fee of the curve leg stays in the vault for the protocol
+                if let Some((is_x_to_y, swap_result)) = &trade.swap {
+                    config.accrue_protocol_fee(*is_x_to_y, swap_result.fee)?;
+                    config.record_swap(*is_x_to_y, swap_result.deposited, swap_result.withdrawn, swap_result.fee)?;
+                }
+
+                // The input leaves the orders and the output joins them, both in the same vaults
+                config.twamm_balance_x = config.twamm_balance_x.saturating_add(trade.bought_x).saturating_sub(trade.sold_x);
+                config.twamm_balance_y = config.twamm_balance_y.saturating_add(trade.bought_y).saturating_sub(trade.sold_y);
+
+                self.book(&trade)?;
+            }
+
+            self.last_slot = next;
+            self.cross(next);
+        }
+
+        Ok(())
+    }
+
+    /// Add Order
+    ///
+    /// Start selling `sell_rate` per slot until `expiry`. Returns the earnings factor of its side
+    pub fn add_order(&mut self, is_x_to_y: bool, sell_rate: u128, expiry: u64) -> Result<u128> {
+        let index = match self.expiries.binary_search_by_key(&expiry, |entry| entry.slot) {
+            Ok(index) => index,
+            Err(index) => {
+                require!(self.expiries.len() < MAX_TWAMM_EXPIRIES, AmmError::TwammExpiriesFull);
+                self.expiries.insert(index, TwammExpiry { slot: expiry, ..Default::default() });
+                index
+            }
+        };
+
+        let entry = &mut self.expiries[index];
+        entry.orders = entry.orders.checked_add(1).ok_or(AmmError::Overflow)?;
+
+        match is_x_to_y {
+            true => {
+                entry.sell_rate_x = entry.sell_rate_x.checked_add(sell_rate).ok_or(AmmError::Overflow)?;
+                self.sell_rate_x = self.sell_rate_x.checked_add(sell_rate).ok_or(AmmError::Overflow)?;
+                Ok(self.earnings_factor_x)
+            }
+            false => {
+                entry.sell_rate_y = entry.sell_rate_y.checked_add(sell_rate).ok_or(AmmError::Overflow)?;
+                self.sell_rate_y = self.sell_rate_y.checked_add(sell_rate).ok_or(AmmError::Overflow)?;
+                Ok(self.earnings_factor_y)
+            }
+        }
+    }
+
+    /// Close Order
+    ///
+    /// Settle an order up to `last_slot`. Returns the proceeds and, if it hasn't expired yet, the
+    /// input it didn't sell. The order stops selling
+    pub fn close_order(&mut self, order: &LongTermOrder) -> Result<(u64, u64)> {
+        let index = self
+            .expiries
+            .binary_search_by_key(&order.expiry, |entry| entry.slot)
+            .map_err(|_| AmmError::InvalidLongTermOrder)?;
+
+        let expired = order.expiry <= self.last_slot;
+        let entry = self.expiries[index];
+
+        let (earnings_factor, unsold) = match (expired, order.is_x_to_y) {
+            (true, true) => (entry.earnings_factor_x, 0),
+            (true, false) => (entry.earnings_factor_y, 0),
+            (false, is_x_to_y) => {
+                let unsold = sold_amount(order.sell_rate, order.expiry - self.last_slot)?;
+                let entry = &mut self.expiries[index];
+
+                match is_x_to_y {
+                    true => {
+                        entry.sell_rate_x = entry.sell_rate_x.checked_sub(order.sell_rate).ok_or(AmmError::Underflow)?;
+                        self.sell_rate_x = self.sell_rate_x.checked_sub(order.sell_rate).ok_or(AmmError::Underflow)?;
+                        (self.earnings_factor_x, unsold)
+                    }
+                    false => {
+                        entry.sell_rate_y = entry.sell_rate_y.checked_sub(order.sell_rate).ok_or(AmmError::Underflow)?;
+                        self.sell_rate_y = self.sell_rate_y.checked_sub(order.sell_rate).ok_or(AmmError::Underflow)?;
+                        (self.earnings_factor_y, unsold)
+                    }
+                }
+            }
+        };
+
+        let proceeds = mul_div(
+            order.sell_rate,
+            earnings_factor.wrapping_sub(order.earnings_factor),
+            TWAMM_EARNINGS_PRECISION,
+            false,
+        )
+        .map_err(AmmError::from)?;
+        let proceeds = u64::try_from(proceeds).map_err(|_| AmmError::Overflow)?;
+
+        let entry = &mut self.expiries[index];
+        entry.orders = entry.orders.saturating_sub(1);
+        if entry.orders == 0 {
+            self.expiries.remove(index);
+        }
+
+        Ok((proceeds, unsold))
+    }
+
+    /// Book
+    ///
+    /// Share the output of a virtual trade between the orders of each side, pro rata of their sell rate
+    fn book(&mut self, trade: &VirtualTrade) -> Result<()> {
+        if self.sell_rate_x > 0 {
+            let earned = mul_div(trade.bought_y as u128, TWAMM_EARNINGS_PRECISION, self.sell_rate_x, false)
+                .map_err(AmmError::from)?;
+            self.earnings_factor_x = self.earnings_factor_x.wrapping_add(earned);
+        }
+
+        if self.sell_rate_y > 0 {
+            let earned = mul_div(trade.bought_x as u128, TWAMM_EARNINGS_PRECISION, self.sell_rate_y, false)
+                .map_err(AmmError::from)?;
+            self.earnings_factor_y = self.earnings_factor_y.wrapping_add(earned);
+        }
+
+        Ok(())
+    }
+
+    /// Cross
+    ///
+    /// Stop the orders ending at `slot` and save the earnings factors they will be settled with
+    fn cross(&mut self, slot: u64) {
+        let Some(entry) = self.expiries.iter_mut().find(|entry| entry.slot == slot) else {
+            return;
+        };
+
+        entry.earnings_factor_x = self.earnings_factor_x;
+        entry.earnings_factor_y = self.earnings_factor_y;
+        self.sell_rate_x = self.sell_rate_x.saturating_sub(entry.sell_rate_x);
+        self.sell_rate_y = self.sell_rate_y.saturating_sub(entry.sell_rate_y);
+    }
+}
+
+/// Order Expiry
+///
+/// End of an order starting at `start_slot` which sells over at least `slots`. It's the next multiple
+/// of the smallest interval (TWAMM_INTERVAL doubled as needed) the order fits in TWAMM_INTERVALS_PER_ORDER times
+pub fn order_expiry(start_slot: u64, slots: u64) -> Result<u64> {
+    let mut interval = TWAMM_INTERVAL;
+    while slots > interval.saturating_mul(TWAMM_INTERVALS_PER_ORDER) {
+        interval = interval.checked_mul(2).ok_or(AmmError::Overflow)?;
+    }
+
+    Ok(start_slot
+        .checked_add(slots)
+        .and_then(|slot| slot.checked_next_multiple_of(interval))
+        .ok_or(AmmError::Overflow)?)
+}
+
+/// Sold Amount
+///
+/// Tokens sold at `sell_rate` (Q64.64) over `slots`, rounded down
+pub fn sold_amount(sell_rate: u128, slots: u64) -> Result<u64> {
+    let amount = mul_div(sell_rate, slots as u128, Q64, false).map_err(AmmError::from)?;
+
+    u64::try_from(amount).map_err(|_| error!(AmmError::Overflow))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use anchor_lang::{
+        prelude::*,
+        solana_program::{entrypoint::SUCCESS, program_stubs},
+    };
+
+    use crate::{
+        helpers::{concentrated::Q64, twamm::VirtualTrade, ConstantProduct, LiquidityPair},
+        state::twamm::order_expiry,
+        Config, CurveType, LongTermOrder, Twamm, MAX_TWAMM_EXPIRIES, MAX_TWAMM_SLOTS, PAUSE_SWAP, TWAMM_INTERVAL,
+        TWAMM_INTERVALS_PER_ORDER,
+    };
+
+    // Slot returned by the Clock sysvar off-chain
+    static SLOT: AtomicU64 = AtomicU64::new(0);
+
+    struct ClockStub;
+
+    impl program_stubs::SyscallStubs for ClockStub {
+        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+            let clock = Clock {
+                slot: SLOT.load(Ordering::Relaxed),
+                unix_timestamp: 1_700_000_000,
+                ..Default::default()
+            };
+            unsafe { *(var_addr as *mut Clock) = clock };
+
+            SUCCESS
+        }
+    }
+
+    fn set_slot(slot: u64) {
+        SLOT.store(slot, Ordering::Relaxed);
+        program_stubs::set_syscall_stubs(Box::new(ClockStub));
+    }
+
+    fn config(fee: u16) -> Config {
+        let mut config = Config::try_deserialize_unchecked(&mut &[0u8; Config::INIT_SPACE][..]).unwrap();
+        config.init(0, None, Pubkey::default(), Pubkey::default(), fee, 0, CurveType::ConstantProduct, 0, 0, 0);
+        config
+    }
+
+    fn twamm() -> Twamm {
+        Twamm {
+            config: Default::default(),
+            sell_rate_x: 0,
+            sell_rate_y: 0,
+            earnings_factor_x: 0,
+            earnings_factor_y: 0,
+            last_slot: 0,
+            expiries: vec![],
+            bump: 0,
+        }
+    }
+
+    fn order(sell_rate: u128, earnings_factor: u128, expiry: u64) -> LongTermOrder {
+        LongTermOrder {
+            config: Default::default(),
+            owner: Default::default(),
+            id: 0,
+            is_x_to_y: true,
+            sell_rate,
+            earnings_factor,
+            start_slot: 0,
+            expiry,
+            bump: 0,
+        }
+    }
+
+    fn bought_y(amount: u64) -> VirtualTrade {
+        VirtualTrade { sold_x: 0, sold_y: 0, bought_x: 0, bought_y: amount, swap: None }
+    }
+
+    #[test]
+    fn orders_share_proceeds_pro_rata() {
+        let mut twamm = twamm();
+
+        // 1 and 3 X per slot, both ending at slot 100
+        let factor_a = twamm.add_order(true, Q64, 100).unwrap();
+        let factor_b = twamm.add_order(true, 3 * Q64, 100).unwrap();
+        assert_eq!(twamm.expiries.len(), 1);
+        assert_eq!(twamm.sell_rate_x, 4 * Q64);
+
+        twamm.book(&bought_y(4_000)).unwrap();
+        twamm.last_slot = 100;
+        twamm.cross(100);
+        assert_eq!(twamm.sell_rate_x, 0);
+
+        // Proceeds after the expiry don't change with later trades
+        twamm.book(&bought_y(1_000)).unwrap();
+
+        assert_eq!(twamm.close_order(&order(Q64, factor_a, 100)).unwrap(), (1_000, 0));
+        assert_eq!(twamm.close_order(&order(3 * Q64, factor_b, 100)).unwrap(), (3_000, 0));
+        assert!(twamm.expiries.is_empty());
+    }
+
+    #[test]
+    fn cancel_returns_unsold_input() {
+        let mut twamm = twamm();
+
+        let factor = twamm.add_order(true, 2 * Q64, 300).unwrap();
+        twamm.add_order(true, Q64, 150).unwrap();
+        assert_eq!(twamm.expiries.iter().map(|entry| entry.slot).collect::<Vec<_>>(), vec![150, 300]);
+
+        twamm.book(&bought_y(300)).unwrap();
+        twamm.last_slot = 100;
+
+        // 2/3 of the proceeds and 200 slots left at 2 per slot
+        assert_eq!(twamm.close_order(&order(2 * Q64, factor, 300)).unwrap(), (200, 400));
+        assert_eq!(twamm.sell_rate_x, Q64);
+        assert_eq!(twamm.expiries.len(), 1);
+    }
+
+    #[test]
+    fn expiries_are_bounded() {
+        assert_eq!(order_expiry(100, 1).unwrap(), 150);
+        assert_eq!(order_expiry(100, 600).unwrap(), 750);
+        // 601 slots don't fit in 4 intervals of 150, they use intervals of 300
+        assert_eq!(order_expiry(100, 601).unwrap(), 900);
+
+        // An order ending on intervals of some size started at most `slot`, so it ends within
+        // TWAMM_INTERVALS_PER_ORDER of them from there. Count every expiry still open at `slot`
+        for slot in [0u64, 150, 1_851_450, 7_777_777, 629_145_451] {
+            let mut expiries = vec![];
+            let mut interval = TWAMM_INTERVAL;
+            loop {
+                let last = order_expiry(slot, interval * TWAMM_INTERVALS_PER_ORDER).unwrap();
+                let mut expiry = (slot / interval + 1) * interval;
+                while expiry <= last {
+                    expiries.push(expiry);
+                    expiry += interval;
+                }
+
+                if interval * TWAMM_INTERVALS_PER_ORDER >= MAX_TWAMM_SLOTS {
+                    break;
+                }
+                interval *= 2;
+            }
+            expiries.sort_unstable();
+            expiries.dedup();
+
+            assert!(expiries.len() <= MAX_TWAMM_EXPIRIES, "{} expiries at slot {}", expiries.len(), slot);
+        }
+    }
+
+    #[test]
+    fn paused_slots_are_sold_at_resume() {
+        let mut config = config(30);
+        let mut twamm = twamm();
+
+        // 1 X per slot until slot 600, on top of 1_000_000 X and Y of reserves
+        twamm.add_order(true, Q64, 600).unwrap();
+        config.twamm_balance_x = 600;
+        let (vault_x, vault_y) = (1_000_600, 1_000_000);
+
+        // Nothing is sold while swaps are paused
+        config.paused = PAUSE_SWAP;
+        set_slot(300);
+        twamm.execute(&mut config, vault_x, vault_y).unwrap();
+        assert_eq!(twamm.last_slot, 0);
+        assert_eq!(config.twamm_balance_x, 600);
+
+        // Once resumed, the 300 slots missed are sold in a single swap at the current price
+        config.paused = 0;
+        twamm.execute(&mut config, vault_x, vault_y).unwrap();
+
+        let swap_result = ConstantProduct::init(1_000_000, 1_000_000, 0, 30, Some(6))
+            .unwrap()
+            .swap(LiquidityPair::TokenX, 300, 0)
+            .unwrap();
+        assert_eq!(twamm.last_slot, 300);
+        assert_eq!(config.swap_count, 1);
+        assert_eq!(config.twamm_balance_x, 300);
+        assert_eq!(config.twamm_balance_y, swap_result.withdrawn);
+    }
+}
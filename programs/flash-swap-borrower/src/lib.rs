@@ -0,0 +1,107 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        instruction::{AccountMeta, Instruction},
+        program::invoke,
+    },
+};
+use anchor_spl::token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked};
+
+declare_id!("6KU6T9oDPcRzKNUpywnYoczNKfZ4sQc6ZHH6J5GJMfoq");
+
+// First byte of the data forwarded by flash_swap, it picks what the callback does
+const PAY: u8 = 0;
+const CALL: u8 = 1;
+
+/// Flash Swap Borrower
+///
+/// Borrower program for the anchor-amm tests. The callback either pays the pool from the user ATA,
+/// or makes the call in the rest of the data, which is how the tests try to get back into the pool
+#[program]
+pub mod flash_swap_borrower {
+    use super::*;
+
+    pub fn flash_swap_callback<'info>(
+        ctx: Context<'_, '_, '_, 'info, FlashSwapCallback<'info>>,
+        _config: Pubkey,
+        _is_x_to_y: bool,
+        amount_in: u64,
+        _amount_out: u64,
+        _fee: u64,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        match data.split_first() {
+            Some((&PAY, _)) => ctx.accounts.pay(amount_in),
+            Some((&CALL, ix_data)) => call(ctx.remaining_accounts, ix_data),
+            _ => err!(BorrowerError::InvalidData),
+        }
+    }
+}
+
+/// Flash Swap Callback Context
+///
+/// The payer comes first, forwarded by the pool with its signature. The input side of the swap
+/// follows, then for CALL the program to call and its accounts
+#[derive(Accounts)]
+pub struct FlashSwapCallback<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, token::mint = mint, token::authority = payer)]
+    pub user_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, token::mint = mint)]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> FlashSwapCallback<'info> {
+    /// Pay
+    ///
+    /// Send the input of the swap, fee included, from the user ATA to the pool vault
+    fn pay(&self, amount: u64) -> Result<()> {
+        let cpi_accounts = TransferChecked {
+            from: self.user_ata.to_account_info(),
+            mint: self.mint.to_account_info(),
+            to: self.vault.to_account_info(),
+            authority: self.payer.to_account_info(),
+        };
+
+        let ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+
+        transfer_checked(ctx, amount, self.mint.decimals)
+    }
+}
+
+/// Call
+///
+/// Invoke the first remaining account with the others and `data`, keeping their signer and
+/// writable flags
+fn call<'info>(remaining_accounts: &[AccountInfo<'info>], data: &[u8]) -> Result<()> {
+    let (program, accounts) = remaining_accounts.split_first().ok_or(BorrowerError::InvalidData)?;
+
+    let ix = Instruction {
+        program_id: program.key(),
+        accounts: accounts
+            .iter()
+            .map(|account| AccountMeta {
+                pubkey: account.key(),
+                is_signer: account.is_signer,
+                is_writable: account.is_writable,
+            })
+            .collect(),
+        data: data.to_vec(),
+    };
+
+    let mut account_infos = accounts.to_vec();
+    account_infos.push(program.clone());
+
+    invoke(&ix, &account_infos).map_err(Into::into)
+}
+
+#[error_code]
+pub enum BorrowerError {
+    #[msg("Unknown flash swap callback data.")]
+    InvalidData,
+}
@@ -1,13 +1,14 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { AnchorAmm } from "../target/types/anchor_amm";
-import { Keypair, LAMPORTS_PER_SOL, PublicKey, SystemProgram, Transaction } from "@solana/web3.js";
+import { FlashSwapBorrower } from "../target/types/flash_swap_borrower";
+import { AccountMeta, Keypair, LAMPORTS_PER_SOL, PublicKey, SystemProgram, Transaction } from "@solana/web3.js";
 import { randomBytes } from "crypto"
 import { BN } from "bn.js";
 import { assert, expect } from "chai";
 
-import { commitment, newMintToAta } from './utils';
-import { ASSOCIATED_TOKEN_PROGRAM_ID, getAccount, getAssociatedTokenAddress, TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { commitment, INewMintToAta, newMintToAta, newTransferFeeMintToAta } from './utils';
+import { ASSOCIATED_TOKEN_PROGRAM_ID, createTransferInstruction, createWrappedNativeAccount, getAccount, getAssociatedTokenAddress, getMint, getOrCreateAssociatedTokenAccount, NATIVE_MINT, TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID, transfer } from "@solana/spl-token";
 
 
 describe("anchor-amm", () => {
@@ -42,17 +43,18 @@ describe("anchor-amm", () => {
   const seed = new BN(randomBytes(8));
 
   // PDAs
-  const [auth, authBump] = PublicKey.findProgramAddressSync([
-    Buffer.from("auth")],
-    program.programId
-  );
-  
   const [config, configBump] = PublicKey.findProgramAddressSync([
     Buffer.from("config"),
     seed.toBuffer().reverse()
   ], program.programId
   );
 
+  const [auth, authBump] = PublicKey.findProgramAddressSync([
+    Buffer.from("auth"),
+    config.toBuffer()],
+    program.programId
+  );
+
   // Three different mints. X, Y and LP. X and Y will get created during the tests and LP can be derived
   let xMint: PublicKey = undefined;
   let yMint: PublicKey = undefined;
@@ -86,6 +88,52 @@ describe("anchor-amm", () => {
     SystemProgram: SystemProgram.programId,
   }
 
+  // Creates a constant product pool with `amount` of each mint from the creator, and returns the accounts
+  // route_swap and split_swap expect for it in the remaining accounts
+  const newPool = async (aMint: PublicKey, bMint: PublicKey, fee: number, amount: number): Promise<AccountMeta[]> => {
+    const [poolXMint, poolYMint] = Buffer.compare(aMint.toBuffer(), bMint.toBuffer()) < 0 ? [aMint, bMint] : [bMint, aMint];
+    const poolSeed = new BN(randomBytes(8));
+    const [poolConfig] = PublicKey.findProgramAddressSync([Buffer.from("config"), poolSeed.toBuffer().reverse()], program.programId);
+    const [poolAuth] = PublicKey.findProgramAddressSync([Buffer.from("auth"), poolConfig.toBuffer()], program.programId);
+    const [observations] = PublicKey.findProgramAddressSync([Buffer.from("observations"), poolConfig.toBuffer()], program.programId);
+    const [twamm] = PublicKey.findProgramAddressSync([Buffer.from("twamm"), poolConfig.toBuffer()], program.programId);
+
+    const poolAccounts = {
+      payer: creatorPool.publicKey,
+      xMint: poolXMint,
+      yMint: poolYMint,
+      config: poolConfig,
+      xTokenProgram: TOKEN_PROGRAM_ID,
+      yTokenProgram: TOKEN_PROGRAM_ID,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    };
+
+    await program.methods.initialize(poolSeed, fee, new BN(3600), creatorPool.publicKey, { constantProduct: {} })
+      .accountsPartial(poolAccounts)
+      .signers([creatorPool])
+      .rpc()
+      .then(confirm);
+
+    await program.methods.deposit(new BN(1e6), new BN(amount), new BN(amount), expiration, false)
+      .accountsPartial(poolAccounts)
+      .signers([creatorPool])
+      .rpc()
+      .then(confirm);
+
+    return [
+      { pubkey: poolConfig, isSigner: false, isWritable: true },
+      { pubkey: poolXMint, isSigner: false, isWritable: false },
+      { pubkey: poolYMint, isSigner: false, isWritable: false },
+      { pubkey: await getAssociatedTokenAddress(poolXMint, poolAuth, true), isSigner: false, isWritable: true },
+      { pubkey: await getAssociatedTokenAddress(poolYMint, poolAuth, true), isSigner: false, isWritable: true },
+      { pubkey: observations, isSigner: false, isWritable: true },
+      { pubkey: poolAuth, isSigner: false, isWritable: false },
+      { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+      { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+      { pubkey: twamm, isSigner: false, isWritable: true },
+    ];
+  };
+
   it("should airdrop SOL to the main users!", async () => {
     let tx = new Transaction();
     tx.instructions = [
@@ -110,6 +158,11 @@ describe("anchor-amm", () => {
       [creatorPool, creatorPool].map(async (user) => await newMintToAta(connection, user)
       ));
 
+    // The pool requires sorted mints, x_mint < y_mint
+    if (Buffer.compare(xToken.mint.toBuffer(), yToken.mint.toBuffer()) > 0) {
+      [xToken, yToken] = [yToken, xToken];
+    }
+
     xMint = xToken.mint;
     yMint = yToken.mint;
 
@@ -149,8 +202,11 @@ describe("anchor-amm", () => {
   });
 
   it('should initialize the config account and the 2 empty vaults per X and Y tokens', async () => {
-    await program.methods.initialize(seed, 0, creatorPool.publicKey)
+    await program.methods.initialize(seed, 0, new BN(3600), creatorPool.publicKey, { constantProduct: {} })
       .accounts({
+        xTokenProgram: TOKEN_PROGRAM_ID,
+        yTokenProgram: TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
         payer: creatorPool.publicKey,
         xMint,
         yMint,
@@ -221,6 +277,9 @@ describe("anchor-amm", () => {
         expiration,
       )
         .accounts({
+          xTokenProgram: TOKEN_PROGRAM_ID,
+          yTokenProgram: TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
           payer: creatorPool.publicKey,
           config: config,
           xMint: xMint,
@@ -271,6 +330,9 @@ describe("anchor-amm", () => {
       expiration,
     )
       .accounts({
+        xTokenProgram: TOKEN_PROGRAM_ID,
+        yTokenProgram: TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
         payer: creatorPool.publicKey,
         config: config,
         xMint: xMint,
@@ -315,6 +377,8 @@ describe("anchor-amm", () => {
         true,
       )
         .accounts({
+          xTokenProgram: TOKEN_PROGRAM_ID,
+          yTokenProgram: TOKEN_PROGRAM_ID,
           payer: creatorPool.publicKey,
           config: config,
           xMint: xMint,
@@ -343,6 +407,8 @@ describe("anchor-amm", () => {
       expiration,
     )
       .accounts({
+        xTokenProgram: TOKEN_PROGRAM_ID,
+        yTokenProgram: TOKEN_PROGRAM_ID,
         payer: creatorPool.publicKey,
         config: config,
         xMint: xMint,
@@ -372,5 +438,645 @@ describe("anchor-amm", () => {
       assert.equal(yAtaBalance.value.amount.toString(), "1000000000");
   });   */
 
-});
+  describe("flash loans", () => {
+    const borrower = new Keypair();
+    const flashFee = 30;
+    const loan = 1e6;
+    // Rounded up, 30 bps of the loan
+    const loanFee = Math.ceil(loan * flashFee / 10000);
+
+    let xBorrowerAta: PublicKey = undefined;
+
+    const borrow = (amount: number) => program.methods.flashBorrow(new BN(amount), true)
+      .accountsPartial({
+        payer: borrower.publicKey,
+        xMint,
+        yMint,
+        config,
+        xTokenProgram: TOKEN_PROGRAM_ID,
+        yTokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([borrower]);
+
+    const repayIx = () => program.methods.flashRepay()
+      .accountsPartial({
+        payer: borrower.publicKey,
+        xMint,
+        yMint,
+        config,
+        xTokenProgram: TOKEN_PROGRAM_ID,
+        yTokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .instruction();
+
+    const vaultBalance = async (): Promise<bigint> => (await getAccount(connection, xVaultAta)).amount;
+
+    before(async () => {
+      // The pool needs liquidity to lend
+      await program.methods.deposit(new BN(1e6), new BN(depositNumber), new BN(depositNumber), expiration, false)
+        .accountsPartial({
+          payer: creatorPool.publicKey,
+          xMint,
+          yMint,
+          config,
+          xTokenProgram: TOKEN_PROGRAM_ID,
+          yTokenProgram: TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([creatorPool])
+        .rpc()
+        .then(confirm);
+
+      await program.methods.setFlashFee(flashFee)
+        .accountsPartial({
+          payer: creatorPool.publicKey,
+          config,
+        })
+        .signers([creatorPool])
+        .rpc()
+        .then(confirm);
+
+      // The borrower only holds the fee of one loan
+      let tx = new Transaction();
+      tx.instructions = [
+        SystemProgram.transfer({
+          fromPubkey: provider.publicKey,
+          toPubkey: borrower.publicKey,
+          lamports: 2 * LAMPORTS_PER_SOL,
+        }),
+      ];
+      await provider.sendAndConfirm(tx);
+
+      xBorrowerAta = (await getOrCreateAssociatedTokenAccount(connection, borrower, xMint, borrower.publicKey)).address;
+      await transfer(connection, creatorPool, xAta, xBorrowerAta, creatorPool, loanFee);
+    });
+
+    it('should lend X when the repayment is later in the transaction', async () => {
+      const vaultBefore = await vaultBalance();
+
+      await borrow(loan)
+        .postInstructions([await repayIx()])
+        .rpc()
+        .then(confirm)
+        .then(log);
+
+      // The loan came back with the fee, which stays with the LPs
+      assert.equal((await vaultBalance()).toString(), (vaultBefore + BigInt(loanFee)).toString());
+      assert.equal((await getAccount(connection, xBorrowerAta)).amount.toString(), "0");
+
+      const configAccount = await program.account.config.fetch(config);
+      assert.isNull(configAccount.flashLoan);
+    });
+
+    it('should not lend without a flash_repay in the transaction', async () => {
+      try {
+        await borrow(loan).rpc();
+
+        throw Error("It should fail and not arrive to this point");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "FlashRepayNotFound");
+      }
+    });
+
+    it('should reject a repayment short of the loan and the fee', async () => {
+      const vaultBefore = await vaultBalance();
+
+      // Half of the loan leaves the borrower before the repayment
+      const sink = (await getOrCreateAssociatedTokenAccount(connection, creatorPool, xMint, userPool.publicKey)).address;
+      const spend = createTransferInstruction(xBorrowerAta, sink, borrower.publicKey, loan / 2);
+
+      await transfer(connection, creatorPool, xAta, xBorrowerAta, creatorPool, loanFee);
+
+      try {
+        await borrow(loan)
+          .postInstructions([spend, await repayIx()])
+          .rpc();
+
+        throw Error("It should fail and not arrive to this point");
+      } catch (err) {
+        assert.include((err.logs ?? []).join("\n"), "insufficient funds");
+      }
+
+      // Nothing left the vault and the pool isn't stuck with the loan
+      assert.equal((await vaultBalance()).toString(), vaultBefore.toString());
+      const configAccount = await program.account.config.fetch(config);
+      assert.isNull(configAccount.flashLoan);
+    });
+  });
+
+  describe("flash swaps", () => {
+    const borrowerProgram = anchor.workspace.FlashSwapBorrower as Program<FlashSwapBorrower>;
+
+    // Callback data understood by the borrower program
+    const PAY = 0;
+    const CALL = 1;
+
+    const amountIn = 1e6;
+
+    // The pool got its liquidity in the flash loan tests. The creator sells X, the borrower
+    // program pays the vault from the creator ATA with the forwarded signature
+    const flashSwap = (data: Buffer, extraAccounts: AccountMeta[] = []) => program.methods
+      .flashSwap(new BN(amountIn), new BN(1), true, expiration, data)
+      .accountsPartial({
+        payer: creatorPool.publicKey,
+        xMint,
+        yMint,
+        config,
+        xTokenProgram: TOKEN_PROGRAM_ID,
+        yTokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts([
+        { pubkey: borrowerProgram.programId, isSigner: false, isWritable: false },
+        { pubkey: xMint, isSigner: false, isWritable: false },
+        { pubkey: xAta, isSigner: false, isWritable: true },
+        { pubkey: xVaultAta, isSigner: false, isWritable: true },
+        { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+        ...extraAccounts,
+      ])
+      .signers([creatorPool]);
+
+    it('should send the output first and get paid in the callback', async () => {
+      const xVaultBefore = (await getAccount(connection, xVaultAta)).amount;
+      const yBefore = (await getAccount(connection, yAta)).amount;
+
+      await flashSwap(Buffer.from([PAY]))
+        .rpc()
+        .then(confirm)
+        .then(log);
+
+      // The pool fee is 0, so the vault gets exactly the input
+      const xVaultAfter = (await getAccount(connection, xVaultAta)).amount;
+      assert.equal(xVaultAfter.toString(), (xVaultBefore + BigInt(amountIn)).toString());
+      assert.isTrue((await getAccount(connection, yAta)).amount > yBefore);
+
+      const configAccount = await program.account.config.fetch(config);
+      assert.isFalse(configAccount.inCallback);
+    });
+
+    it('should not let the callback get back into the pool', async () => {
+      const xVaultBefore = (await getAccount(connection, xVaultAta)).amount;
+      const yVaultBefore = (await getAccount(connection, yVaultAta)).amount;
+
+      // The callback tries a plain swap on the same pool instead of paying
+      const reentry = await program.methods.swap(new BN(amountIn), new BN(1), false, expiration, false)
+        .accountsPartial({
+          payer: creatorPool.publicKey,
+          xMint,
+          yMint,
+          config,
+          xTokenProgram: TOKEN_PROGRAM_ID,
+          yTokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .instruction();
+
+      try {
+        await flashSwap(
+          Buffer.concat([Buffer.from([CALL]), reentry.data]),
+          [{ pubkey: program.programId, isSigner: false, isWritable: false }, ...reentry.keys],
+        ).rpc();
+
+        throw Error("It should fail and not arrive to this point");
+      } catch (err) {
+        // The runtime refuses the call back into the pool before the in_callback guard
+        // (ReentrancyDetected) gets to run, either way the whole flash swap fails
+        const logs = (err.logs ?? []).join("\n");
+        assert.isTrue(
+          logs.includes("ReentrancyDetected") || logs.includes("reentrancy not allowed"),
+          logs,
+        );
+      }
+
+      // Nothing moved and the guard isn't left set
+      assert.equal((await getAccount(connection, xVaultAta)).amount.toString(), xVaultBefore.toString());
+      assert.equal((await getAccount(connection, yVaultAta)).amount.toString(), yVaultBefore.toString());
+
+      const configAccount = await program.account.config.fetch(config);
+      assert.isFalse(configAccount.inCallback);
+    });
+  });
+
+  describe("concentrated liquidity with transfer fee mints", () => {
+    // Both mints withhold 1% of every transfer
+    const transferFee = 100;
+    const [tickLower, tickUpper] = [-100, 100];
+    const liquidity = new BN(1e9);
+
+    const clSeed = new BN(randomBytes(8));
+    const [clConfig] = PublicKey.findProgramAddressSync([
+      Buffer.from("config"),
+      clSeed.toBuffer().reverse()
+    ], program.programId);
+    const [clAuth] = PublicKey.findProgramAddressSync([
+      Buffer.from("auth"),
+      clConfig.toBuffer()
+    ], program.programId);
+    const [clPosition] = PublicKey.findProgramAddressSync([
+      Buffer.from("position"),
+      clConfig.toBuffer(),
+      creatorPool.publicKey.toBuffer(),
+      new BN(tickLower).toTwos(32).toArrayLike(Buffer, "le", 4),
+      new BN(tickUpper).toTwos(32).toArrayLike(Buffer, "le", 4),
+    ], program.programId);
+
+    let clXMint: PublicKey = undefined;
+    let clYMint: PublicKey = undefined;
+    let clXAta: PublicKey = undefined;
+    let clYAta: PublicKey = undefined;
+    let clXVault: PublicKey = undefined;
+    let clYVault: PublicKey = undefined;
+
+    const balance = async (ata: PublicKey): Promise<bigint> =>
+      (await getAccount(connection, ata, undefined, TOKEN_2022_PROGRAM_ID)).amount;
+
+    const positionAccounts = () => ({
+      payer: creatorPool.publicKey,
+      xMint: clXMint,
+      yMint: clYMint,
+      config: clConfig,
+      position: clPosition,
+      xTokenProgram: TOKEN_2022_PROGRAM_ID,
+      yTokenProgram: TOKEN_2022_PROGRAM_ID,
+    });
+
+    const increase = (xMax: BN, yMax: BN) => program.methods.increaseLiquidity(liquidity, xMax, yMax, expiration)
+      .accountsPartial(positionAccounts())
+      .signers([creatorPool]);
+
+    before(async () => {
+      let [xToken, yToken] = await Promise.all(
+        [creatorPool, creatorPool].map(async (user) => await newTransferFeeMintToAta(connection, user, transferFee)
+      ));
+
+      if (Buffer.compare(xToken.mint.toBuffer(), yToken.mint.toBuffer()) > 0) {
+        [xToken, yToken] = [yToken, xToken];
+      }
+
+      [clXMint, clYMint, clXAta, clYAta] = [xToken.mint, yToken.mint, xToken.ata, yToken.ata];
+      clXVault = await getAssociatedTokenAddress(clXMint, clAuth, true, TOKEN_2022_PROGRAM_ID);
+      clYVault = await getAssociatedTokenAddress(clYMint, clAuth, true, TOKEN_2022_PROGRAM_ID);
+
+      // Price 1.0, the sqrt price is Q64.64
+      await program.methods.initializeConcentrated(clSeed, 30, new BN(3600), creatorPool.publicKey, 10, new BN(1).shln(64))
+        .accountsPartial({
+          payer: creatorPool.publicKey,
+          xMint: clXMint,
+          yMint: clYMint,
+          config: clConfig,
+          xTokenProgram: TOKEN_2022_PROGRAM_ID,
+          yTokenProgram: TOKEN_2022_PROGRAM_ID,
+        })
+        .signers([creatorPool])
+        .rpc()
+        .then(confirm);
+
+      await program.methods.openPosition(tickLower, tickUpper)
+        .accountsPartial({
+          payer: creatorPool.publicKey,
+          config: clConfig,
+        })
+        .signers([creatorPool])
+        .rpc()
+        .then(confirm);
+    });
+
+    it('should send enough for the vaults to hold the liquidity credited to the position', async () => {
+      const [xBefore, yBefore] = [await balance(clXAta), await balance(clYAta)];
+
+      await increase(new BN(1e9), new BN(1e9))
+        .rpc()
+        .then(confirm)
+        .then(log);
+
+      const [xSent, ySent] = [xBefore - await balance(clXAta), yBefore - await balance(clYAta)];
+      const [xVault, yVault] = [await balance(clXVault), await balance(clYVault)];
+
+      // The owner paid the transfer fee on top of what the vaults received
+      assert.isTrue(xVault > 0n && xSent > xVault);
+      assert.isTrue(yVault > 0n && ySent > yVault);
+
+      // As the only position, it can take all of its liquidity back out of the vaults
+      await program.methods.decreaseLiquidity(liquidity, new BN(0), new BN(0), expiration)
+        .accountsPartial(positionAccounts())
+        .signers([creatorPool])
+        .rpc()
+        .then(confirm)
+        .then(log);
+
+      const position = await program.account.position.fetch(clPosition);
+      assert.equal(position.liquidity.toString(), "0");
+    });
+
+    it('should check x_max and y_max against what the owner sends, transfer fee included', async () => {
+      const xVaultBefore = await balance(clXVault);
+
+      await increase(new BN(1e9), new BN(1e9))
+        .rpc()
+        .then(confirm);
+
+      // Exactly what the vault needs for the same liquidity, without the transfer fee
+      const xNet = (await balance(clXVault)) - xVaultBefore;
+
+      try {
+        await increase(new BN(xNet.toString()), new BN(1e9)).rpc();
+
+        throw Error("It should fail and not arrive to this point");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "SlippageExceeded");
+      }
+    });
+  });
+
+  describe("vault migration", () => {
+    // Loaded by the validator from tests/fixtures, a pool created while the vaults belonged to the global auth PDA
+    const legacyConfig = new PublicKey("EvoELcXp9gvEvQHA4KgUG21itiNQhYtjso5okDbgUsDG");
+    const legacyXMint = new PublicKey("74YJvCzQNNx8SWYduvNtgudXdstn3TpTaAkU8cbm2Dtd");
+    const legacyYMint = new PublicKey("BJMLePynqSidMumiGR3SFMc2dWBpZTBxtJDAUTbYxPGo");
+    const legacyLpMint = new PublicKey("9iFJ1tM2VxsyDN8aPfXzB453r5Mhsq7ZwM8vDAeGNc6G");
+    const legacyXVault = new PublicKey("2YatypWD5CaoNAeoNWpE1rT3a8eNnsd5vJs3Sw7MfA3s");
+    const legacyYVault = new PublicKey("2S1ASGGMmUbyd4NtRzyinqtUd5Bc56EDF4fpjLFyLGQp");
+
+    const [poolAuth] = PublicKey.findProgramAddressSync([
+      Buffer.from("auth"),
+      legacyConfig.toBuffer()
+    ], program.programId);
+
+    const migrate = () => program.methods.migrateVaults()
+      .accountsPartial({
+        payer: userPool.publicKey,
+        xMint: legacyXMint,
+        yMint: legacyYMint,
+        config: legacyConfig,
+        xTokenProgram: TOKEN_PROGRAM_ID,
+        yTokenProgram: TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([userPool]);
+
+    it('should let anyone move the whole legacy vaults to the pool vaults', async () => {
+      const [xLegacy, yLegacy] = [
+        (await getAccount(connection, legacyXVault)).amount,
+        (await getAccount(connection, legacyYVault)).amount,
+      ];
+
+      await migrate()
+        .rpc()
+        .then(confirm)
+        .then(log);
+
+      const xVault = await getAssociatedTokenAddress(legacyXMint, poolAuth, true, TOKEN_PROGRAM_ID);
+      const yVault = await getAssociatedTokenAddress(legacyYMint, poolAuth, true, TOKEN_PROGRAM_ID);
+      assert.equal((await getAccount(connection, xVault)).amount.toString(), xLegacy.toString());
+      assert.equal((await getAccount(connection, yVault)).amount.toString(), yLegacy.toString());
+      assert.equal((await getAccount(connection, legacyXVault)).amount.toString(), "0");
+      assert.equal((await getAccount(connection, legacyYVault)).amount.toString(), "0");
+
+      // The pool auth mints the LP tokens from now on
+      const lpMintAccount = await getMint(connection, legacyLpMint);
+      assert.equal(lpMintAccount.mintAuthority.toBase58(), poolAuth.toBase58());
 
+      // The config is in the current layout and keeps the legacy settings
+      const configAccount = await program.account.config.fetch(legacyConfig);
+      assert.isTrue(configAccount.migrated);
+      assert.equal(configAccount.fee, 30);
+      assert.equal(configAccount.xMint.toBase58(), legacyXMint.toBase58());
+      assert.equal(configAccount.yMint.toBase58(), legacyYMint.toBase58());
+    });
+
+    it('should only migrate a pool once', async () => {
+      try {
+        await migrate().rpc();
+
+        throw Error("It should fail and not arrive to this point");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "AlreadyMigrated");
+      }
+    });
+  });
+
+  describe("native SOL", () => {
+    const solSeed = new BN(randomBytes(8));
+    const amountIn = 1e6;
+
+    let tokenMint: PublicKey = undefined;
+    let solIsX: boolean = undefined;
+
+    const mints = () => solIsX ? { xMint: NATIVE_MINT, yMint: tokenMint } : { xMint: tokenMint, yMint: NATIVE_MINT };
+
+    const [solConfig] = PublicKey.findProgramAddressSync([
+      Buffer.from("config"),
+      solSeed.toBuffer().reverse()
+    ], program.programId);
+
+    const swapSol = (user: Keypair, wrapNative: boolean) => program.methods.swap(new BN(amountIn), new BN(1), solIsX, expiration, wrapNative)
+      .accountsPartial({
+        payer: user.publicKey,
+        ...mints(),
+        config: solConfig,
+        xTokenProgram: TOKEN_PROGRAM_ID,
+        yTokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([user]);
+
+    before(async () => {
+      tokenMint = (await newMintToAta(connection, creatorPool)).mint;
+      solIsX = Buffer.compare(NATIVE_MINT.toBuffer(), tokenMint.toBuffer()) < 0;
+
+      await program.methods.initialize(solSeed, 30, new BN(3600), creatorPool.publicKey, { constantProduct: {} })
+        .accountsPartial({
+          payer: creatorPool.publicKey,
+          ...mints(),
+          xTokenProgram: TOKEN_PROGRAM_ID,
+          yTokenProgram: TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([creatorPool])
+        .rpc()
+        .then(confirm);
+
+      // The creator holds wSOL already, so the liquidity goes in through the plain token path
+      await createWrappedNativeAccount(connection, creatorPool, creatorPool.publicKey, 2 * LAMPORTS_PER_SOL);
+
+      await program.methods.deposit(new BN(1e6), new BN(LAMPORTS_PER_SOL), new BN(LAMPORTS_PER_SOL), expiration, false)
+        .accountsPartial({
+          payer: creatorPool.publicKey,
+          ...mints(),
+          config: solConfig,
+          xTokenProgram: TOKEN_PROGRAM_ID,
+          yTokenProgram: TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([creatorPool])
+        .rpc()
+        .then(confirm);
+    });
+
+    it('should pay with the wSOL account and keep it open when wrap_native is false', async () => {
+      const wsolAta = await getAssociatedTokenAddress(NATIVE_MINT, creatorPool.publicKey);
+      const wsolBefore = (await getAccount(connection, wsolAta)).amount;
+      const lamportsBefore = await connection.getBalance(creatorPool.publicKey);
+
+      await swapSol(creatorPool, false)
+        .rpc()
+        .then(confirm)
+        .then(log);
+
+      // The input came out of the wSOL account, the wallet only paid the transaction fee
+      assert.equal((await getAccount(connection, wsolAta)).amount, wsolBefore - BigInt(amountIn));
+      assert.isTrue(lamportsBefore - await connection.getBalance(creatorPool.publicKey) < amountIn);
+    });
+
+    it('should wrap SOL from the wallet and close the wSOL account when wrap_native is true', async () => {
+      const wsolAta = await getAssociatedTokenAddress(NATIVE_MINT, userPool.publicKey);
+      const tokenAta = await getAssociatedTokenAddress(tokenMint, userPool.publicKey);
+      const lamportsBefore = await connection.getBalance(userPool.publicKey);
+
+      await swapSol(userPool, true)
+        .rpc()
+        .then(confirm)
+        .then(log);
+
+      // The input came from the wallet and the temporary wSOL account gave its rent back
+      assert.isTrue(lamportsBefore - await connection.getBalance(userPool.publicKey) >= amountIn);
+      assert.isNull(await connection.getAccountInfo(wsolAta));
+      assert.isTrue((await getAccount(connection, tokenAta)).amount > 0n);
+    });
+  });
+
+  describe("split swaps", () => {
+    const liquidity = 1e8;
+
+    let aToken: INewMintToAta = undefined;
+    let bToken: INewMintToAta = undefined;
+    let cheapPool: AccountMeta[] = undefined;
+    let dearPool: AccountMeta[] = undefined;
+
+    const splitSwap = (amounts: number[], minAmountOut: number, pools: AccountMeta[][]) =>
+      program.methods.splitSwap(amounts.map((amount) => new BN(amount)), new BN(minAmountOut), expiration)
+        .accountsPartial({
+          payer: creatorPool.publicKey,
+          inputMint: aToken.mint,
+          outputMint: bToken.mint,
+          inputTokenProgram: TOKEN_PROGRAM_ID,
+          outputTokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .remainingAccounts(pools.flat())
+        .signers([creatorPool]);
+
+    before(async () => {
+      [aToken, bToken] = await Promise.all(
+        [creatorPool, creatorPool].map(async (user) => await newMintToAta(connection, user)
+      ));
+
+      // Same pair, the registry allows one pool per fee tier
+      cheapPool = await newPool(aToken.mint, bToken.mint, 30, liquidity);
+      dearPool = await newPool(aToken.mint, bToken.mint, 100, liquidity);
+    });
+
+    it('should check min_amount_out against the output of all the pools together', async () => {
+      const bBefore = (await getAccount(connection, bToken.ata)).amount;
+
+      // Each leg returns less than 500_000, only both together reach the minimum
+      await splitSwap([5e5, 5e5], 9e5, [cheapPool, dearPool])
+        .rpc()
+        .then(confirm)
+        .then(log);
+
+      const received = (await getAccount(connection, bToken.ata)).amount - bBefore;
+      assert.isTrue(received >= 900_000n && received < 1_000_000n);
+
+      // Fees and slippage keep the total under the input
+      try {
+        await splitSwap([5e5, 5e5], 1e6, [cheapPool, dearPool]).rpc();
+
+        throw Error("It should fail and not arrive to this point");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "SlippageExceeded");
+      }
+    });
+
+    it('should not take the same pool twice', async () => {
+      try {
+        await splitSwap([5e5, 5e5], 1, [cheapPool, cheapPool]).rpc();
+
+        throw Error("It should fail and not arrive to this point");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "InvalidRoute");
+      }
+    });
+  });
+
+  describe("route swaps", () => {
+    const liquidity = 1e8;
+    const amountIn = 1e6;
+
+    let aToken: INewMintToAta = undefined;
+    let bToken: INewMintToAta = undefined;
+    let cToken: INewMintToAta = undefined;
+    let abPool: AccountMeta[] = undefined;
+    let bcPool: AccountMeta[] = undefined;
+
+    const routeSwap = (minAmountOut: number, pools: AccountMeta[][]) =>
+      program.methods.routeSwap(new BN(amountIn), new BN(minAmountOut), pools.length, expiration)
+        .accountsPartial({
+          payer: creatorPool.publicKey,
+          inputMint: aToken.mint,
+          outputMint: cToken.mint,
+          inputTokenProgram: TOKEN_PROGRAM_ID,
+          outputTokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .remainingAccounts(pools.flat())
+        .signers([creatorPool]);
+
+    const balance = async (token: INewMintToAta): Promise<bigint> => (await getAccount(connection, token.ata)).amount;
+
+    before(async () => {
+      [aToken, bToken, cToken] = await Promise.all(
+        [creatorPool, creatorPool, creatorPool].map(async (user) => await newMintToAta(connection, user)
+      ));
+
+      // A to B, then B to C
+      abPool = await newPool(aToken.mint, bToken.mint, 30, liquidity);
+      bcPool = await newPool(bToken.mint, cToken.mint, 30, liquidity);
+    });
+
+    it('should send the output of the last pool to the user', async () => {
+      const [aBefore, bBefore, cBefore] = [await balance(aToken), await balance(bToken), await balance(cToken)];
+
+      await routeSwap(9e5, [abPool, bcPool])
+        .rpc()
+        .then(confirm)
+        .then(log);
+
+      // B only moved between the vaults
+      assert.equal(aBefore - await balance(aToken), BigInt(amountIn));
+      assert.equal(await balance(bToken), bBefore);
+
+      const received = await balance(cToken) - cBefore;
+      assert.isTrue(received >= 900_000n && received < BigInt(amountIn));
+    });
+
+    it('should check min_amount_out against the output of the whole route', async () => {
+      // The first hop alone returns ~987_000, the fee and price impact of the second one take the route under it
+      try {
+        await routeSwap(980_000, [abPool, bcPool]).rpc();
+
+        throw Error("It should fail and not arrive to this point");
+      } catch (err) {
+        assert.equal(err.error.errorCode.code, "SlippageExceeded");
+      }
+    });
+
+    it('should reject duplicate pools and pools which don\'t follow the route', async () => {
+      for (const pools of [[abPool, abPool], [bcPool, abPool], [abPool]]) {
+        try {
+          await routeSwap(1, pools).rpc();
+
+          throw Error("It should fail and not arrive to this point");
+        } catch (err) {
+          assert.equal(err.error.errorCode.code, "InvalidRoute");
+        }
+      }
+    });
+  });
+
+});
@@ -1,6 +1,15 @@
 import { getProvider } from "@coral-xyz/anchor";
-import { createAccount, createMint, mintTo } from "@solana/spl-token";
-import { Commitment, Connection, Keypair, PublicKey } from "@solana/web3.js";
+import {
+  createAccount,
+  createInitializeMintInstruction,
+  createInitializeTransferFeeConfigInstruction,
+  createMint,
+  ExtensionType,
+  getMintLen,
+  mintTo,
+  TOKEN_2022_PROGRAM_ID,
+} from "@solana/spl-token";
+import { Commitment, Connection, Keypair, PublicKey, sendAndConfirmTransaction, SystemProgram, Transaction } from "@solana/web3.js";
 
 export const commitment: Commitment = "confirmed"; // processed, confirmed, finalized
 
@@ -40,4 +49,42 @@ export const newMintToAta = async (connection, minter: Keypair): Promise<INewMin
     mint,
     ata
   }
-}
\ No newline at end of file
+}
+
+// Token-2022 mint with the TransferFee extension, every transfer withholds `feeBasisPoints` in the receiver
+export const newTransferFeeMintToAta = async (connection: Connection, minter: Keypair, feeBasisPoints: number): Promise<INewMintToAta> => {
+  const mintKeypair = new Keypair();
+  const mintLen = getMintLen([ExtensionType.TransferFeeConfig]);
+  const lamports = await connection.getMinimumBalanceForRentExemption(mintLen);
+
+  const tx = new Transaction().add(
+    SystemProgram.createAccount({
+      fromPubkey: minter.publicKey,
+      newAccountPubkey: mintKeypair.publicKey,
+      space: mintLen,
+      lamports,
+      programId: TOKEN_2022_PROGRAM_ID,
+    }),
+    createInitializeTransferFeeConfigInstruction(
+      mintKeypair.publicKey,
+      minter.publicKey,
+      minter.publicKey,
+      feeBasisPoints,
+      BigInt(2e9),
+      TOKEN_2022_PROGRAM_ID,
+    ),
+    createInitializeMintInstruction(mintKeypair.publicKey, 6, minter.publicKey, null, TOKEN_2022_PROGRAM_ID),
+  );
+  await sendAndConfirmTransaction(connection, tx, [minter, mintKeypair], { commitment });
+
+  const mint = mintKeypair.publicKey;
+  const ata = await createAccount(connection, minter, mint, minter.publicKey, undefined, { commitment }, TOKEN_2022_PROGRAM_ID);
+  const signature = await mintTo(connection, minter, mint, ata, minter, 2e9, [], { commitment }, TOKEN_2022_PROGRAM_ID);
+
+  await confirmTx(connection, signature)
+
+  return {
+    mint,
+    ata
+  }
+}